@@ -0,0 +1,167 @@
+//!
+//! 一个简易的摘要及编码方案 (SHA-1, Base64)
+//!
+//! > 注意，SHA-1 已不再安全，仅用于协议兼容 (如 `WebSocket` 握手)
+//!
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+///
+/// 计算数据的 SHA-1 摘要
+///
+/// **Example:**
+/// ```
+/// mod crypto;
+///
+/// assert_eq!(crypto::sha1(b"abc")[..4], [0xa9, 0x99, 0x3e, 0x36]);
+/// ```
+///
+#[allow(dead_code)]
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut data = data.to_vec();
+    let bits = (data.len() as u64).wrapping_mul(8);
+    data.push(0x80);
+    while data.len() % 64 != 56 {
+        data.push(0);
+    }
+    data.extend_from_slice(&bits.to_be_bytes());
+
+    for block in data.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e]) {
+            *x = x.wrapping_add(y);
+        }
+    }
+
+    let mut res = [0u8; 20];
+    for (i, x) in h.iter().enumerate() {
+        res[i * 4..i * 4 + 4].copy_from_slice(&x.to_be_bytes());
+    }
+
+    res
+}
+
+///
+/// 对数据进行 Base64 编码 (标准字母表，带 `=` 填充)
+///
+/// **Example:**
+/// ```
+/// mod crypto;
+///
+/// assert_eq!(crypto::base64_encode(b"user:pass"), "dXNlcjpwYXNz");
+/// ```
+///
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+
+        res.push(BASE64[(n >> 18) as usize & 63] as char);
+        res.push(BASE64[(n >> 12) as usize & 63] as char);
+        res.push(if chunk.len() > 1 { BASE64[(n >> 6) as usize & 63] as char } else { '=' });
+        res.push(if chunk.len() > 2 { BASE64[n as usize & 63] as char } else { '=' });
+    }
+
+    res
+}
+
+///
+/// 对 Base64 编码的字符串进行解码 (标准字母表，填充可省略)
+///
+/// 返回一个 `Option` 枚举
+/// - Some(Vec<u8>): 解码后的数据
+/// - None: 存在非法字符或长度不正确
+///
+/// **Example:**
+/// ```
+/// mod crypto;
+///
+/// assert_eq!(crypto::base64_decode("dXNlcjpwYXNz"), Some(b"user:pass".to_vec()));
+/// ```
+///
+#[allow(dead_code)]
+pub fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let data = data.trim_end_matches('=').as_bytes();
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut res = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut buf, mut bits) = (0u32, 0);
+    for byte in data {
+        let n = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buf = buf << 6 | n as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buf >> bits) as u8);
+        }
+    }
+
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_vectors() {
+        let hex = |x: [u8; 20]| x.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(sha1(&[b'a'; 1000])), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let cases = ["", "f", "fo", "foo", "foob", "fooba", "foobar"];
+        let encoded = ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE=", "Zm9vYmFy"];
+        for (x, y) in cases.iter().zip(encoded) {
+            assert_eq!(base64_encode(x.as_bytes()), y);
+            assert_eq!(base64_decode(y).as_deref(), Some(x.as_bytes()));
+        }
+        assert_eq!(base64_decode("Zm9v!"), None);
+    }
+}
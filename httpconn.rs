@@ -0,0 +1,416 @@
+//!
+//! 一个可复用的 HTTP/1.1 持续连接 (Keep-Alive)
+//!
+//! 由 `sal_notice` `sal_file` 与 `sal_http` (`native-backend`) 共用，
+//! 统一处理 `Content-Length` `Transfer-Encoding: chunked` 与断线重连
+//!
+
+#![allow(dead_code)] // 各模块仅使用其中一部分方法
+
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+///
+/// HTTP持续连接结构体
+///
+/// - host: 目标主机，如 `www.pushplus.plus:80`
+/// - stream: 当前连接，首次请求时建立，服务器关闭后自动重新连接
+/// - limit: 响应的最大长度，超出时返回 `ErrorKind::FileTooLarge`
+/// - timeout: 连接与读写的超时时间，`None` 为不限制
+///
+/// 返回的响应为完整的原始数据，`chunked` 主体已解码，
+/// 并以 `Content-Length` 替换 `Transfer-Encoding`
+///
+/// **Example:**
+/// ```
+/// mod httpconn;
+/// use httpconn::HttpConn;
+///
+/// let mut conn = HttpConn::new("www.pushplus.plus:80");
+/// let raw = conn.get("/", &[("Accept", "*/*")])?;
+/// let raw = conn.post("/send", &[("Content-Type", "application/json")], b"{}")?;
+/// ```
+///
+#[derive(Debug)]
+pub struct HttpConn {
+    host: String,
+    stream: Option<BufReader<TcpStream>>,
+    limit: Option<usize>,
+    timeout: Option<Duration>,
+}
+
+impl HttpConn {
+
+    ///
+    /// 创建一个新的 `HttpConn` 实例，此时不会建立连接
+    ///
+    pub fn new(host: &str) -> HttpConn {
+        HttpConn { host: host.into(), stream: None, limit: None, timeout: None }
+    }
+
+    ///
+    /// 获取目标主机
+    ///
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    ///
+    /// 设置响应的最大长度 (Byte)，包括头部与主体，`None` 为不限制
+    ///
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    ///
+    /// 设置连接与读写的超时时间，`None` 为不限制，对当前连接立即生效
+    ///
+    /// 超时后返回 `ErrorKind::TimedOut` 或 `ErrorKind::WouldBlock` (取决于平台)
+    ///
+    /// > 注意，`Some(Duration::ZERO)` 将导致之后的连接返回 `ErrorKind::InvalidInput`
+    ///
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.timeout = timeout;
+        if let Some(stream) = &self.stream {
+            stream.get_ref().set_read_timeout(timeout)?;
+            stream.get_ref().set_write_timeout(timeout)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 立即建立连接，已连接时将重新连接
+    ///
+    pub fn connect(&mut self) -> Result<()> {
+        let stream = match self.timeout {
+            Some(timeout) => Self::connect_timeout(&self.host, timeout)?,
+            None => TcpStream::connect(&self.host)?,
+        };
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+
+        self.stream = Some(BufReader::new(stream));
+        Ok(())
+    }
+
+    ///
+    /// 断开连接，下次请求时将重新连接
+    ///
+    pub fn close(&mut self) {
+        self.stream = None;
+    }
+
+    ///
+    /// 发送 `GET` 请求，自动添加 `Host` 头部
+    ///
+    pub fn get(&mut self, path: &str, headers: &[(&str, &str)]) -> Result<Vec<u8>> {
+        let raw = self.build("GET", path, headers, None);
+        self.request(&raw)
+    }
+
+    ///
+    /// 发送 `POST` 请求，自动添加 `Host` 与 `Content-Length` 头部
+    ///
+    pub fn post(&mut self, path: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<Vec<u8>> {
+        let mut raw = self.build("POST", path, headers, Some(body.len()));
+        raw.extend_from_slice(body);
+        self.request(&raw)
+    }
+
+    ///
+    /// 发送原始请求，并返回完整的原始响应
+    ///
+    /// 复用的连接已被服务器关闭时，将重新连接并重发一次
+    ///
+    pub fn request(&mut self, raw: &[u8]) -> Result<Vec<u8>> {
+        let reused = self.stream.is_some();
+
+        match self.exchange(raw, &mut std::io::empty()) {
+            Err(e) if reused && Self::stale(&e) => {
+                self.connect()?;
+                self.exchange(raw, &mut std::io::empty())
+            }
+            res => res,
+        }
+    }
+
+    ///
+    /// 发送请求头部，并从 `body` 流式发送主体，返回完整的原始响应
+    ///
+    /// > 注意，`body` 无法重放，因此该方法不会自动重发
+    ///
+    pub fn request_stream(&mut self, head: &[u8], body: &mut dyn Read) -> Result<Vec<u8>> {
+        self.exchange(head, body)
+    }
+
+    fn build(&self, method: &str, path: &str, headers: &[(&str, &str)], length: Option<usize>) -> Vec<u8> {
+        let mut raw = format!("{method} {path} HTTP/1.1\r\nHost: {}\r\n", self.host);
+        for (key, value) in headers {
+            raw.extend([key, ": ", value, "\r\n"]);
+        }
+        if let Some(length) = length {
+            raw.push_str(&format!("Content-Length: {length}\r\n"));
+        }
+        raw.push_str("\r\n");
+
+        raw.into_bytes()
+    }
+
+    fn exchange(&mut self, head: &[u8], body: &mut dyn Read) -> Result<Vec<u8>> {
+        if self.stream.is_none() {
+            self.connect()?;
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Err(Error::new(ErrorKind::NotConnected, "Not Connected!"));
+        };
+
+        let sent = {
+            let mut writer = BufWriter::new(stream.get_ref());
+            writer
+                .write_all(head)
+                .and_then(|_| std::io::copy(body, &mut writer))
+                .and_then(|_| writer.flush())
+        };
+        if let Err(e) = sent {
+            self.close(); // 请求不完整，断开连接避免服务器接收残缺数据
+            return Err(e);
+        }
+
+        match Self::read_response(stream, head.starts_with(b"HEAD "), self.limit) {
+            Ok((data, keep_alive)) => {
+                if !keep_alive {
+                    self.close();
+                }
+                Ok(data)
+            }
+            Err(e) => {
+                self.close();
+                Err(e)
+            }
+        }
+    }
+
+    // 依次尝试解析到的地址，返回最后一个错误
+    fn connect_timeout(host: &str, timeout: Duration) -> Result<TcpStream> {
+        let mut last = Error::new(ErrorKind::InvalidInput, "No Address Resolved!");
+        for addr in host.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(x) => return Ok(x),
+                Err(e) => last = e,
+            }
+        }
+
+        Err(last)
+    }
+
+    // 连接已被服务器关闭，且未收到任何响应
+    fn stale(e: &Error) -> bool {
+        matches!(
+            e.kind(),
+            ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+        )
+    }
+
+    fn read_response(reader: &mut impl BufRead, head_only: bool, limit: Option<usize>) -> Result<(Vec<u8>, bool)> {
+        let max = limit.unwrap_or(usize::MAX);
+        let too_large = || Error::new(ErrorKind::FileTooLarge, "Response Too Large!");
+
+        let mut head = Vec::new();
+        let status = loop {
+            head.clear();
+            while !head.ends_with(b"\r\n\r\n") {
+                if reader.read_until(b'\n', &mut head)? == 0 {
+                    if head.is_empty() {
+                        return Err(Error::new(ErrorKind::ConnectionAborted, "Connection Closed by Server"));
+                    }
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "InvalidData Received from Server"));
+                }
+                if head.len() > max {
+                    return Err(too_large());
+                }
+            }
+
+            let status = head
+                .split(|x| *x == b' ')
+                .nth(1)
+                .and_then(|x| std::str::from_utf8(x).ok())
+                .and_then(|x| x.parse::<u16>().ok())
+                .unwrap_or(0);
+
+            if !(100..200).contains(&status) || status == 101 {
+                break status; // 跳过 `100 Continue` 等临时响应
+            }
+        };
+
+        let mut length = None;
+        let mut chunked = false;
+        let mut keep_alive = !head.starts_with(b"HTTP/1.0");
+        let text = String::from_utf8_lossy(&head).into_owned();
+        for line in text.lines().skip(1) {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            if key.eq_ignore_ascii_case("Content-Length") {
+                length = value.parse::<usize>().ok();
+            } else if key.eq_ignore_ascii_case("Transfer-Encoding") {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            } else if key.eq_ignore_ascii_case("Connection") {
+                keep_alive = match value.to_ascii_lowercase().as_str() {
+                    "close" => false,
+                    "keep-alive" => true,
+                    _ => keep_alive,
+                };
+            }
+        }
+
+        let mut body = Vec::new();
+        if head_only || matches!(status, 101 | 204 | 304) {
+            // 没有主体
+        } else if chunked {
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                let size = line.split(';').next().unwrap_or("").trim();
+                let Ok(size) = usize::from_str_radix(size, 16) else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "InvalidData Received from Server",
+                    ));
+                };
+
+                if size == 0 {
+                    while !matches!(reader.read_line(&mut line)?, 0 | 2) {}
+                    break;
+                }
+                let total = head.len().checked_add(body.len()).and_then(|x| x.checked_add(size));
+                if total.is_none_or(|x| x > max) {
+                    return Err(too_large());
+                }
+
+                Self::read_body(reader, &mut body, size)?;
+                reader.read_line(&mut line)?; // \r\n
+            }
+
+            // 已解码，移除 `Transfer-Encoding` 并补充 `Content-Length`
+            let text: String = text
+                .split_inclusive("\r\n")
+                .filter(|x| !x.to_ascii_lowercase().starts_with("transfer-encoding:"))
+                .collect();
+            head = text.trim_end().as_bytes().to_vec();
+            head.extend_from_slice(format!("\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes());
+        } else if let Some(length) = length {
+            if head.len().saturating_add(length) > max {
+                return Err(too_large());
+            }
+            Self::read_body(reader, &mut body, length)?;
+        } else {
+            let left = (max - head.len()) as u64;
+            reader.take(left.saturating_add(1)).read_to_end(&mut body)?;
+            if body.len() as u64 > left {
+                return Err(too_large());
+            }
+            keep_alive = false;
+        }
+
+        head.extend_from_slice(&body);
+
+        Ok((head, keep_alive))
+    }
+
+    // 读取 `size` 字节追加到 `body`，按实际收到的数据扩容，不信任对方声明的长度预先分配
+    fn read_body(reader: &mut impl BufRead, body: &mut Vec<u8>, size: usize) -> Result<()> {
+        let start = body.len();
+        reader.take(size as u64).read_to_end(body)?;
+        if body.len() - start < size {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "InvalidData Received from Server"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mock_server::MockServer;
+
+    #[test]
+    fn keep_alive_and_chunked() {
+        let server = MockServer::new();
+        server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        server.push_parts([
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n",
+            "2\r\nde\r\n0\r\n\r\n",
+        ]);
+
+        let mut conn = HttpConn::new(&server.host());
+        let raw = conn.get("/a", &[]).unwrap();
+        assert!(raw.ends_with(b"\r\n\r\nhello"));
+
+        let raw = String::from_utf8(conn.get("/b", &[]).unwrap()).unwrap();
+        assert!(!raw.to_ascii_lowercase().contains("transfer-encoding"));
+        assert!(raw.ends_with("Content-Length: 5\r\n\r\nabcde"));
+
+        // 两次请求复用同一连接
+        assert_eq!(server.requests().len(), 2);
+        assert!(server.last_request().unwrap().starts_with(b"GET /b HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn reconnect_after_close() {
+        let server = MockServer::new();
+        server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\na");
+        server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nb");
+
+        let mut conn = HttpConn::new(&server.host());
+        assert!(conn.get("/", &[]).unwrap().ends_with(b"a"));
+        assert!(conn.get("/", &[]).unwrap().ends_with(b"b"));
+    }
+
+    #[test]
+    fn chunk_size_overflow() {
+        let server = MockServer::new();
+        server.push_response("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nabc\r\n");
+
+        let mut conn = HttpConn::new(&server.host());
+        conn.set_limit(Some(1024));
+        assert_eq!(conn.get("/", &[]).unwrap_err().kind(), ErrorKind::FileTooLarge);
+    }
+
+    #[test]
+    fn chunk_size_overflow_without_limit() {
+        let server = MockServer::new();
+        server.push_response(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n\
+             1\r\na\r\nffffffffffffffff\r\nabc\r\n",
+        );
+
+        let mut conn = HttpConn::new(&server.host());
+        assert_eq!(conn.get("/", &[]).unwrap_err().kind(), ErrorKind::FileTooLarge);
+    }
+
+    #[test]
+    fn huge_content_length_without_limit() {
+        let server = MockServer::new();
+        let raw = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\nabc", usize::MAX);
+        server.push_response(raw);
+
+        // 不会按声明的长度分配内存，连接关闭后返回 `UnexpectedEof`
+        let mut conn = HttpConn::new(&server.host());
+        assert_eq!(conn.get("/", &[]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn response_limit() {
+        let server = MockServer::new();
+        server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n");
+
+        let mut conn = HttpConn::new(&server.host());
+        conn.set_limit(Some(1024));
+        assert_eq!(conn.get("/", &[]).unwrap_err().kind(), ErrorKind::FileTooLarge);
+    }
+}
@@ -0,0 +1,158 @@
+//!
+//! 一个简易的HTTP首行 (请求行、状态行) 解析方案
+//!
+
+///
+/// HTTP版本枚举
+///
+/// - Version::Http10 => `HTTP/1.0`
+/// - Version::Http11 => `HTTP/1.1`
+/// - Version::Http2 => `HTTP/2`，`cUrl` 的状态行中出现
+/// - Version::Http3 => `HTTP/3`，`cUrl` 的状态行中出现
+///
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+    Http2,
+    Http3,
+}
+
+#[allow(dead_code)]
+impl Version {
+    ///
+    /// 解析HTTP版本，如 `HTTP/1.1`，区分大小写
+    ///
+    pub fn parse(data: &str) -> Option<Version> {
+        match data {
+            "HTTP/1.0" => Some(Version::Http10),
+            "HTTP/1.1" => Some(Version::Http11),
+            "HTTP/2" | "HTTP/2.0" => Some(Version::Http2),
+            "HTTP/3" | "HTTP/3.0" => Some(Version::Http3),
+            _ => None,
+        }
+    }
+
+    ///
+    /// 获取HTTP版本的字符串形式
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+            Version::Http2 => "HTTP/2",
+            Version::Http3 => "HTTP/3",
+        }
+    }
+}
+
+///
+/// 解析请求行，如 `GET /files HTTP/1.1`
+///
+/// 返回一个 `Option` 枚举
+/// - Some((method, target, version))
+///     - method: 请求方法，仅包含 `token` 字符
+///     - target: 请求目标，原样返回
+/// - None: 不是三段，或方法、版本不合法
+///
+/// > 注意，各段之间允许多个空白
+///
+/// **Example:**
+/// ```
+/// mod httpline;
+/// use httpline::Version;
+///
+/// let line = httpline::parse_request_line("GET  /files HTTP/1.1");
+/// assert_eq!(line, Some(("GET", "/files", Version::Http11)));
+///
+/// assert_eq!(httpline::parse_request_line("GET /files"), None);
+/// ```
+///
+#[allow(dead_code)]
+pub fn parse_request_line(line: &str) -> Option<(&str, &str, Version)> {
+    let mut parts = line.split_whitespace();
+    let (method, target, version) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || !is_token(method) {
+        return None;
+    }
+
+    Some((method, target, Version::parse(version)?))
+}
+
+///
+/// 解析状态行，如 `HTTP/1.1 404 Not Found`
+///
+/// 返回一个 `Option` 枚举
+/// - Some((version, status, reason))
+///     - status: 状态码，范围为 `100..=599`
+///     - reason: 原因短语，可包含空格，可为空
+/// - None: 版本或状态码不合法
+///
+/// **Example:**
+/// ```
+/// mod httpline;
+/// use httpline::Version;
+///
+/// let line = httpline::parse_status_line("HTTP/1.1 520 LOVE YOU");
+/// assert_eq!(line, Some((Version::Http11, 520, "LOVE YOU")));
+///
+/// assert_eq!(httpline::parse_status_line("HTTP/2 200"), Some((Version::Http2, 200, "")));
+/// ```
+///
+#[allow(dead_code)]
+pub fn parse_status_line(line: &str) -> Option<(Version, u16, &str)> {
+    let (version, rest) = line.trim().split_once(char::is_whitespace)?;
+    let rest = rest.trim_start();
+    let (status, reason) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    if status.len() != 3 || !status.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let status = status.parse().ok().filter(|x| (100..=599).contains(x))?;
+
+    Some((Version::parse(version)?, status, reason.trim()))
+}
+
+// RFC 9110 中的 `token` 字符
+fn is_token(data: &str) -> bool {
+    !data.is_empty()
+        && data.bytes().all(|x| x.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_lines() {
+        assert_eq!(parse_request_line("GET /files HTTP/1.1"), Some(("GET", "/files", Version::Http11)));
+        assert_eq!(parse_request_line(" POST\t/a?b=1  HTTP/1.0 "), Some(("POST", "/a?b=1", Version::Http10)));
+        assert_eq!(parse_request_line("M-SEARCH * HTTP/1.1"), Some(("M-SEARCH", "*", Version::Http11)));
+
+        assert_eq!(parse_request_line(""), None);
+        assert_eq!(parse_request_line("GET /files"), None);
+        assert_eq!(parse_request_line("GET /a b HTTP/1.1"), None);
+        assert_eq!(parse_request_line("G(E)T / HTTP/1.1"), None);
+        assert_eq!(parse_request_line("GET / http/1.1"), None);
+        assert_eq!(parse_request_line("GET / HTTP/1.2"), None);
+    }
+
+    #[test]
+    fn status_lines() {
+        assert_eq!(parse_status_line("HTTP/1.1 404 Not Found\r\n"), Some((Version::Http11, 404, "Not Found")));
+        assert_eq!(parse_status_line("HTTP/1.0  200   OK"), Some((Version::Http10, 200, "OK")));
+        assert_eq!(parse_status_line("HTTP/2 200"), Some((Version::Http2, 200, "")));
+        assert_eq!(parse_status_line("HTTP/3.0 599 x"), Some((Version::Http3, 599, "x")));
+
+        assert_eq!(parse_status_line(""), None);
+        assert_eq!(parse_status_line("HTTP/1.1"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 OK"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 20 OK"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 099 Low"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 600 High"), None);
+        assert_eq!(parse_status_line("HTTP/1.1 +20 OK"), None);
+        assert_eq!(parse_status_line("ICY 200 OK"), None);
+        assert_eq!(Version::parse(Version::Http2.as_str()), Some(Version::Http2));
+    }
+}
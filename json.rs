@@ -0,0 +1,294 @@
+//!
+//! 一个简易的JSON数据结构及序列化方案
+//!
+
+use std::fmt;
+
+///
+/// Value JSON数据枚举
+///
+/// 用于储存 ***任意JSON数据***
+///
+/// - Object 使用 `Vec` 储存，保持键的插入顺序
+///
+/// **Example:**
+/// ```
+/// mod json;
+/// use json::Value;
+///
+/// let value = Value::Object(vec![
+///     ("Hello".into(), Value::String("World".into())),
+///     ("Count".into(), Value::Number(3.0)),
+/// ]);
+///
+/// assert_eq!(value.to_string(), r#"{"Hello":"World","Count":3}"#);
+/// ```
+///
+#[allow(dead_code)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(x) => write!(f, "{x}"),
+            Self::Number(x) if x.is_finite() => write!(f, "{x}"),
+            Self::Number(_) => f.write_str("null"),
+            Self::String(x) => escape(f, x),
+            Self::Array(list) => {
+                f.write_str("[")?;
+                for (index, value) in list.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(map) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str(",")?;
+                    }
+                    escape(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Value {
+    ///
+    /// 解析JSON字符串
+    ///
+    /// 返回一个 `Option` 枚举
+    /// - Some(Value)
+    /// - None: 数据格式错误，或结尾存在多余的数据
+    ///
+    /// **Example:**
+    /// ```
+    /// mod json;
+    /// use json::Value;
+    ///
+    /// let value = Value::parse(r#"{"Hello":["World",3]}"#).unwrap();
+    ///
+    /// assert_eq!(value.get("Hello").unwrap().to_string(), r#"["World",3]"#);
+    /// ```
+    ///
+    pub fn parse(data: &str) -> Option<Value> {
+        let mut parser = Parser { data, place: 0 };
+        let value = parser.value(0)?;
+
+        parser.space();
+        (parser.place == data.len()).then_some(value)
+    }
+
+    ///
+    /// 获取 `Object` 中键对应的值，存在重复的键时返回第一个
+    ///
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(map) => map.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    ///
+    /// 获取 `String` 的值
+    ///
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    ///
+    /// 获取 `Number` 的值
+    ///
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Number(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    ///
+    /// 获取 `Array` 的值
+    ///
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+const MAX_DEPTH: usize = 128; // 嵌套的最大层数，避免恶意数据导致栈溢出
+
+struct Parser<'d> {
+    data: &'d str,
+    place: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        self.space();
+        match self.peek()? {
+            b'n' => self.word("null", Value::Null),
+            b't' => self.word("true", Value::Bool(true)),
+            b'f' => self.word("false", Value::Bool(false)),
+            b'"' => self.string().map(Value::String),
+            b'[' => {
+                self.place += 1;
+                let mut list = Vec::new();
+                if !self.close(b']') {
+                    loop {
+                        list.push(self.value(depth + 1)?);
+                        if self.close(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Some(Value::Array(list))
+            }
+            b'{' => {
+                self.place += 1;
+                let mut map = Vec::new();
+                if !self.close(b'}') {
+                    loop {
+                        self.space();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        map.push((key, self.value(depth + 1)?));
+                        if self.close(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Some(Value::Object(map))
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.as_bytes().get(self.place).copied()
+    }
+
+    fn space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.place += 1;
+        }
+    }
+
+    fn expect(&mut self, x: u8) -> Option<()> {
+        self.space();
+        (self.peek()? == x).then(|| self.place += 1)
+    }
+
+    // 跳过空白，若下一个字符为 `x` 则消耗并返回 `true`
+    fn close(&mut self, x: u8) -> bool {
+        self.expect(x).is_some()
+    }
+
+    fn word(&mut self, word: &str, value: Value) -> Option<Value> {
+        self.data[self.place..].starts_with(word).then(|| {
+            self.place += word.len();
+            value
+        })
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let rest = &self.data[self.place..];
+        let len = rest
+            .find(|x: char| !matches!(x, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+
+        let value = rest[..len].parse().ok()?;
+        self.place += len;
+
+        Some(Value::Number(value))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.peek()? != b'"' {
+            return None;
+        }
+        self.place += 1;
+
+        let mut res = String::new();
+        let mut chars = self.data[self.place..].char_indices();
+        loop {
+            let (index, x) = chars.next()?;
+            match x {
+                '"' => {
+                    self.place += index + 1;
+                    return Some(res);
+                }
+                '\\' => match chars.next()?.1 {
+                    'n' => res.push('\n'),
+                    'r' => res.push('\r'),
+                    't' => res.push('\t'),
+                    'b' => res.push('\u{8}'),
+                    'f' => res.push('\u{c}'),
+                    'u' => {
+                        let mut code = Self::hex4(&mut chars)?;
+                        if (0xD800..=0xDBFF).contains(&code) { // 代理对
+                            let (Some((_, '\\')), Some((_, 'u'))) = (chars.next(), chars.next()) else {
+                                return None;
+                            };
+                            let low = Self::hex4(&mut chars).filter(|x| (0xDC00..=0xDFFF).contains(x))?;
+                            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                        }
+                        res.push(char::from_u32(code)?);
+                    }
+                    x @ ('"' | '\\' | '/') => res.push(x),
+                    _ => return None,
+                },
+                x if (x as u32) < 0x20 => return None,
+                x => res.push(x),
+            }
+        }
+    }
+
+    fn hex4(chars: &mut std::str::CharIndices) -> Option<u32> {
+        let hex: String = chars.by_ref().take(4).map(|(_, x)| x).collect();
+        u32::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 4)
+    }
+}
+
+fn escape(f: &mut fmt::Formatter<'_>, data: &str) -> fmt::Result {
+    f.write_str("\"")?;
+
+    for c in data.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        };
+    }
+
+    f.write_str("\"")
+}
@@ -0,0 +1,107 @@
+//!
+//! 一个根据文件扩展名推断 `Content-Type` 的解决方法
+//!
+//! 由 `sal_server` 的静态文件应答与 `sal_http` 的 `Response::content_type` 共用
+//!
+
+const TABLE: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("wasm", "application/wasm"),
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+];
+
+///
+/// 默认的 `Content-Type`，用于未知的扩展名
+///
+pub const DEFAULT: &str = "application/octet-stream";
+
+///
+/// 根据文件扩展名推断 `Content-Type`
+///
+/// 参数：
+/// - ext: 文件扩展名，不区分大小写，可带有前导的 `.`
+///
+/// 返回对应的 `Content-Type`，未知时返回 `application/octet-stream`
+///
+/// **Example:**
+/// ```
+/// mod mime;
+///
+/// assert_eq!(mime::from_extension("PNG"), "image/png");
+/// assert_eq!(mime::from_extension(".unknown"), mime::DEFAULT);
+/// ```
+///
+pub fn from_extension(ext: &str) -> &'static str {
+    let ext = ext.strip_prefix('.').unwrap_or(ext);
+
+    TABLE
+        .iter()
+        .find(|(x, _)| x.eq_ignore_ascii_case(ext))
+        .map_or(DEFAULT, |(_, x)| x)
+}
+
+///
+/// 根据文件路径推断 `Content-Type`
+///
+/// **Example:**
+/// ```
+/// mod mime;
+///
+/// assert_eq!(mime::from_path("/static/index.html"), "text/html; charset=utf-8");
+/// ```
+///
+pub fn from_path(path: &str) -> &'static str {
+    match path.rsplit_once('.') {
+        Some((name, ext)) if !name.is_empty() && !ext.contains('/') => from_extension(ext),
+        _ => DEFAULT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions() {
+        assert_eq!(from_extension("html"), "text/html; charset=utf-8");
+        assert_eq!(from_extension("CSS"), "text/css; charset=utf-8");
+        assert_eq!(from_extension(".js"), "text/javascript; charset=utf-8");
+        assert_eq!(from_extension("json"), "application/json");
+        assert_eq!(from_extension("Jpg"), "image/jpeg");
+        assert_eq!(from_extension("pdf"), "application/pdf");
+        assert_eq!(from_extension("txt"), "text/plain; charset=utf-8");
+        assert_eq!(from_extension("bin"), DEFAULT);
+        assert_eq!(from_extension(""), DEFAULT);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(from_path("/static/index.html"), "text/html; charset=utf-8");
+        assert_eq!(from_path("a.tar.gz"), "application/gzip");
+        assert_eq!(from_path(".png"), DEFAULT); // 隐藏文件，无扩展名
+        assert_eq!(from_path("/v1.2/readme"), DEFAULT);
+        assert_eq!(from_path("/"), DEFAULT);
+    }
+}
@@ -0,0 +1,276 @@
+//!
+//! 一个用于测试的模拟HTTP服务器
+//!
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
+use std::thread;
+use std::time::Duration;
+
+const PART_DELAY: Duration = Duration::from_millis(50); // 分段应答每段之间的间隔
+
+///
+/// 模拟服务器结构体
+///
+/// 绑定于 `127.0.0.1:0`，按顺序返回预设的原始应答，并记录收到的原始请求
+///
+/// - 预设应答为空时，返回 `200 OK`，主体为收到的原始请求
+/// - 预设应答包含 `Connection: close` 时，写出后关闭连接
+/// - 请求主体仅支持 `Content-Length`，不支持 `chunked`
+/// - 实例销毁时停止监听
+///
+/// 仅依赖标准库，各模块均可在测试中以 `#[cfg(test)] mod mock_server;` 引入
+///
+/// **Example:**
+/// ```
+/// mod mock_server;
+/// use mock_server::MockServer;
+/// use std::io::{Read, Write};
+/// use std::net::TcpStream;
+///
+/// let server = MockServer::new();
+/// server.push_response("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+///
+/// let mut stream = TcpStream::connect(server.host())?;
+/// stream.write_all(b"GET /index.html HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+///
+/// let mut response = String::new();
+/// stream.read_to_string(&mut response)?;
+///
+/// assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+/// assert!(server.last_request().unwrap().starts_with(b"GET /index.html HTTP/1.1\r\n"));
+/// ```
+///
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    closed: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct State {
+    responses: VecDeque<Vec<Vec<u8>>>,
+    requests: Vec<Vec<u8>>,
+}
+
+impl MockServer {
+
+    ///
+    /// 创建并启动一个 `MockServer` 实例
+    ///
+    /// 监听与连接处理均在后台线程中进行，不会阻塞
+    ///
+    pub fn new() -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Error: Couldn't bind port!");
+        let addr = listener.local_addr().expect("Error: Couldn't get local address!");
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let (state_clone, closed_clone) = (state.clone(), closed.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if closed_clone.load(Ordering::Relaxed) {
+                    break;
+                };
+                let Ok(stream) = stream else { continue; };
+
+                let state = state_clone.clone();
+                thread::spawn(move || Self::handler(stream, state));
+            };
+        });
+
+        MockServer { addr, state, closed }
+    }
+
+    ///
+    /// 追加一个原始应答，按追加的顺序返回
+    ///
+    /// **Example:**
+    /// ```
+    /// mod mock_server;
+    /// use mock_server::MockServer;
+    ///
+    /// let server = MockServer::new();
+    /// server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}");
+    /// ```
+    ///
+    pub fn push_response<T: Into<Vec<u8>>>(&self, raw: T) {
+        self.lock().responses.push_back(vec![raw.into()]);
+    }
+
+    ///
+    /// 追加一个分段写出的原始应答，每段之间间隔 `50ms`，用于模拟数据分多次到达
+    ///
+    /// **Example:**
+    /// ```
+    /// mod mock_server;
+    /// use mock_server::MockServer;
+    ///
+    /// let server = MockServer::new();
+    /// server.push_parts(["HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n", "{}"]);
+    /// ```
+    ///
+    pub fn push_parts<T: Into<Vec<u8>>>(&self, parts: impl IntoIterator<Item = T>) {
+        self.lock().responses.push_back(parts.into_iter().map(Into::into).collect());
+    }
+
+    ///
+    /// 获取服务器的地址，如 `http://127.0.0.1:41234`
+    ///
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    ///
+    /// 获取服务器的 `host:port`，如 `127.0.0.1:41234`，可用于 `Transport` 与 `with_host`
+    ///
+    pub fn host(&self) -> String {
+        self.addr.to_string()
+    }
+
+    ///
+    /// 获取最后收到的原始请求
+    ///
+    pub fn last_request(&self) -> Option<Vec<u8>> {
+        self.lock().requests.last().cloned()
+    }
+
+    ///
+    /// 获取已收到的全部原始请求，按收到的顺序排列
+    ///
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn handler(stream: TcpStream, state: Arc<Mutex<State>>) {
+        let mut reader = BufReader::new(&stream);
+        let mut writer = &stream;
+
+        while let Some(request) = Self::read_request(&mut reader) {
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            let response = match state.responses.pop_front() {
+                Some(x) => x,
+                None => vec![[format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", request.len()).as_bytes(), &request].concat()],
+            };
+            state.requests.push(request);
+            drop(state);
+
+            for (i, part) in response.iter().enumerate() {
+                if i > 0 {
+                    thread::sleep(PART_DELAY);
+                };
+                if writer.write_all(part).and_then(|_| writer.flush()).is_err() {
+                    return;
+                };
+            };
+
+            if Self::closing(&response.concat()) {
+                break;
+            };
+        };
+    }
+
+    // 应答头部包含 `Connection: close`
+    fn closing(response: &[u8]) -> bool {
+        let end = response.windows(4).position(|x| x == b"\r\n\r\n").unwrap_or(response.len());
+        String::from_utf8_lossy(&response[..end])
+            .lines()
+            .filter_map(|x| x.split_once(':'))
+            .any(|(k, v)| k.trim().eq_ignore_ascii_case("Connection") && v.trim().eq_ignore_ascii_case("close"))
+    }
+
+    fn read_request(reader: &mut impl BufRead) -> Option<Vec<u8>> {
+        let mut request = Vec::new();
+        loop {
+            match reader.read_until(b'\n', &mut request) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) if request.ends_with(b"\r\n\r\n") => break,
+                Ok(_) => {},
+            };
+        };
+
+        let length = String::from_utf8_lossy(&request)
+            .lines()
+            .filter_map(|x| x.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, v)| v.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+
+        reader.take(length).read_to_end(&mut request).ok()?;
+        Some(request)
+    }
+}
+
+impl Default for MockServer {
+    fn default() -> MockServer {
+        MockServer::new()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        let _ = TcpStream::connect(self.addr); // 唤醒阻塞中的 `accept`
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(server: &MockServer, raw: &[u8]) -> String {
+        let mut stream = TcpStream::connect(server.host()).unwrap();
+        stream.write_all(raw).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn echo_without_preset() {
+        let server = MockServer::new();
+        let request = b"POST /send HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        let expected = [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", request.len()).as_bytes(), request].concat();
+
+        let mut stream = TcpStream::connect(server.host()).unwrap();
+        for _ in 0..2 {
+            stream.write_all(request).unwrap();
+            let mut response = vec![0; expected.len()];
+            stream.read_exact(&mut response).unwrap();
+            assert_eq!(response, expected);
+        }
+
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(server.last_request().unwrap(), request);
+    }
+
+    #[test]
+    fn responses_in_order() {
+        let server = MockServer::new();
+        server.push_response("HTTP/1.1 201 Created\r\nConnection: close\r\n\r\n");
+        server.push_parts(["HTTP/1.1 404 Not Found\r\n", "Connection: close\r\n\r\n"]);
+
+        assert_eq!(exchange(&server, b"GET /a HTTP/1.1\r\n\r\n"), "HTTP/1.1 201 Created\r\nConnection: close\r\n\r\n");
+        assert_eq!(exchange(&server, b"GET /b HTTP/1.1\r\n\r\n"), "HTTP/1.1 404 Not Found\r\nConnection: close\r\n\r\n");
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with(b"GET /a "));
+        assert!(requests[1].starts_with(b"GET /b "));
+    }
+
+    #[test]
+    fn url_and_host() {
+        let server = MockServer::new();
+        assert!(server.host().starts_with("127.0.0.1:"));
+        assert_eq!(server.url(), format!("http://{}", server.host()));
+        assert!(server.last_request().is_none());
+    }
+}
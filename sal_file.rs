@@ -2,6 +2,14 @@
 //! 超星云盘利用工具
 //!
 
+mod httpconn;
+mod json;
+#[cfg(test)] // 测试用的模拟服务器
+mod mock_server;
+pub mod sal_notice;
+mod thread_limit;
+mod urlcode;
+
 /* 如何获取 `token`:
  *
  * 浏览器登陆超星学习通帐号之后访问:
@@ -14,14 +22,34 @@
  */
 
 use std::{
-    io::{BufRead as _, Write as _},
-    io::{BufReader, BufWriter},
-    net::TcpStream,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::Read,
+    mem::take,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
 };
 
+use self::httpconn::HttpConn;
+use self::json::Value;
+use self::sal_notice::{Notice, NoticeError};
+use self::thread_limit::ThreadLimit;
+
 pub use std::io::{Error, ErrorKind, Result};
 const HOST_SCAN: &str = "pan-yz.chaoxing.com:80";
 const HOST_LINK: &str = "sharewh.xuexi365.com:80";
+const SCAN_THREADS: usize = 4;
+const SCAN_SIZE: usize = 100; // 单次扫描的数量，与 `list_folder` 相同
+const RETRIES: u32 = 3; // 连接中断时默认的重试次数
+const RETRY_DELAY: Duration = Duration::from_millis(200); // 首次重试前的等待时间，之后每次翻倍
+const TOKEN_EXPIRED: [&str; 3] = ["token无效", "token已过期", "用户未登录"]; // `_token` 失效时服务器返回的 `msg`
+const UPLOAD_PATH: &str = "/upload?_from=mobilelearn"; // 云盘移动端的上传接口，`puid` 随表单发送
+const BOUNDARY: &str = "----SalFileBoundaryq8T2xKpZ4vWm"; // multipart 分隔符
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) \
+    AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
 
 ///
 /// `CloudFile` 实例结构体
@@ -96,7 +124,7 @@ const HOST_LINK: &str = "sharewh.xuexi365.com:80";
 /// let data = read(path)?;
 /// filer.extend_from_raw(&data)?;
 /// write(path, &filer)?;
-////
+///
 /// println!("扫描完成，新增{counter:03}项文件");
 /// filer.set_stream(Stream::Link)?;
 /// for (name, objid) in filer.get_filemap() {
@@ -109,13 +137,225 @@ const HOST_LINK: &str = "sharewh.xuexi365.com:80";
 #[allow(dead_code)]
 pub struct CloudFile {
     inner: Vec<u8>,
-    stream: Option<TcpStream>,
+    scan_stream: Cell<bool>,     // 是否已与 `hosts.0` 连接
+    link_stream: Cell<bool>,     // 是否已与 `hosts.1` 连接，可与 `scan_stream` 同时保持
+    hosts: (String, String),     // (scan, link) 主机，默认为 `HOST_SCAN` `HOST_LINK`
+    transport: RefCell<Box<dyn Transport>>,
+    interval: Duration,          // 请求的最小间隔
+    last: Cell<Option<Instant>>, // 上次请求的时间
+    retries: u32,                // 连接中断时的最大重试次数
 
     uid: String,   // puid
     token: String, // _token
     dirid: String, // fldid
+    agent: String, // User-Agent
+    format: Format,
+    resids: Vec<String>,     // 最近一次 `scan` 的 residstr
+    version: Option<String>, // 最近一次 `scan` 的条件请求头，由 `ETag` 或 `Last-Modified` 生成
+    deferred: bool,          // 延迟编码，由 `finalize` 统一更新 `inner`
+    dirty: bool,             // `inner` 是否落后于 `filemap`
 
     filemap: Vec<(String, String)>, // filelist: (name, objid)
+    sizes: HashMap<String, u64>,    // objid: size，仅记录本次运行中获取的大小，不写入备份
+    folders: RefCell<HashSet<String>>, // 列表中见到的文件夹 objid，`get_link` 直接返回 `IsFolder`
+}
+
+///
+/// 实例备份文件的格式枚举
+///
+/// 与 `CloudFile::set_format` 配合使用
+///
+/// - Format::V1 => 使用分隔符 (`\u{1B}`, `\u{1A}`) 分隔数据，默认格式
+/// - Format::V2 => 使用长度前缀 (`u16`, 大端序) 分隔数据，文件名可包含任意字符
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    V1,
+    V2,
+}
+
+///
+/// `CloudFileBuilder` 构建器结构体
+///
+/// 由 `CloudFile::builder` 创建，用于避免位置参数，并设置可选的配置
+///
+/// - uid token passwd: 必须设置，要求与 `CloudFile::new` 相同
+/// - dirid: 默认为空，即账号根目录
+/// - timeout: 连接与读写的超时时间，默认不限制，仅作用于默认的 `TcpTransport`
+/// - agent: 默认为 `USER_AGENT`，见 `set_user_agent`
+/// - hosts: 默认为 `HOST_SCAN` `HOST_LINK`，见 `set_hosts`
+/// - interval: 请求的最小间隔，默认不限制，见 `set_rate_limit`
+/// - retries: 连接中断时的最大重试次数，默认为 `RETRIES`，见 `set_retries`
+///
+#[derive(Default)]
+pub struct CloudFileBuilder {
+    uid: Option<String>,
+    token: Option<String>,
+    dirid: String,
+    passwd: Option<[u8; 4]>,
+    timeout: Option<Duration>,
+    agent: Option<String>,
+    hosts: Option<(String, String)>,
+    interval: Duration,
+    retries: Option<u32>,
+}
+
+///
+/// `FileEntry` 文件信息结构体
+///
+/// 用于储存从服务器获取的 **文件（或文件夹）** 信息
+///
+/// - name: 文件名
+/// - objid: `objectId`，用于获取下载链接
+/// - resid: `residstr`，用于在服务器上管理文件
+/// - size: 文件大小 (Byte)，未知时为 `None`
+/// - is_folder: 是否为文件夹
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    pub name: String,
+    pub objid: String,
+    pub resid: String,
+    pub size: Option<u64>,
+    pub is_folder: bool,
+}
+
+///
+/// `ShareInfo` 分享信息结构体
+///
+/// 由 `CloudFile::get_share_info` 返回，页面中不存在的字段为 `None`
+///
+/// - url: 下载链接
+/// - name: 文件名
+/// - size: 文件大小 (Byte)
+/// - expires: 链接的过期时间，为页面中的原始数值 (通常为时间戳)
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareInfo {
+    pub url: String,
+    pub name: Option<String>,
+    pub size: Option<u64>,
+    pub expires: Option<u64>,
+}
+
+///
+/// `Diff` 文件差异结构体
+///
+/// 由 `CloudFile::diff` 返回，文件以 `objid` 比较
+///
+/// - added: 新增的文件 (name, objid)
+/// - removed: 移除的文件 (name, objid)
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+}
+
+///
+/// `CloudError` 错误枚举
+///
+/// 由 `CloudFile` 的公开方法返回，便于按情况处理
+///
+/// - CloudError::TokenExpired => `_token` 失效，需要重新获取
+/// - CloudError::ScanFinished => `scan` 已扫描至结束
+/// - CloudError::NotFound => 文件不存在
+/// - CloudError::IsFolder => `objid` 为文件夹，无法获取下载链接，请使用 `list_folder`
+/// - CloudError::ServerError => 服务器返回的其他错误信息
+/// - CloudError::Io => 网络或文件读写错误
+/// - CloudError::Parse => 数据格式错误
+///
+/// 实现了 `From<std::io::Error>` 及 `From<CloudError> for std::io::Error`，
+/// 可在返回 `std::io::Result` 的函数中直接使用 `?`
+///
+/// **Example:**
+/// ```
+/// mod sal_file;
+/// use sal_file::{CloudError, CloudFile};
+///
+/// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+/// loop {
+///     match cloud.scan() {
+///         Ok(n) => println!("新文件: {n}"),
+///         Err(CloudError::ScanFinished) => break,
+///         Err(CloudError::TokenExpired) => panic!("请更新 `_token`"),
+///         Err(e) => return Err(e.into()),
+///     }
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub enum CloudError {
+    TokenExpired,
+    ScanFinished,
+    NotFound,
+    IsFolder,
+    ServerError(String),
+    Io(Error),
+    Parse(String),
+}
+
+pub type CloudResult<T> = std::result::Result<T, CloudError>;
+
+///
+/// `export_links` 的返回值：(links, failures)
+///
+/// - links: Vec<(name, url)>，成功获取的链接
+/// - failures: Vec<(name, CloudError)>，获取失败的文件及其错误
+///
+pub type ExportedLinks = (Vec<(String, String)>, Vec<(String, CloudError)>);
+
+// 解码后的明文：([puid, _token, fldid], filemap)
+type Plain = ([String; 3], Vec<(String, String)>);
+
+impl fmt::Display for CloudError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TokenExpired => f.write_str("Token Expired: Refresh `_token`!"),
+            Self::ScanFinished => f.write_str("Scan Finished: Read 0000!"),
+            Self::NotFound => f.write_str("Download Link Not Found: Check ObjectID!"),
+            Self::IsFolder => f.write_str("ObjectID is a Folder: Use `list_folder` Instead!"),
+            Self::ServerError(x) => write!(f, "Error Received: {x}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Parse(x) => f.write_str(x),
+        }
+    }
+}
+
+impl std::error::Error for CloudError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for CloudError {
+    fn from(e: Error) -> CloudError {
+        CloudError::Io(e)
+    }
+}
+
+impl From<NoticeError> for CloudError {
+    fn from(e: NoticeError) -> CloudError {
+        CloudError::Io(e.into())
+    }
+}
+
+impl From<CloudError> for Error {
+    fn from(e: CloudError) -> Error {
+        let kind = match e {
+            CloudError::Io(e) => return e,
+            CloudError::TokenExpired | CloudError::ServerError(_) => ErrorKind::PermissionDenied,
+            CloudError::ScanFinished => ErrorKind::WriteZero,
+            CloudError::NotFound => ErrorKind::NotFound,
+            CloudError::IsFolder => ErrorKind::IsADirectory,
+            CloudError::Parse(_) => ErrorKind::InvalidData,
+        };
+
+        Error::new(kind, e.to_string())
+    }
 }
 
 ///
@@ -127,6 +367,8 @@ pub struct CloudFile {
 /// - Stream::Link => 与超星下载服务器连接
 /// - Stream::None => 与服务器断开连接
 ///
+/// `Scan` 与 `Link` 可同时保持，交替调用 `scan` `get_link` 时无需重新连接
+///
 #[allow(dead_code)]
 pub enum Stream {
     Scan,
@@ -134,12 +376,330 @@ pub enum Stream {
     None,
 }
 
+///
+/// 流状态枚举
+///
+/// 由 `CloudFile::stream_status` 返回
+///
+/// - StreamStatus::Disconnected => 未与服务器连接
+/// - StreamStatus::Scan => 已与超星云盘服务器连接
+/// - StreamStatus::Link => 已与超星下载服务器连接
+/// - StreamStatus::Both => 同时与以上两个服务器连接
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStatus {
+    Disconnected,
+    Scan,
+    Link,
+    Both,
+}
+
+///
+/// `Transport` 网络传输特征
+///
+/// 用于替换 `CloudFile` 与服务器交流的方式，默认为 `TcpTransport`
+///
+/// - connect: 与服务器建立连接，由 `CloudFile::set_stream` 调用
+/// - close: 与所有服务器断开连接
+/// - disconnect: 仅与 `host` 断开连接，默认调用 `close`
+/// - request: 发送原始请求，并返回服务器的原始响应
+/// - request_stream: 发送请求头部，并从 `body` 读取主体流式发送
+///     - 默认读取全部主体后调用 `request`
+///
+/// **Example:**
+/// ```
+/// mod sal_file;
+/// use sal_file::{CloudFile, Result, Transport};
+///
+/// #[derive(Debug)]
+/// struct Mock(Vec<u8>);
+///
+/// impl Transport for Mock {
+///     fn request(&mut self, _host: &str, _raw: &[u8]) -> Result<Vec<u8>> {
+///         Ok(self.0.clone())
+///     }
+/// }
+///
+/// let mut cloud = CloudFile::new(
+///     "29*******".into(),
+///     "b8***391*******d3726f*******d0b2".into(),
+///     "94***555*******592".into(),
+///     &[127, 97, 112, 128],
+/// )?;
+///
+/// cloud.set_transport(Box::new(Mock(Vec::from("HTTP/1.1 200 OK\r\n\r\n"))));
+/// ```
+///
+pub trait Transport: fmt::Debug + Send {
+    fn connect(&mut self, _host: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) {}
+
+    fn disconnect(&mut self, _host: &str) {
+        self.close()
+    }
+
+    fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>>;
+
+    fn request_stream(&mut self, host: &str, head: &[u8], body: &mut dyn Read) -> Result<Vec<u8>> {
+        let mut raw = head.to_vec();
+        body.read_to_end(&mut raw)?;
+        self.request(host, &raw)
+    }
+}
+
+///
+/// `TcpTransport` 默认的网络传输实现
+///
+/// 基于 `HttpConn` 与服务器保持连接，每个主机各保持一个连接
+///
+/// - 根据 `Content-Length` 或 `Transfer-Encoding: chunked` 读取完整的响应
+/// - 服务器返回 `Connection: close` 或关闭空闲连接时，下次请求将自动重新连接
+///
+#[derive(Debug, Default)]
+pub struct TcpTransport {
+    conns: Vec<HttpConn>, // 每个主机一个连接
+    timeout: Option<Duration>,
+}
+
+impl Transport for TcpTransport {
+    fn connect(&mut self, host: &str) -> Result<()> {
+        let mut conn = self.new_conn(host);
+        conn.connect()?;
+        self.disconnect(host);
+        self.conns.push(conn);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.conns.clear();
+    }
+
+    fn disconnect(&mut self, host: &str) {
+        self.conns.retain(|x| x.host() != host);
+    }
+
+    fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>> {
+        self.conn(host).request(raw)
+    }
+
+    fn request_stream(&mut self, host: &str, head: &[u8], body: &mut dyn Read) -> Result<Vec<u8>> {
+        self.conn(host).request_stream(head, body)
+    }
+}
+
+impl TcpTransport {
+    ///
+    /// 创建一个带有超时时间的 `TcpTransport`，作用于连接与读写
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::{CloudFile, TcpTransport};
+    /// use std::time::Duration;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.set_transport(Box::new(TcpTransport::with_timeout(Duration::from_secs(10))));
+    /// ```
+    ///
+    pub fn with_timeout(timeout: Duration) -> TcpTransport {
+        TcpTransport { conns: Vec::new(), timeout: Some(timeout) }
+    }
+
+    // 查找该主机的连接，不存在时添加，新连接在首次请求时建立
+    fn conn(&mut self, host: &str) -> &mut HttpConn {
+        let place = match self.conns.iter().position(|x| x.host() == host) {
+            Some(place) => place,
+            None => {
+                self.conns.push(self.new_conn(host));
+                self.conns.len() - 1
+            }
+        };
+
+        &mut self.conns[place]
+    }
+
+    fn new_conn(&self, host: &str) -> HttpConn {
+        let mut conn = HttpConn::new(host);
+        let _ = conn.set_timeout(self.timeout); // 尚未连接，不会失败
+        conn
+    }
+}
+
+// 读取恰好 `left` 字节，提前结束时返回 `UnexpectedEof`，并报告进度
+struct Exact<R, F> {
+    inner: R,
+    left: u64,
+    done: u64,
+    finished: bool,
+    progress: F,
+}
+
+impl<R: Read, F: FnMut(u64, Option<u64>)> Read for Exact<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.left == 0 {
+            if !self.finished {
+                self.finished = true;
+                (self.progress)(self.done, Some(self.done));
+            }
+            return Ok(0);
+        }
+
+        let max = self.left.min(buf.len() as u64) as usize;
+        let n = self.inner.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("Short Read: {} Bytes Missing", self.left),
+            ));
+        }
+        self.left -= n as u64;
+        self.done += n as u64;
+        if self.left > 0 {
+            (self.progress)(self.done, Some(self.done + self.left));
+        }
+
+        Ok(n)
+    }
+}
+
+///
+/// 使用线程池同时扫描多个账号
+///
+/// 参数：
+/// - accounts: `Vec<CloudFile>` 需要扫描的实例
+///
+/// 返回一个 `Vec<(CloudFile, CloudResult<usize>)>`
+/// - CloudFile: 扫描后的实例，顺序与 `accounts` 相同
+/// - CloudResult<usize>: 该实例新扫描到的文件数量，或扫描中遇到的错误
+///
+/// 每个实例都会 `set_stream(Stream::Scan)` 并扫描至结束，
+/// 单个实例的失败（如认证失败）不会影响其他实例
+///
+/// > 注意，若扫描中发生 `panic`，该实例仍会出现在返回结果中，其结果为 `Err(CloudError::Io)`
+///
+/// **Example:**
+/// ```
+/// mod sal_file;
+/// use sal_file::{scan_all, CloudFile};
+///
+/// let accounts = vec![
+///     CloudFile::from_file("/root/a.bin")?,
+///     CloudFile::from_file("/root/b.bin")?,
+/// ];
+///
+/// for (cloud, res) in scan_all(accounts) {
+///     println!("{:?}: {:?}", cloud.get_filemap().len(), res);
+/// }
+/// ```
+///
+/// *请注意：该方法会阻塞运行！*
+///
+pub fn scan_all(accounts: Vec<CloudFile>) -> Vec<(CloudFile, CloudResult<usize>)> {
+    let thread = ThreadLimit::new(SCAN_THREADS);
+
+    let tasks: Vec<_> = accounts
+        .into_iter()
+        .map(|cloud| {
+            let cloud = Arc::new(Mutex::new(cloud)); // 任务 `panic` 后仍可取回实例
+            let shared = Arc::clone(&cloud);
+            let receiver = thread.execute_with_result(move || {
+                shared.lock().unwrap_or_else(PoisonError::into_inner).scan_all_pages()
+            });
+            (cloud, receiver)
+        })
+        .collect();
+
+    tasks
+        .into_iter()
+        .map(|(cloud, receiver)| {
+            // 通道关闭即任务 `panic`，此时任务持有的 `Arc` 已随之释放
+            let res = receiver
+                .recv()
+                .unwrap_or_else(|_| Err(Error::other("Scan Panicked!").into()));
+            let cloud = Arc::into_inner(cloud).expect("Scan Task Still Running");
+            (cloud.into_inner().unwrap_or_else(PoisonError::into_inner), res)
+        })
+        .collect()
+}
+
+///
+/// 扫描实例并推送新文件通知
+///
+/// 参数：
+/// - cloud: 需要扫描的实例
+/// - notice: 用于推送的 `Notice` 实例
+/// - title: 通知的标题
+///
+/// 返回一个 `Result` 枚举
+/// - Ok(usize): 推送的新文件数量，无新文件时不推送并返回 `0`
+/// - Err(CloudError): 扫描或推送中遇到的错误
+///
+/// 通知内容为 `markdown` 列表，每行一个新文件名
+///
+/// 使用 `scan_preserve` 扫描，**不会**删除服务器上的文件
+///
+/// > 注意，`Notice` 需要使用 `sal_file::sal_notice` 中的类型
+///
+/// **Example:**
+/// ```
+/// mod sal_file;
+/// use sal_file::{notify_new_files, sal_notice::Notice, CloudFile};
+///
+/// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+/// let notice = Notice::builder().token("2b********").build()?;
+///
+/// let n = notify_new_files(&mut cloud, &notice, "云盘新文件")?;
+/// println!("新文件: {n}");
+/// cloud.to_file("/root/test.bin")?;
+/// ```
+///
+/// *请注意：该方法会阻塞运行！*
+///
+pub fn notify_new_files(cloud: &mut CloudFile, notice: &Notice, title: &str) -> CloudResult<usize> {
+    let added = cloud.scan_preserve()?;
+    if added.is_empty() {
+        return Ok(0);
+    }
+
+    let content: String = added.iter().map(|x| format!("- {}\n", x.name)).collect();
+    notice.send_markdown(title, &content)?;
+
+    Ok(added.len())
+}
+
 impl AsRef<[u8]> for CloudFile {
     fn as_ref(&self) -> &[u8] {
         &self.inner
     }
 }
 
+// 遍历 `filemap`，即 `for (name, objid) in &cloud`
+impl<'c> IntoIterator for &'c CloudFile {
+    type Item = &'c (String, String);
+    type IntoIter = std::slice::Iter<'c, (String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.filemap.iter()
+    }
+}
+
+// 释放前清除 `_token` 与含有密码的 `inner`
+impl Drop for CloudFile {
+    fn drop(&mut self) {
+        wipe(&mut take(&mut self.token).into_bytes());
+        wipe(&mut self.inner);
+    }
+}
+
+// 尽力清零：`black_box` 避免写入被视为无用而被优化掉，但不保证已复制的数据被清除
+fn wipe(buf: &mut [u8]) {
+    buf.fill(0);
+    std::hint::black_box(buf);
+}
+
 #[allow(dead_code)]
 impl CloudFile {
     ///
@@ -158,7 +718,9 @@ impl CloudFile {
     ///
     /// 返回一个 `Result` 枚举
     /// - Ok(CloudFile)
-    /// - Err(std::io::Error)
+    /// - Err(CloudError)
+    ///
+    /// > 需要设置超时等可选配置时，使用 `builder`
     ///
     /// **Example:**
     /// ```
@@ -173,34 +735,29 @@ impl CloudFile {
     /// )?;
     /// ```
     ///
-    pub fn new(uid: String, token: String, dirid: String, passwd: &[u8; 4]) -> Result<CloudFile> {
-        let mut data = vec![
-            uid.as_bytes(),   // puid
-            token.as_bytes(), // _token
-            dirid.as_bytes(), // fldid
-        ]
-        .join(&[27u8][..]);
-        while data.len() < 64 {
-            data.push(0);
-        }
-
-        let data = Self::matrix_encode(passwd, &data)?;
-        let data = &Self::sixteen_to_eight(&data);
-
-        let mut inner = Vec::new();
-        inner.extend_from_slice(&[3, 3, 4, 21, 7, 23, 10, 8]);
-        inner.extend_from_slice(passwd);
-        inner.extend_from_slice(&[25, 0, 0, 3]);
-        inner.extend_from_slice(&data);
+    pub fn new(uid: String, token: String, dirid: String, passwd: &[u8; 4]) -> CloudResult<CloudFile> {
+        CloudFile::builder().uid(uid).token(token).dirid(dirid).passwd(passwd).build()
+    }
 
-        Ok(Self {
-            uid,
-            token,
-            dirid,
-            inner,
-            stream: None,
-            filemap: Vec::new(),
-        })
+    ///
+    /// 创建一个 `CloudFileBuilder` 构建器
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    /// use std::time::Duration;
+    ///
+    /// let cloud = CloudFile::builder()
+    ///     .uid("29*******")
+    ///     .token("b8***391*******d3726f*******d0b2")
+    ///     .passwd(&[127, 97, 112, 128])
+    ///     .timeout(Duration::from_secs(10))
+    ///     .build()?;
+    /// ```
+    ///
+    pub fn builder() -> CloudFileBuilder {
+        CloudFileBuilder::default()
     }
 
     ///
@@ -212,7 +769,8 @@ impl CloudFile {
     ///
     /// 返回一个 `Result` 枚举
     /// - Ok(CloudFile)
-    /// - Err(std::io::Error)
+    /// - Err(CloudError)
+    ///     - 文件头中的密码与数据不匹配（或文件已损坏）时，返回 `InvalidData`
     ///
     /// **Example:**
     /// ```
@@ -224,27 +782,26 @@ impl CloudFile {
     /// let cloud = CloudFile::from_raw(&data)?;
     /// ```
     ///
-    pub fn from_raw(raw_data: &[u8]) -> Result<CloudFile> {
-        if raw_data.len() < 144 {
+    pub fn from_raw(raw_data: &[u8]) -> CloudResult<CloudFile> {
+        if raw_data.len() < 16 {
             return Err(Error::new(
                 ErrorKind::InvalidInput,
-                "Len of Data to Short: [144..]",
-            ));
+                "Len of Data to Short: [16..]",
+            )
+            .into());
         }
 
         let [day_rz, day_yy, passwd, _] = raw_data.chunks(4).take(4).collect::<Vec<&[u8]>>()[..]
         else {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("Unknown: {}", line!()),
-            ));
+            return Err(Error::other(format!("Unknown: {}", line!())).into());
         };
 
-        if day_rz != &[3, 3, 4, 21] && day_yy != &[7, 23, 10, 8] {
+        if day_rz != [3, 3, 4, 21] && day_yy != [7, 23, 10, 8] {
             return Err(Error::new(
                 ErrorKind::Unsupported,
                 "Wrong File Type: Unsupported File Type",
-            ));
+            )
+            .into());
         }
 
         let passwd: &[u8; 4] = match passwd.try_into() {
@@ -253,44 +810,335 @@ impl CloudFile {
                 return Err(Error::new(
                     ErrorKind::Unsupported,
                     "Wrong Password Type: Unsupported Password Type",
-                ))
+                )
+                .into())
             }
         };
 
-        let data = Self::eight_to_sixteen(&raw_data[16..]);
-        let mut data = Self::matrix_decode(&passwd, &data)?;
-        let _ = data.retain(|x| x != &0);
-        let (base, list) = data.split_at(64); // len >= 64
+        let format = match raw_data[13] {
+            0 => Format::V1,
+            2 => Format::V2,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "Wrong File Version: Unsupported File Version",
+                )
+                .into())
+            }
+        };
 
-        let mut base_data: [&str; 3] = [""; 3];
-        let base = String::from_utf8_lossy(base);
-        for (index, value) in base.splitn(3, '\u{1B}').enumerate() {
-            base_data[index] = value.trim();
+        if format == Format::V1 && raw_data.len() < 144 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Len of Data to Short: [144..]",
+            )
+            .into());
         }
 
-        let mut list_res = Vec::new();
-        if !list.is_empty() {
-            for val in String::from_utf8_lossy(list).split('\u{1B}') {
-                let [name, objid] = val.splitn(2, "\u{1A}").collect::<Vec<&str>>()[..] else {
-                    return Err(Error::new(
-                        ErrorKind::InvalidData,
-                        "Wrong File Data: Unsupported File Type",
-                    ));
-                };
-                list_res.push((name.into(), objid.into()))
-            }
+        let data = Self::eight_to_sixteen(&raw_data[16..]);
+        let mut data = Self::matrix_decode(passwd, &data)?;
+        if let Err(e) = Self::check_plain(format, &data) {
+            wipe(&mut data);
+            return Err(e.into());
         }
+        let parsed = match format {
+            Format::V1 => Self::parse_v1(&data),
+            Format::V2 => Self::parse_v2(&data),
+        };
+        wipe(&mut data);
+        let ([uid, token, dirid], filemap) = parsed?;
 
         Ok(Self {
             inner: raw_data.into(),
-            uid: base_data[0].to_string(),
-            token: base_data[1].to_string(),
-            dirid: base_data[2].to_string(),
-            filemap: list_res,
-            stream: None,
+            uid,
+            token,
+            dirid,
+            filemap,
+            scan_stream: Cell::new(false),
+            link_stream: Cell::new(false),
+            hosts: (HOST_SCAN.into(), HOST_LINK.into()),
+            transport: RefCell::new(Box::new(TcpTransport::default())),
+            interval: Duration::ZERO,
+            retries: RETRIES,
+            last: Cell::new(None),
+            agent: USER_AGENT.into(),
+            format,
+            resids: Vec::new(),
+            version: None,
+            sizes: HashMap::new(),
+            folders: RefCell::default(),
+            deferred: false,
+            dirty: false,
         })
     }
 
+    ///
+    /// 从文件路径读取并导入生成实例
+    ///
+    /// 参数：
+    /// - path: 实例备份文件的路径
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(CloudFile)
+    /// - Err(CloudError)
+    ///     - 文件不存在或无法读取时，错误信息包含文件路径
+    ///     - 文件类型错误时，与 `from_raw` 相同
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn from_file<P: AsRef<Path>>(path: P) -> CloudResult<CloudFile> {
+        let path = path.as_ref();
+        let data = match fs::read(path) {
+            Ok(x) => x,
+            Err(e) => {
+                return Err(Error::new(
+                    e.kind(),
+                    format!("Fail to Read File: {}: {e}", path.display()),
+                )
+                .into())
+            }
+        };
+
+        Self::from_raw(&data)
+    }
+
+    ///
+    /// 从环境变量读取 `puid` `_token` `fldid` 并生成实例，避免在源码中写入凭据
+    ///
+    /// 参数：
+    /// - prefix: 环境变量的前缀，读取 `{prefix}_UID` `{prefix}_TOKEN` `{prefix}_DIRID`
+    ///     - `{prefix}_DIRID` 可选，不存在或为空时为账号根目录
+    /// - passwd: 加密密码，与 `new` 相同
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(CloudFile)
+    /// - Err(CloudError)
+    ///     - `{prefix}_UID` `{prefix}_TOKEN` 不存在或为空时返回 `ErrorKind::NotFound`，错误信息包含变量名
+    ///     - 变量不是合法的 `UTF-8` 时返回 `ErrorKind::InvalidData`
+    ///     - 其他错误与 `new` 相同
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// // export CLOUD_UID=29******* CLOUD_TOKEN=b8***391... CLOUD_DIRID=94***555...
+    /// let cloud = CloudFile::from_env("CLOUD", &[127, 97, 112, 128])?;
+    /// ```
+    ///
+    pub fn from_env(prefix: &str, passwd: &[u8; 4]) -> CloudResult<CloudFile> {
+        let var = |name: &str| {
+            let name = format!("{prefix}_{name}");
+            match std::env::var(&name) {
+                Ok(x) if !x.trim().is_empty() => Ok(x.trim().to_string()),
+                Ok(_) | Err(std::env::VarError::NotPresent) => {
+                    Err(Error::new(ErrorKind::NotFound, format!("Missing Environment Variable: {name}")))
+                }
+                Err(e) => Err(Error::new(ErrorKind::InvalidData, format!("Invalid Environment Variable: {name}: {e}"))),
+            }
+        };
+
+        let dirid = match var("DIRID") {
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(), // 账号根目录
+            x => x?,
+        };
+
+        Self::new(var("UID")?, var("TOKEN")?, dirid, passwd)
+    }
+
+    ///
+    /// 将实例写入到文件路径
+    ///
+    /// 参数：
+    /// - path: 实例备份文件的路径，文件存在时将被覆盖
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError)
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::new(
+    ///     "29*******".into(),
+    ///     "b8***391*******d3726f*******d0b2".into(),
+    ///     "94***555*******592".into(),
+    ///     &[127, 97, 112, 128],
+    /// )?;
+    ///
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> CloudResult<()> {
+        let path = path.as_ref();
+        let encoded;
+        let data = match self.dirty {
+            true => {
+                encoded = self.encode()?;
+                &encoded
+            }
+            false => &self.inner,
+        };
+        if let Err(e) = fs::write(path, data) {
+            return Err(Error::new(
+                e.kind(),
+                format!("Fail to Write File: {}: {e}", path.display()),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 将实例导出为JSON字符串，便于其他工具读取
+    ///
+    /// 导出的数据包含 `uid` `dirid` `format` `files`
+    /// - uid: 仅保留前两位，其余以 `*` 代替
+    /// - files: `[{"name": "...", "objid": "..."}]`
+    ///
+    /// > 注意，不包含 `_token`，无法由 `from_json` 导入，需要导入时使用 `to_json_full`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// println!("{}", cloud.to_json()); // {"uid":"29*******","dirid":"...","format":1,"files":[...]}
+    /// ```
+    ///
+    pub fn to_json(&self) -> String {
+        let uid = self
+            .uid
+            .chars()
+            .enumerate()
+            .map(|(index, x)| if index < 2 { x } else { '*' })
+            .collect();
+
+        self.json(uid, None)
+    }
+
+    ///
+    /// 将实例导出为JSON字符串，包含完整的 `uid` 与 `_token`
+    ///
+    /// > 注意，导出的数据未经加密，请妥善保管
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use std::fs::write;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// write("/root/test.json", cloud.to_json_full())?;
+    /// ```
+    ///
+    pub fn to_json_full(&self) -> String {
+        self.json(self.uid.clone(), Some(self.token.clone()))
+    }
+
+    ///
+    /// 从 `to_json_full` 导出的JSON字符串导入生成实例
+    ///
+    /// 参数：
+    /// - data: `&str` JSON字符串
+    /// - passwd: `&[u8; 4]` 新实例所使用的密码，要求与 `new` 相同
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(CloudFile)
+    /// - Err(CloudError)
+    ///     - 数据格式错误，或缺少 `_token` 时，返回 `InvalidData`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use std::fs::read_to_string;
+    /// use sal_file::CloudFile;
+    ///
+    /// let data = read_to_string("/root/test.json")?;
+    /// let cloud = CloudFile::from_json(&data, &[127, 97, 112, 128])?;
+    /// ```
+    ///
+    pub fn from_json(data: &str, passwd: &[u8; 4]) -> CloudResult<CloudFile> {
+        let invalid = |info: &str| -> CloudError {
+            Error::new(ErrorKind::InvalidData, format!("Wrong JSON Data: {info}")).into()
+        };
+
+        let Some(value) = Value::parse(data) else {
+            return Err(invalid("Unsupported JSON"));
+        };
+        let text = |key: &str| value.get(key).and_then(Value::as_str);
+
+        let (Some(uid), Some(token)) = (text("uid"), text("token")) else {
+            return Err(invalid("Uid and Token are Required, Export with `to_json_full`"));
+        };
+        let format = match value.get("format").and_then(Value::as_f64) {
+            None | Some(1.0) => Format::V1,
+            Some(2.0) => Format::V2,
+            Some(_) => return Err(invalid("Unsupported File Version")),
+        };
+
+        let mut filemap = Vec::new();
+        for file in value.get("files").and_then(Value::as_array).unwrap_or_default() {
+            let (Some(name), Some(objid)) = (
+                file.get("name").and_then(Value::as_str),
+                file.get("objid").and_then(Value::as_str),
+            ) else {
+                return Err(invalid("Name and ObjectID are Required"));
+            };
+            filemap.push((name.to_string(), objid.to_string()));
+        }
+
+        let mut cloud = CloudFile::builder()
+            .uid(uid)
+            .token(token)
+            .dirid(text("dirid").unwrap_or_default())
+            .passwd(passwd)
+            .build()?;
+        cloud.format = format;
+        cloud.filemap = filemap;
+        cloud.update_inner()?;
+
+        Ok(cloud)
+    }
+
+    fn json(&self, uid: String, token: Option<String>) -> String {
+        let files = self
+            .filemap
+            .iter()
+            .map(|(name, objid)| {
+                Value::Object(vec![
+                    ("name".into(), Value::String(name.clone())),
+                    ("objid".into(), Value::String(objid.clone())),
+                ])
+            })
+            .collect();
+
+        let mut map = vec![("uid".into(), Value::String(uid))];
+        if let Some(token) = token {
+            map.push(("token".into(), Value::String(token)));
+        }
+        map.push(("dirid".into(), Value::String(self.dirid.clone())));
+        map.push(("format".into(), Value::Number(match self.format {
+            Format::V1 => 1.0,
+            Format::V2 => 2.0,
+        })));
+        map.push(("files".into(), Value::Array(files)));
+
+        Value::Object(map).to_string()
+    }
+
     ///
     /// 从一个实例获取 `filemap` 并扩展到本实例
     ///
@@ -299,7 +1147,7 @@ impl CloudFile {
     ///
     /// 返回一个 `Result` 枚举
     /// - Ok(())
-    /// - Err(std::io::Error)
+    /// - Err(CloudError)
     ///
     /// **Example:**
     /// ```
@@ -319,21 +1167,128 @@ impl CloudFile {
     /// cloud.extend_from_raw(&data)?;
     /// ```
     ///
-    pub fn extend_from_raw(&mut self, raw_data: &[u8]) -> Result<()> {
-        let file = CloudFile::from_raw(&raw_data)?;
+    pub fn extend_from_raw(&mut self, raw_data: &[u8]) -> CloudResult<()> {
+        let file = CloudFile::from_raw(raw_data)?;
         self.filemap.extend_from_slice(&file.filemap);
-        self.update_inner()?;
+        self.touch()?;
 
         Ok(())
     }
 
+    ///
+    /// 合并多个实例备份，生成一个新的实例
+    ///
+    /// 参数：
+    /// - base: `&[u8]` 基础实例备份，新实例使用其 `puid`, `_token`, `fldid`
+    /// - others: `&[&[u8]]` 其他实例备份，仅合并其 `filemap`
+    /// - passwd: `&[u8; 4]` 新实例所使用的密码，要求与 `new` 相同
+    ///
+    /// 合并后的 `filemap` 按 `objid` 去重，保留最先出现的项
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(CloudFile)
+    /// - Err(CloudError)
+    ///     - 任意备份无法解析时，错误信息包含其序号 (`base` 为 0)
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use std::fs::read;
+    /// use sal_file::CloudFile;
+    ///
+    /// let base = read("/root/test.bin")?;
+    /// let other = read("/root/test.old.bin")?;
+    ///
+    /// let cloud = CloudFile::merge(&base, &[&other], &[127, 97, 112, 128])?;
+    /// ```
+    ///
+    pub fn merge(base: &[u8], others: &[&[u8]], passwd: &[u8; 4]) -> CloudResult<CloudFile> {
+        let mut files = Vec::new();
+        for (index, raw_data) in [base].iter().chain(others).enumerate() {
+            match CloudFile::from_raw(raw_data) {
+                Ok(x) => files.push(x),
+                Err(e) => {
+                    let e = Error::from(e);
+                    return Err(Error::new(
+                        e.kind(),
+                        format!("Fail to Merge Data [{index}]: {e}"),
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut files = files.into_iter();
+        let Some(mut base) = files.next() else {
+            return Err(Error::other(format!("Unknown: {}", line!())).into());
+        };
+
+        let (uid, token, dirid) = (take(&mut base.uid), take(&mut base.token), take(&mut base.dirid));
+        let mut cloud = CloudFile::new(uid, token, dirid, passwd)?;
+        let filemap = take(&mut base.filemap);
+        for (name, objid) in filemap.into_iter().chain(files.flat_map(|mut x| take(&mut x.filemap))) {
+            if !cloud.filemap.iter().any(|(_, o)| o == &objid) {
+                cloud.filemap.push((name, objid));
+            }
+        }
+        cloud.update_inner()?;
+
+        Ok(cloud)
+    }
+
+    ///
+    /// 用于比较本实例与另一实例的文件列表
+    ///
+    /// 参数：
+    /// - other: 作为基准的实例，一般为之前的备份
+    ///
+    /// 返回一个 `Diff` 结构体
+    /// - added: 本实例中存在，而 `other` 中不存在的文件
+    /// - removed: `other` 中存在，而本实例中不存在的文件
+    ///
+    /// > 注意，文件以 `objid` 比较，与顺序无关
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let old = CloudFile::from_file("/root/test.bin")?;
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// while let Ok(_) = cloud.scan() {}
+    ///
+    /// for (name, _) in cloud.diff(&old).added {
+    ///     println!("新文件: {name}");
+    /// }
+    /// ```
+    ///
+    pub fn diff(&self, other: &CloudFile) -> Diff {
+        let ours: HashSet<&str> = self.filemap.iter().map(|(_, x)| x.as_str()).collect();
+        let theirs: HashSet<&str> = other.filemap.iter().map(|(_, x)| x.as_str()).collect();
+
+        Diff {
+            added: self
+                .filemap
+                .iter()
+                .filter(|(_, x)| !theirs.contains(x.as_str()))
+                .cloned()
+                .collect(),
+            removed: other
+                .filemap
+                .iter()
+                .filter(|(_, x)| !ours.contains(x.as_str()))
+                .cloned()
+                .collect(),
+        }
+    }
+
     ///
     /// 从云服务器扫描新文件并添加到本实例
     ///
     /// 返回一个 `Result` 枚举
     /// - Ok(usize): 新扫描到的文件数量
-    ///     - 由于传输限制，一次扫描最多4个
-    /// - Err(std::io::Error)
+    ///     - 一次扫描最多100个，更多的文件请多次调用，或使用 `scan_all_pages`
+    /// - Err(CloudError)
     ///
     /// **Example:**
     /// ```
@@ -351,72 +1306,103 @@ impl CloudFile {
     /// while let Ok(_) = cloud.scan() {}
     /// ```
     ///
-    /// 注意：该函数会**自动结束**流!!!
+    /// 注意：该函数会**自动结束** `Scan` 流!!! `Link` 流保持不变
     /// ```
-    /// pub fn scan(&mut self) -> Result<usize> {
+    /// pub fn scan(&mut self) -> CloudResult<usize> {
     ///
     ///     // inner code
     ///
-    ///     self.set_stream(Stream::None)?;
+    ///     self.end_stream(StreamStatus::Scan);
     /// }
     /// ````
     ///
-    pub fn scan(&mut self) -> Result<usize> {
-        let Some(stream) = &self.stream else {
-            return Err(Error::new(
-                ErrorKind::AddrNotAvailable,
-                format!("Stream is Unavailable!"),
-            ));
-        };
+    /// > 若尚未连接 `Stream::Scan`，将自动连接，已连接的 `Stream::Link` 不受影响
+    ///
+    pub fn scan(&mut self) -> CloudResult<usize> {
+        // 非条件请求总是返回 `Some`
+        Ok(self.scan_page(false)?.map_or(0, |x| x.len()))
+    }
 
-        let mut writer = BufWriter::new(stream);
-        let mut reader = BufReader::new(stream);
+    ///
+    /// 与 `scan` 相同，但若服务器表明列表自上次扫描以来未改变，则跳过本次扫描
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(Some(usize)): 同 `scan`，新扫描到的文件数量
+    /// - Ok(None): 列表未改变，不会删除或更新任何文件，并结束流
+    /// - Err(CloudError): 同 `scan`
+    ///
+    /// 依据上次扫描响应中的 `ETag` 或 `Last-Modified` 发送条件请求，
+    /// 服务器返回 `304 Not Modified` 或相同的 `ETag` 时视为未改变；
+    /// 服务器未提供以上信息时，等同于 `scan`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// match cloud.scan_if_changed() {
+    ///     Ok(Some(n)) => println!("新文件: {n}"),
+    ///     Ok(None) => println!("未改变"),
+    ///     Err(e) => println!("{e}"),
+    /// }
+    /// ```
+    ///
+    pub fn scan_if_changed(&mut self) -> CloudResult<Option<usize>> {
+        Ok(self.scan_page(true)?.map(|x| x.len()))
+    }
 
-        writer.write_all(
-            format!(
-                "GET /api/getMyDirAndFiles\
-                ?puid={}&_token={}&fldid={}\
-                &page=1&size=4 HTTP/1.1\r\n\
-                Connection: Keep-Alive\r\n\
-                Host: pan-yz.chaoxing.com\r\n\r\n",
-                self.uid, self.token, self.dirid
-            )
-            .as_bytes(),
-        )?;
-        writer.flush()?;
+    // 返回本次新增的文件，条件请求且列表未改变时返回 `None`
+    fn scan_page(&mut self, conditional: bool) -> CloudResult<Option<Vec<FileEntry>>> {
+        let host = &self.switch_stream(StreamStatus::Scan)?;
 
-        let data = reader.fill_buf()?.to_vec();
+        let mut raw = self.build_scan_request(&self.dirid, 1, SCAN_SIZE);
+        let version = self.version.take();
+        if let (true, Some(version)) = (conditional, &version) {
+            raw.insert_str(raw.len() - 2, &format!("{version}\r\n"));
+        }
 
-        let _ = drop(writer);
-        let _ = drop(reader);
+        let data = self.request(host, &raw)?;
+        if data.is_empty() {
+            return Err(Self::empty_response());
+        }
 
         let data = String::from_utf8_lossy(&data);
-        let Some((_, data)) = data.split_once("\r\n\r\n") else {
-            return Err(Error::new(
-                ErrorKind::UnexpectedEof,
-                "InvalidData Received from Server",
-            ));
+        let Some((head, data)) = data.split_once("\r\n\r\n") else {
+            return Err(CloudError::Parse("InvalidData Received from Server".into()));
         };
 
-        let counter = self.filemap.len();
+        self.version = Self::scan_version(head);
+        if conditional && version.is_some() {
+            let not_modified = head.split(' ').nth(1) == Some("304");
+            let same_etag = self.version == version
+                && version.as_deref().is_some_and(|x| x.starts_with("If-None-Match"));
+            if not_modified || same_etag {
+                self.version = version;
+                self.end_stream(StreamStatus::Scan);
+                return Ok(None);
+            }
+        }
+
+        // 如 `204` 或连接中断时仅有头部，与认证失败区分
+        if data.trim().is_empty() {
+            return Err(Self::empty_response());
+        }
+
+        let mut entries = Vec::new();
         let mut resid = Vec::new();
         if data.contains("\"result\":true") {
             if !data.contains("\"data\":[],") {
                 for file in data[match data.find("[{") {
                     Some(x) => x,
                     None => {
-                        return Err(Error::new(
-                            ErrorKind::ConnectionReset,
-                            "InvalidData Received from Server",
-                        ))
+                        return Err(CloudError::Parse("InvalidData Received from Server".into()))
                     }
-                } + 2..match data.find("}]") {
+                } + 2..match data.rfind("}]") {
                     Some(x) => x,
                     None => {
-                        return Err(Error::new(
-                            ErrorKind::ConnectionReset,
-                            "InvalidData Received from Server",
-                        ))
+                        return Err(CloudError::Parse("InvalidData Received from Server".into()))
                     }
                 }]
                     .split("},{")
@@ -426,16 +1412,10 @@ impl CloudFile {
                         if let Some(o) = file.find("\",\"") {
                             file[..o].to_string()
                         } else {
-                            return Err(Error::new(
-                                ErrorKind::ConnectionReset,
-                                "InvalidData Received from Server",
-                            ));
+                            return Err(CloudError::Parse("InvalidData Received from Server".into()));
                         }
                     } else {
-                        return Err(Error::new(
-                            ErrorKind::ConnectionReset,
-                            "InvalidData Received from Server",
-                        ));
+                        return Err(CloudError::Parse("InvalidData Received from Server".into()));
                     };
 
                     let name = if let Some(o) = file.find("\"name\"") {
@@ -443,73 +1423,134 @@ impl CloudFile {
                         if let Some(o) = file.find("\",\"") {
                             file[..o].to_string()
                         } else {
-                            return Err(Error::new(
-                                ErrorKind::ConnectionReset,
-                                "InvalidData Received from Server",
-                            ));
+                            return Err(CloudError::Parse("InvalidData Received from Server".into()));
                         }
                     } else {
-                        return Err(Error::new(
-                            ErrorKind::ConnectionReset,
-                            "InvalidData Received from Server",
-                        ));
+                        return Err(CloudError::Parse("InvalidData Received from Server".into()));
                     };
 
-                    self.filemap.push((name, objid));
+                    let size = Self::field(file, "size").and_then(|x| x.parse().ok());
+                    if let Some(size) = size {
+                        self.sizes.insert(objid.clone(), size);
+                    }
+                    self.filemap.push((name.clone(), objid.clone()));
                     resid.push(if let Some(o) = file.find("\"residstr\"") {
                         let file = &file[o + 12..];
                         if let Some(o) = file.find("\",\"") {
                             file[..o].to_string()
                         } else {
-                            return Err(Error::new(
-                                ErrorKind::ConnectionReset,
-                                "InvalidData Received from Server",
-                            ));
+                            return Err(CloudError::Parse("InvalidData Received from Server".into()));
                         }
                     } else {
-                        return Err(Error::new(
-                            ErrorKind::ConnectionReset,
-                            "InvalidData Received from Server",
-                        ));
+                        return Err(CloudError::Parse("InvalidData Received from Server".into()));
+                    });
+                    let is_folder = Self::field(file, "isfile") == Some("false");
+                    if is_folder {
+                        self.folders.get_mut().insert(objid.clone());
+                    }
+                    entries.push(FileEntry {
+                        name,
+                        objid,
+                        resid: resid.last().cloned().unwrap_or_default(),
+                        size,
+                        is_folder,
                     });
                 }
             }
         } else {
-            return Err(Error::new(
-                ErrorKind::PermissionDenied,
-                format!("Error Received: {}", data),
-            ));
+            return Err(Self::server_error(data));
         }
 
-        self.delete(&stream, &resid)?;
-        self.update_inner()?;
-        if self.filemap.len() == counter {
-            self.set_stream(Stream::None)?;
-            return Err(Error::new(
-                ErrorKind::WriteZero,
-                format!("Scan Finished: Read 0000!"),
-            ));
+        self.delete(host, &resid)?;
+        self.resids = resid;
+        self.touch()?;
+        if entries.is_empty() {
+            self.end_stream(StreamStatus::Scan);
+            return Err(CloudError::ScanFinished);
         }
 
-        Ok(self.filemap.len() - counter)
+        Ok(Some(entries))
+    }
+
+    // 由响应头中的 `ETag` 或 `Last-Modified` 生成下次扫描的条件请求头，优先使用 `ETag`
+    fn scan_version(head: &str) -> Option<String> {
+        let value = |key: &str| {
+            head.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                let value = value.trim();
+                (name.trim().eq_ignore_ascii_case(key) && !value.is_empty()).then_some(value)
+            })
+        };
+
+        match (value("ETag"), value("Last-Modified")) {
+            (Some(etag), _) => Some(format!("If-None-Match: {etag}")),
+            (None, Some(date)) => Some(format!("If-Modified-Since: {date}")),
+            (None, None) => None,
+        }
     }
 
     ///
-    /// 通过 `objectid` 获取下载链接
+    /// 重复调用 `scan`，直到扫描完所有文件
     ///
     /// 返回一个 `Result` 枚举
-    /// - Ok(String): 对应文件的链接
-    /// - Err(std::io::Error)
+    /// - Ok(usize): 新扫描到的文件总数
+    /// - Err(CloudError): 中途出错时返回，已扫描的文件仍保留在本实例中
     ///
-    /// 直接请求访问下载链接可能会：
-    /// ```
-    /// <h1>403 Forbidden</h1>
-    /// <p>You don't have permission to access the URL on this server.<hr/>Powered by Tengine
+    /// **Example:**
     /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
     ///
-    /// 需要给链接添加 `Referer` Header:
-    /// ```
-    /// `Referer: http://sharewh{id}.xuexi365.com/` id: OPTION(1~4)
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// let n = cloud.scan_all_pages()?;
+    /// println!("新文件: {n}");
+    /// ```
+    ///
+    /// 注意：该函数会**自动结束** `Scan` 流!!!
+    ///
+    /// > 扫描期间延迟编码，结束 (或出错) 时仅编码一次，见 `set_deferred`
+    ///
+    pub fn scan_all_pages(&mut self) -> CloudResult<usize> {
+        self.set_stream(Stream::Scan)?;
+
+        let deferred = std::mem::replace(&mut self.deferred, true);
+        let mut counter = 0;
+        let res = loop {
+            match self.scan() {
+                Ok(n) => counter += n,
+                Err(CloudError::ScanFinished) => break Ok(counter),
+                Err(e) => {
+                    self.end_stream(StreamStatus::Scan);
+                    break Err(e);
+                }
+            }
+        };
+
+        self.deferred = deferred;
+        if !deferred {
+            self.finalize()?;
+        }
+
+        res
+    }
+
+    ///
+    /// 通过 `objectid` 获取下载链接
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(String): 对应文件的链接
+    /// - Err(CloudError)
+    ///
+    /// 直接请求访问下载链接可能会：
+    /// ```
+    /// <h1>403 Forbidden</h1>
+    /// <p>You don't have permission to access the URL on this server.<hr/>Powered by Tengine
+    /// ```
+    ///
+    /// 需要给链接添加 `Referer` Header:
+    /// ```
+    /// `Referer: http://sharewh{id}.xuexi365.com/` id: OPTION(1~4)
     /// ```
     ///
     /// 请求成功会以 `attachment` 形式返回数据。
@@ -545,382 +1586,2968 @@ impl CloudFile {
     ///
     /// 注意：该函数**不会**自动结束流!!!
     ///
-    pub fn get_link(&self, object_id: &String) -> Result<String> {
-        let Some(stream) = &self.stream else {
-            return Err(Error::new(
-                ErrorKind::AddrNotAvailable,
-                format!("Stream is Unavailable!"),
-            ));
-        };
-
-        let mut writer = BufWriter::new(stream);
-        let mut reader = BufReader::new(stream);
+    /// > 若尚未连接 `Stream::Link`，将自动连接，已连接的 `Stream::Scan` 不受影响
+    ///
+    /// > `object_id` 为空或含有控制字符时返回 `ErrorKind::InvalidInput`，不会发送请求；
+    /// > 其他特殊字符 (如 `/` 与空格) 将被URL编码
+    ///
+    /// > 需要文件名、大小等信息时，使用 `get_share_info`
+    ///
+    /// > `object_id` 为文件夹时返回 `CloudError::IsFolder`，请使用 `list_folder` 获取其中的文件
+    ///
+    pub fn get_link(&self, object_id: &str) -> CloudResult<String> {
+        Ok(self.get_share_info(object_id)?.url)
+    }
 
-        writer.write_all(
-            format!(
-                "GET /share/download/{} HTTP/1.1\r\n\
-                Host: sharewh.xuexi365.com\r\n\r\n",
-                object_id
-            )
-            .as_bytes(),
-        )?;
-        writer.flush()?;
+    ///
+    /// 获取 `filemap` 中所有文件的下载链接
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(ExportedLinks): (links, failures)
+    ///     - links: Vec<(name, url)>，成功获取的链接，按 `filemap` 的顺序排列
+    ///     - failures: Vec<(name, CloudError)>，获取失败的文件及其错误，不会中断其他文件
+    /// - Err(CloudError): 无法连接 `Stream::Link`
+    ///
+    /// 所有文件复用同一个 `Link` 流
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.scan_all_pages()?;
+    ///
+    /// let (links, failures) = cloud.export_links()?;
+    /// for (name, url) in links {
+    ///     println!("文件: {name}\r\n直链: {url}\r\n");
+    /// }
+    /// for (name, e) in failures {
+    ///     println!("失败: {name}: {e}");
+    /// }
+    /// ```
+    ///
+    /// 注意：与 `get_link` 相同，该函数**不会**自动结束流!!!
+    ///
+    pub fn export_links(&self) -> CloudResult<ExportedLinks> {
+        self.switch_stream(StreamStatus::Link)?;
 
-        let data = reader.fill_buf()?.to_vec();
+        let (mut links, mut failures) = (Vec::new(), Vec::new());
+        for (name, objid) in &self.filemap {
+            match self.get_share_info(objid) {
+                Ok(x) => links.push((name.clone(), x.url)),
+                Err(e) => failures.push((name.clone(), e)),
+            }
+        }
 
-        let _ = drop(writer);
-        let _ = drop(reader);
+        Ok((links, failures))
+    }
 
-        let data = String::from_utf8_lossy(&data).replace(' ', "");
+    ///
+    /// 通过 `objectid` 获取下载链接，以及文件名、大小与过期时间
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(ShareInfo): 页面中不存在的字段为 `None`
+    /// - Err(CloudError): 与 `get_link` 相同
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// let info = cloud.get_share_info("5f3c2a1b9e8d7c6b5a4f3e2d1c0b9a8f")?;
+    /// println!("{:?} {:?}: {}", info.name, info.size, info.url);
+    /// ```
+    ///
+    /// > 若尚未连接 `Stream::Link`，将自动连接，已连接的 `Stream::Scan` 不受影响
+    ///
+    pub fn get_share_info(&self, object_id: &str) -> CloudResult<ShareInfo> {
+        Self::validate_objid(object_id)?;
+        if self.folders.borrow().contains(object_id) {
+            return Err(CloudError::IsFolder);
+        }
+        let host = &self.switch_stream(StreamStatus::Link)?;
 
-        let mut res = String::new();
-        if let Some(x) = data.find("vardownloadUrl='") {
-            let Some((data, _)) = data[x + 16..].split_once("';\r\n") else {
-                return Err(Error::new(
-                    ErrorKind::ConnectionReset,
-                    "InvalidData Received from Server",
-                ));
-            };
-            res.push_str(data);
-        } else if data.contains("获取下载地址失败") {
-            return Err(Error::new(
-                ErrorKind::NotFound,
-                "Download Link Not Found: Check ObjectID!",
-            ));
-        } else {
-            let Some((_, data)) = data.split_once("\r\n\r\n") else {
-                return Err(Error::new(
-                    ErrorKind::UnexpectedEof,
-                    "InvalidData Received from Server",
-                ));
-            };
+        let data = self.request(host, &self.build_link_request(object_id))?;
 
-            return Err(Error::new(
-                ErrorKind::UnexpectedEof,
-                format!("InvalidData Received: {}", data),
-            ));
+        let data = String::from_utf8_lossy(&data);
+        let Some((_, data)) = data.split_once("\r\n\r\n") else {
+            return Err(CloudError::Parse("InvalidData Received from Server".into()));
         };
 
-        Ok(res)
+        if let Some(url) = Self::page_value(data, "downloadUrl").filter(|x| !x.is_empty()) {
+            let find = |keys: &[&str]| keys.iter().find_map(|x| Self::page_value(data, x));
+            let number = |keys: &[&str]| keys.iter().find_map(|x| Self::page_number(data, x));
+
+            return Ok(ShareInfo {
+                url,
+                name: find(&["fileName", "filename"]).filter(|x| !x.is_empty()),
+                size: number(&["fileSize", "filesize"]),
+                expires: number(&["expireTime", "expires"]),
+            });
+        }
+        if Self::page_flag(data, "isfile") == Some(false) || Self::page_flag(data, "isFolder") == Some(true) {
+            self.folders.borrow_mut().insert(object_id.into());
+            return Err(CloudError::IsFolder);
+        }
+        if data.contains("获取下载地址失败") {
+            return Err(CloudError::NotFound);
+        }
+
+        Err(CloudError::Parse(format!(
+            "Download Link Not Found (Tried: var downloadUrl='...', \"downloadUrl\":\"...\", \
+            downloadUrl = \"...\"): {data}"
+        )))
     }
 
     ///
-    /// 用于为实例开启流式通道，与服务器连接
+    /// 获取分享文件夹中的文件列表
     ///
     /// 参数：
-    /// - stream: `Stream` 枚举
-    ///     - Stream::Scan => 与超星云盘服务器连接
-    ///     - Stream::Link => 与超星下载服务器连接
-    ///     - Stream::None => 与服务器断开连接
+    /// - object_id: `&str` 文件夹的 `id`
     ///
     /// 返回一个 `Result` 枚举
-    /// - Ok(())
-    /// - Err(std::io::Error)
+    /// - Ok(Vec<FileEntry>): 文件夹中的文件（与文件夹）
+    ///     - 仅获取第一页，最多100项
+    /// - Err(CloudError)
+    ///
+    /// 与 `scan` 不同，该函数**不会**修改 `filemap`，也**不会**删除服务器上的文件，
+    /// 且无需调用 `set_stream`
+    ///
+    /// > `object_id` 为空或含有控制字符时返回 `ErrorKind::InvalidInput`，不会发送请求；
+    /// > 其他特殊字符 (如 `/` 与空格) 将被URL编码
     ///
     /// **Example:**
     /// ```
     /// mod sal_file;
-    /// use sal_file::{CloudFile, Stream};
+    /// use sal_file::CloudFile;
     ///
-    /// let mut cloud = CloudFile::new(
+    /// let cloud = CloudFile::new(
     ///     "29*******".into(),
     ///     "b8***391*******d3726f*******d0b2".into(),
     ///     "94***555*******592".into(),
     ///     &[127, 97, 112, 128],
     /// )?;
     ///
-    /// cloud.set_stream(Stream::Scan)?;
-    /// while let Ok(_) = cloud.scan() {}
-    ///
-    /// cloud.set_stream(Stream::Link)?;
-    /// let _ = cloud.get_link()?;
-    /// cloud.set_stream(Stream::None)?;
+    /// for file in cloud.list_folder("94***555*******593")? {
+    ///     println!("{}: {}", file.name, file.objid);
+    /// }
     /// ```
     ///
-    pub fn set_stream(&mut self, stream: Stream) -> Result<()> {
-        match stream {
-            Stream::Scan => self.stream = Some(TcpStream::connect(HOST_SCAN)?),
-            Stream::Link => self.stream = Some(TcpStream::connect(HOST_LINK)?),
-            Stream::None => self.stream = None,
-        }
-
-        Ok(())
+    pub fn list_folder(&self, object_id: &str) -> CloudResult<Vec<FileEntry>> {
+        Self::validate_objid(object_id)?;
+        self.list_page(&self.hosts.0, object_id, 1)
     }
 
     ///
-    /// 用于获取 `filemap` 的引用
+    /// 从云服务器扫描新文件并添加到本实例，但**不会**删除服务器上的文件
     ///
-    /// 返回
-    /// - &[(String, String)]
-    ///     - 文件表：(name, objectid)
-    ///     - name: 文件名
-    ///     - objectid: 用于从服务器下载文件
+    /// 返回一个 `Result` 枚举
+    /// - Ok(Vec<FileEntry>): 本次新增的文件，`filemap` 中已有的 `objid` 不会重复添加
+    ///     - 没有新文件时返回空的 `Vec`
+    /// - Err(CloudError): 同 `scan`，出错时 `filemap` 保持不变
+    ///
+    /// 与 `scan` 不同，该函数按页列出文件夹中的全部文件，只读不删；
+    /// 列出的全部 `residstr` 可通过 `last_scan_resids` 获取，由调用者自行管理
+    ///
+    /// 返回值即本次新增的文件，可直接交给通知或日志，无需自行计算 `diff`
     ///
     /// **Example:**
     /// ```
     /// mod sal_file;
     /// use sal_file::CloudFile;
     ///
-    /// let mut cloud = CloudFile::new(
-    ///     "29*******".into(),
-    ///     "b8***391*******d3726f*******d0b2".into(),
-    ///     "94***555*******592".into(),
-    ///     &[127, 97, 112, 128],
-    /// )?;
-    ///
-    /// cloud.set_stream(Stream::Scan)?;
-    /// while let Ok(_) = cloud.scan() {}
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
     ///
-    /// let map = cloud.get_filemap();
+    /// for file in cloud.scan_preserve()? {
+    ///     println!("新文件: {} ({})", file.name, file.resid);
+    /// }
+    /// println!("服务器上共 {} 个文件", cloud.last_scan_resids().len());
     /// ```
     ///
-    pub fn get_filemap(&self) -> &[(String, String)] {
-        &self.filemap
-    }
+    /// 注意：与 `scan` 相同，该函数会**自动结束** `Scan` 流!!!
+    ///
+    pub fn scan_preserve(&mut self) -> CloudResult<Vec<FileEntry>> {
+        let host = &self.switch_stream(StreamStatus::Scan)?;
 
-    fn update_inner(&mut self) -> Result<()> {
-        /*  File:
-         *  3, 3, 4, 21,   //  [0, 4]    FileHeader
-         *  7, 23, 10, 8   //  [4, 8]    FileHeader
-         *  2, 5, 1, 3,    //  [8, 12]   Password
-         *  25, 0, 0, 3,   //  [12, 16]  ETX
-         *  ...........    //  [16, ..]  EnCodedData
-         *
-         * EnCodedData:
-         *  ...........    //  [16, 144]   BaseData
-         *  ...........    //  [144, ..]   ListData
-         *
-         * DeCodedData:
-         *  ...........    //  [0, 64]   BaseData
-         *  ...........    //  [64, ..]  ListData
-         *
-         * */
+        let mut listed = Vec::new();
+        let mut seen = HashSet::new();
+        for page in 1.. {
+            let files = match self.list_page(host, &self.dirid, page) {
+                Ok(x) => x,
+                Err(e) => {
+                    self.end_stream(StreamStatus::Scan);
+                    return Err(e);
+                }
+            };
 
-        if self.inner.len() < 144 {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Len of Data to Short: [144..]",
-            ));
+            // 服务器忽略 `page` 而重复返回同一页时，同样视为结束
+            let last = files.len() < SCAN_SIZE;
+            let before = seen.len();
+            for file in files {
+                if seen.insert(file.objid.clone()) {
+                    listed.push(file);
+                }
+            }
+            if last || seen.len() == before {
+                break;
+            }
         }
+        self.end_stream(StreamStatus::Scan);
 
-        let inner = self.inner.clone();
-        let [_, _, passwd, _] = inner.chunks(4).take(4).collect::<Vec<&[u8]>>()[..] else {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("Unknown: {}", line!()),
-            ));
-        };
+        let known: HashSet<&str> = self.filemap.iter().map(|(_, x)| x.as_str()).collect();
+        let added: Vec<FileEntry> = listed
+            .iter()
+            .filter(|x| !known.contains(x.objid.as_str()))
+            .cloned()
+            .collect();
 
-        let passwd: &[u8; 4] = match passwd.try_into() {
-            Ok(x) => x,
-            Err(_) => {
-                return Err(Error::new(
-                    ErrorKind::Unsupported,
-                    "Wrong Password Type: Unsupported Password Type",
-                ))
+        self.resids = listed.into_iter().map(|x| x.resid).collect();
+        for file in &added {
+            if let Some(size) = file.size {
+                self.sizes.insert(file.objid.clone(), size);
             }
-        };
-
-        let mut data = vec![
-            self.uid.as_bytes(),
-            self.token.as_bytes(),
-            self.dirid.as_bytes(),
-        ]
-        .join(&[27u8][..]); // Sep By \u{1B}
-        while data.len() < 64 {
-            data.push(0);
+            self.filemap.push((file.name.clone(), file.objid.clone()));
+        }
+        if !added.is_empty() {
+            self.touch()?;
         }
 
-        data.extend_from_slice(
-            &self
-                .filemap
-                .iter()
-                .map(|(name, objid)| vec![name.as_bytes(), objid.as_bytes()].join(&[26u8][..]))
-                .collect::<Vec<Vec<u8>>>()
-                .join(&[27u8][..]),
-        );
-
-        let data = Self::matrix_encode(passwd, &data)?;
-        let data = Self::sixteen_to_eight(&data);
-
-        self.inner = vec![3, 3, 4, 21, 7, 23, 10, 8];
-        self.inner.extend_from_slice(passwd);
-        self.inner.extend_from_slice(&[25, 0, 0, 3]);
-        self.inner.extend_from_slice(&data);
-
-        Ok(())
+        Ok(added)
     }
 
-    fn delete(&self, stream: &TcpStream, resid: &[String]) -> Result<bool> {
-        if resid.len() == 0 {
-            return Ok(true);
+    // 列出 `dirid` 中的一页文件 (最多 `SCAN_SIZE` 项)，只读不删
+    fn list_page(&self, host: &str, dirid: &str, page: usize) -> CloudResult<Vec<FileEntry>> {
+        let data = self.request(host, &self.build_scan_request(dirid, page, SCAN_SIZE))?;
+        if data.is_empty() {
+            return Err(Self::empty_response());
         }
 
-        let mut writer = BufWriter::new(stream);
-        let mut reader = BufReader::new(stream);
-
-        writer.write_all(
-            format!(
-                "GET /api/delete\
-                ?puid={}&_token={}\
-                &resids={} HTTP/1.1\r\n\
-                Host: pan-yz.chaoxing.com\r\n\r\n",
-                self.uid,
-                self.token,
-                resid.join(","),
-            )
-            .as_bytes(),
-        )?;
-
-        let _ = writer.flush()?;
-        let data = reader.fill_buf()?.to_vec();
-
-        let _ = drop(writer);
-        let _ = drop(reader);
-
         let data = String::from_utf8_lossy(&data);
-        let data = match data.split_once("\r\n\r\n") {
-            Some((_, x)) => x,
-            None => {
-                return Err(Error::new(
-                    ErrorKind::ConnectionReset,
-                    "InvalidData Received from Server",
-                ))
-            }
+        let Some((_, data)) = data.split_once("\r\n\r\n") else {
+            return Err(CloudError::Parse("InvalidData Received from Server".into()));
         };
+        if data.trim().is_empty() {
+            return Err(Self::empty_response());
+        }
 
-        if data.contains("\"result\":true") {
-            if data.contains("\"success\":false") {
-                return Ok(false);
-            }
-        } else {
-            return Err(Error::new(
-                ErrorKind::PermissionDenied,
-                format!("Error Received: {}", data),
-            ));
+        if !data.contains("\"result\":true") {
+            return Err(Self::server_error(data));
         }
 
-        Ok(true)
-    }
+        let (Some(fron), Some(back)) = (data.find("[{"), data.rfind("}]")) else {
+            return Ok(Vec::new()); // "data":[]
+        };
 
-    fn matrix_encode(passwd: &[u8; 4], data: &[u8]) -> Result<Vec<u16>> {
-        let [a, b, c, d] = passwd.map(|x| x as u16);
+        let mut res = Vec::new();
+        for file in data[fron + 2..back].split("},{") {
+            let (Some(name), Some(objid)) = (Self::field(file, "name"), Self::field(file, "objectId"))
+            else {
+                return Err(CloudError::Parse("InvalidData Received from Server".into()));
+            };
 
-        for p in passwd {
-            if p > &128 {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    "Passwd Too Big: 0..=128",
-                ));
+            let is_folder = Self::field(file, "isfile") == Some("false");
+            if is_folder {
+                self.folders.borrow_mut().insert(objid.into());
             }
+            res.push(FileEntry {
+                name: name.into(),
+                objid: objid.into(),
+                resid: Self::field(file, "residstr").unwrap_or_default().into(),
+                size: Self::field(file, "size").and_then(|x| x.parse().ok()),
+                is_folder,
+            });
         }
 
-        if a * d <= b * c {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Wrong Passwd: the Val MUST be POSITIVE",
-            ));
-        }
+        Ok(res)
+    }
 
-        let mut res = Vec::new();
-        let len = data.len();
-        let max = len >> 1;
+    ///
+    /// 以流式方式上传文件至实例的文件夹，并添加到本实例
+    ///
+    /// 参数：
+    /// - name: 文件名
+    /// - reader: 文件数据，边读取边发送，不会一次性载入内存
+    /// - len: 文件长度，用于计算 `Content-Length`
+    /// - progress: 进度回调 `(已发送字节数, 总字节数)`
+    ///     - 发送过程中多次调用，完成时以最终字节数调用一次
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(String): 上传后文件的 `objid`
+    /// - Err(CloudError)
+    ///     - CloudError::Io(UnexpectedEof) => `reader` 的数据少于 `len`，连接将被断开
+    ///
+    /// > 注意，`reader` 中超出 `len` 的数据将被忽略
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// let file = std::fs::File::open("/root/video.mp4")?;
+    /// let len = file.metadata()?.len();
+    /// let objid = cloud.upload_stream("video.mp4", file, len, |done, total| {
+    ///     println!("{done} / {}", total.unwrap_or(0));
+    /// })?;
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn upload_stream(
+        &mut self,
+        name: &str,
+        reader: impl Read,
+        len: u64,
+        progress: impl FnMut(u64, Option<u64>),
+    ) -> CloudResult<String> {
+        let host = &self.switch_stream(StreamStatus::Scan)?;
+        let (head, prefix, suffix) = self.build_upload_request(name, len);
 
-        let mut i = 0;
-        while i < max {
-            res.push(a * data[2 * i] as u16 + b * data[2 * i + 1] as u16);
-            res.push(c * data[2 * i] as u16 + d * data[2 * i + 1] as u16);
+        let mut body = prefix
+            .as_bytes()
+            .chain(Exact {
+                inner: reader,
+                left: len,
+                done: 0,
+                finished: false,
+                progress,
+            })
+            .chain(suffix.as_bytes());
+        let data = self.request_stream(host, &head, &mut body)?;
 
-            i += 1;
-        }
+        let data = String::from_utf8_lossy(&data);
+        let Some((_, data)) = data.split_once("\r\n\r\n") else {
+            return Err(CloudError::Parse("InvalidData Received from Server".into()));
+        };
 
-        if len % 2 == 1 {
-            res.push(a as u16 * data[len - 1] as u16);
-            res.push(c as u16 * data[len - 1] as u16);
+        if !data.contains("\"result\":true") {
+            return Err(Self::server_error(data));
+        }
+
+        let Some(objid) = Self::field(data, "objectId") else {
+            return Err(CloudError::Parse("InvalidData Received from Server".into()));
+        };
+
+        let objid = objid.to_string();
+        self.sizes.insert(objid.clone(), len);
+        self.filemap.push((name.into(), objid.clone()));
+        self.touch()?;
+
+        Ok(objid)
+    }
+
+    ///
+    /// 通过 `objectid` 生成文件的预览页面链接
+    ///
+    /// 返回
+    /// - String: `http://cloud.ananas.chaoxing.com/view/fileview?objectid={objid}`
+    ///     - `objid` 会进行URL编码
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let url = CloudFile::view_url("a1b2c3");
+    /// assert_eq!(url, "http://cloud.ananas.chaoxing.com/view/fileview?objectid=a1b2c3");
+    /// ```
+    ///
+    pub fn view_url(object_id: &str) -> String {
+        format!(
+            "http://cloud.ananas.chaoxing.com/view/fileview?objectid={}",
+            urlcode::encode(object_id)
+        )
+    }
+
+    ///
+    /// 通过 `objectid` 生成文件的分享页面链接
+    ///
+    /// 返回
+    /// - String: `http://sharewh.xuexi365.com/share/download/{objid}`
+    ///     - `objid` 会进行URL编码
+    ///     - 即 `get_link` 所请求的页面
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let url = CloudFile::share_url("a1b2c3");
+    /// assert_eq!(url, "http://sharewh.xuexi365.com/share/download/a1b2c3");
+    /// ```
+    ///
+    pub fn share_url(object_id: &str) -> String {
+        format!(
+            "http://sharewh.xuexi365.com/share/download/{}",
+            urlcode::encode(object_id)
+        )
+    }
+
+    ///
+    /// 用于获取最近一次 `scan` 或 `scan_preserve` 所处理文件的 `residstr`
+    ///
+    /// 返回一个 `&[String]` 切片，未扫描时为空
+    ///
+    /// > 注意，`scan` 处理的文件已从服务器上删除，仅用于记录；
+    /// > `scan_preserve` 列出的文件仍保留在服务器上，可自行管理
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// while let Ok(_) = cloud.scan() {
+    ///     println!("{:?}", cloud.last_scan_resids());
+    /// }
+    /// ```
+    ///
+    pub fn last_scan_resids(&self) -> &[String] {
+        &self.resids
+    }
+
+    ///
+    /// 用于为实例开启流式通道，与服务器连接
+    ///
+    /// 参数：
+    /// - stream: `Stream` 枚举
+    ///     - Stream::Scan => 与超星云盘服务器连接
+    ///     - Stream::Link => 与超星下载服务器连接
+    ///     - Stream::None => 与所有服务器断开连接
+    ///
+    /// `Scan` 与 `Link` 互不影响，可同时保持，用于交替调用 `scan` 与 `get_link`
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError)
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::{CloudFile, Stream};
+    ///
+    /// let mut cloud = CloudFile::new(
+    ///     "29*******".into(),
+    ///     "b8***391*******d3726f*******d0b2".into(),
+    ///     "94***555*******592".into(),
+    ///     &[127, 97, 112, 128],
+    /// )?;
+    ///
+    /// cloud.set_stream(Stream::Scan)?;
+    /// while let Ok(_) = cloud.scan() {}
+    ///
+    /// cloud.set_stream(Stream::Link)?;
+    /// let _ = cloud.get_link()?;
+    /// cloud.set_stream(Stream::None)?;
+    /// ```
+    ///
+    pub fn set_stream(&mut self, stream: Stream) -> CloudResult<()> {
+        let (opened, host) = match stream {
+            Stream::Scan => (&self.scan_stream, &self.hosts.0),
+            Stream::Link => (&self.link_stream, &self.hosts.1),
+            Stream::None => {
+                self.scan_stream.set(false);
+                self.link_stream.set(false);
+                self.transport.get_mut().close();
+                return Ok(());
+            }
+        };
+
+        self.transport.get_mut().connect(host)?;
+        opened.set(true);
+
+        Ok(())
+    }
+
+    ///
+    /// 用于获取实例当前的流状态
+    ///
+    /// 返回一个 `StreamStatus` 枚举
+    /// - StreamStatus::Disconnected => 未调用 `set_stream` 或流已结束
+    /// - StreamStatus::Scan => 可调用 `scan`
+    /// - StreamStatus::Link => 可调用 `get_link`
+    ///
+    /// > 注意，`scan` 扫描至结束时会自动结束 `Scan` 流
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::{CloudFile, Stream, StreamStatus};
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// assert_eq!(cloud.stream_status(), StreamStatus::Disconnected);
+    ///
+    /// cloud.set_stream(Stream::Link)?;
+    /// assert_eq!(cloud.stream_status(), StreamStatus::Link);
+    /// ```
+    ///
+    pub fn stream_status(&self) -> StreamStatus {
+        match (self.scan_stream.get(), self.link_stream.get()) {
+            (false, false) => StreamStatus::Disconnected,
+            (true, false) => StreamStatus::Scan,
+            (false, true) => StreamStatus::Link,
+            (true, true) => StreamStatus::Both,
+        }
+    }
+
+    ///
+    /// 用于设置实例请求的最小间隔，避免触发服务器的频率限制
+    ///
+    /// 参数：
+    /// - min_interval: 两次请求之间的最小间隔，默认为 `Duration::ZERO`
+    ///     - 作用于 `scan`, `get_link`, `list_folder` 及其内部的删除请求
+    ///
+    /// > 注意，间隔不足时将**阻塞**当前线程
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    /// use std::time::Duration;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.set_rate_limit(Duration::from_millis(500));
+    /// while let Ok(_) = cloud.scan() {}
+    /// ```
+    ///
+    pub fn set_rate_limit(&mut self, min_interval: Duration) {
+        self.interval = min_interval;
+    }
+
+    ///
+    /// 用于设置连接中断时的最大重试次数
+    ///
+    /// 参数：
+    /// - retries: 最大重试次数，默认为 `3`，为 `0` 时不重试
+    ///     - 作用于 `scan`, `get_link`, `list_folder` 及其内部的删除请求
+    ///     - 仅在连接被重置或中断 (`ConnectionReset` `ConnectionAborted` `BrokenPipe`) 时重试
+    ///     - 认证失败等服务器返回的错误不会重试
+    ///
+    /// 每次重试前重新连接，等待时间从 `200ms` 开始逐次翻倍；
+    /// 重试不会影响已扫描到的 `filemap`
+    ///
+    /// > 注意，等待期间将**阻塞**当前线程
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.set_retries(5);
+    /// while let Ok(_) = cloud.scan() {}
+    /// ```
+    ///
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
+    ///
+    /// 用于设置实例备份文件的格式
+    ///
+    /// 参数：
+    /// - format: `Format` 枚举
+    ///     - Format::V1 => 默认格式，兼容旧版本
+    ///     - Format::V2 => 长度前缀格式，文件名可包含分隔符
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError)
+    ///
+    /// > 注意，`from_raw` 会自动识别格式，并在之后的更新中保持该格式
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::{CloudFile, Format};
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.set_format(Format::V2)?;
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn set_format(&mut self, format: Format) -> CloudResult<()> {
+        self.format = format;
+        Ok(self.update_inner()?)
+    }
+
+    ///
+    /// 更换本地储存数据时所使用的密码，并以新密码重新编码实例
+    ///
+    /// 参数：
+    /// - new_passwd: `&[u8; 4]` 新密码，要求与 `new` 相同
+    ///     - 每一位的范围为 `0..=128`
+    ///     - 必须保证密码的行列式大于零
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError): 新密码不合法或编码失败时，实例保持不变
+    ///
+    /// 之后的 `to_file` `as_ref` 均使用新密码，`from_raw` 可直接读取
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.passwd_rotate(&[100, 3, 7, 90])?;
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn passwd_rotate(&mut self, new_passwd: &[u8; 4]) -> CloudResult<()> {
+        Self::validate_passwd(new_passwd)?;
+
+        let Some(passwd) = self.inner.get_mut(8..12) else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Len of Data to Short: [16..]").into());
+        };
+        let mut old = [0; 4];
+        old.copy_from_slice(passwd);
+        passwd.copy_from_slice(new_passwd);
+
+        if let Err(e) = self.update_inner() {
+            self.inner[8..12].copy_from_slice(&old); // 编码失败时恢复原密码
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 用于设置请求时所使用的 `User-Agent`
+    ///
+    /// 参数：
+    /// - agent: `&str` 新的 `User-Agent`
+    ///     - 默认为浏览器的 `User-Agent`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::new(
+    ///     "29*******".into(),
+    ///     "b8***391*******d3726f*******d0b2".into(),
+    ///     "94***555*******592".into(),
+    ///     &[127, 97, 112, 128],
+    /// )?;
+    ///
+    /// cloud.set_user_agent("Mozilla Curl Saloxy");
+    /// ```
+    ///
+    pub fn set_user_agent(&mut self, agent: &str) {
+        self.agent = agent.into();
+    }
+
+    ///
+    /// 用于设置请求的服务器，可指向镜像或本地测试服务器
+    ///
+    /// 参数：
+    /// - scan: 云盘服务器，默认为 `pan-yz.chaoxing.com:80`
+    /// - link: 下载服务器，默认为 `sharewh.xuexi365.com:80`
+    ///     - 格式为 `host` 或 `host:port`，省略端口时为 `80`
+    ///
+    /// 作用于 `set_stream` 的连接目标与请求的 `Host` 头部，
+    /// 当前的流将被结束，需要重新调用 `set_stream`
+    ///
+    /// > `Host` 头部仅省略 `http` 的默认端口 `80`，其他端口 (包括 `443`) 均保留，
+    /// > 如 `127.0.0.1:8080` => `Host: 127.0.0.1:8080`
+    ///
+    /// > 注意，`share_url` `view_url` 返回的链接不受影响
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.set_hosts("127.0.0.1:8080", "127.0.0.1:8081");
+    /// ```
+    ///
+    pub fn set_hosts(&mut self, scan: &str, link: &str) {
+        let with_port = |host: &str| match host.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() && !host.ends_with(']') => host.to_string(),
+            _ => format!("{host}:80"),
+        };
+
+        self.hosts = (with_port(scan), with_port(link));
+        self.scan_stream.set(false);
+        self.link_stream.set(false);
+        self.transport.get_mut().close();
+    }
+
+    ///
+    /// 用于替换实例与服务器交流的方式
+    ///
+    /// 参数：
+    /// - transport: `Box<dyn Transport>` 新的网络传输实现
+    ///     - 默认为 `TcpTransport`
+    ///     - 替换后需要重新调用 `set_stream`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::{CloudFile, TcpTransport};
+    ///
+    /// let mut cloud = CloudFile::new(
+    ///     "29*******".into(),
+    ///     "b8***391*******d3726f*******d0b2".into(),
+    ///     "94***555*******592".into(),
+    ///     &[127, 97, 112, 128],
+    /// )?;
+    ///
+    /// cloud.set_transport(Box::new(TcpTransport::default()));
+    /// ```
+    ///
+    pub fn set_transport(&mut self, transport: Box<dyn Transport>) {
+        self.scan_stream.set(false);
+        self.link_stream.set(false);
+        self.transport = RefCell::new(transport);
+    }
+
+    ///
+    /// 设置是否延迟编码，默认为 `false`
+    ///
+    /// 每次 `scan` `upload_stream` `extend_from_raw` 后都会重新编码整个实例，
+    /// 耗时与 `filemap` 的长度成正比，多次调用时总耗时为平方级别
+    ///
+    /// 开启后仅标记待更新，由 `finalize` 统一编码一次：
+    /// - `to_file` 总是写入最新的数据
+    /// - `as_ref` 在 `finalize` 之前返回上次编码的数据
+    ///
+    /// 关闭时若有待更新的数据，将立即编码
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    ///
+    /// cloud.set_deferred(true)?;
+    /// while let Ok(_) = cloud.scan() {}
+    /// cloud.finalize()?;
+    /// ```
+    ///
+    pub fn set_deferred(&mut self, deferred: bool) -> CloudResult<()> {
+        self.deferred = deferred;
+        if !deferred {
+            self.finalize()?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 编码延迟的更新，没有待更新的数据时不做任何操作
+    ///
+    pub fn finalize(&mut self) -> CloudResult<()> {
+        if self.dirty {
+            self.update_inner()?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 用于获取 `filemap` 的引用
+    ///
+    /// 返回
+    /// - &[(String, String)]
+    ///     - 文件表：(name, objectid)
+    ///     - name: 文件名
+    ///     - objectid: 用于从服务器下载文件
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::new(
+    ///     "29*******".into(),
+    ///     "b8***391*******d3726f*******d0b2".into(),
+    ///     "94***555*******592".into(),
+    ///     &[127, 97, 112, 128],
+    /// )?;
+    ///
+    /// cloud.set_stream(Stream::Scan)?;
+    /// while let Ok(_) = cloud.scan() {}
+    ///
+    /// let map = cloud.get_filemap();
+    /// ```
+    ///
+    pub fn get_filemap(&self) -> &[(String, String)] {
+        &self.filemap
+    }
+
+    ///
+    /// 获取 `filemap` 中的文件数量
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::builder()
+    ///     .uid("29*******")
+    ///     .token("b8***391*******d3726f*******d0b2")
+    ///     .dirid("94***555*******592")
+    ///     .passwd(&[127, 97, 112, 128])
+    ///     .build()?;
+    /// assert!(cloud.is_empty());
+    ///
+    /// cloud.scan_all_pages()?;
+    /// for (name, objid) in &cloud {
+    ///     println!("{name}: {objid}");
+    /// }
+    /// println!("{}", cloud.len());
+    /// ```
+    ///
+    pub fn len(&self) -> usize {
+        self.filemap.len()
+    }
+
+    ///
+    /// `filemap` 是否为空
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.filemap.is_empty()
+    }
+
+    ///
+    /// 根据 `objid` 查找文件名
+    ///
+    /// - Some(name): `filemap` 中对应的文件名
+    /// - None: 未找到该 `objid`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    /// let name = cloud.name_of("19*******58");
+    /// ```
+    ///
+    pub fn name_of(&self, objid: &str) -> Option<&str> {
+        self.filemap.iter().find(|(_, o)| o == objid).map(|(n, _)| n.as_str())
+    }
+
+    ///
+    /// 根据文件名查找 `objid`
+    ///
+    /// > 注意，文件名不保证唯一，仅返回第一个匹配项
+    ///
+    /// - Some(objid): `filemap` 中第一个同名文件的 `objid`
+    /// - None: 未找到该文件名
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let cloud = CloudFile::from_file("/root/test.bin")?;
+    /// let objid = cloud.objid_of("test.txt");
+    /// ```
+    ///
+    pub fn objid_of(&self, name: &str) -> Option<&str> {
+        self.filemap.iter().find(|(n, _)| n == name).map(|(_, o)| o.as_str())
+    }
+
+    ///
+    /// 清空 `filemap`，并重新编码实例
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError)
+    ///
+    /// > 注意，仅清空本地的文件表，**不会**删除服务器上的任何文件；
+    /// > 开启 `set_deferred` 时，由 `finalize` 统一编码
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.clear_filemap()?;
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn clear_filemap(&mut self) -> CloudResult<()> {
+        self.filemap.clear();
+        self.sizes.clear();
+        Ok(self.touch()?)
+    }
+
+    ///
+    /// 将实例重置为仅包含 `puid` `_token` `fldid` 的状态，并立即重新编码
+    ///
+    /// 与 `clear_filemap` 不同，同时清除本次运行中记录的文件大小、`residstr` 与扫描状态，
+    /// 且不受 `set_deferred` 影响，之后的 `as_ref` `to_file` 即为不含文件的备份
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(CloudError)
+    ///
+    /// > 注意，**不会**删除服务器上的任何文件
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.reset_to_empty()?;
+    /// cloud.to_file("/root/test.bin")?;
+    /// ```
+    ///
+    pub fn reset_to_empty(&mut self) -> CloudResult<()> {
+        self.filemap.clear();
+        self.sizes.clear();
+        self.resids.clear();
+        self.version = None;
+        Ok(self.update_inner()?)
+    }
+
+    ///
+    /// 计算 `filemap` 中所有文件的总大小 (Byte)
+    ///
+    /// 返回一个 `Option` 枚举
+    /// - Some(u64): 所有文件的大小之和，`filemap` 为空时为 `0`
+    /// - None: 存在大小未知的文件，避免返回误导性的部分和
+    ///
+    /// > 注意，文件大小仅在本次运行中由 `scan` `upload_stream` 获取，
+    /// > 不会写入备份，从备份导入的文件大小均未知
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_file;
+    /// use sal_file::CloudFile;
+    ///
+    /// let mut cloud = CloudFile::from_file("/root/test.bin")?;
+    /// cloud.scan_all_pages()?;
+    ///
+    /// match cloud.total_size() {
+    ///     Some(x) => println!("总大小: {x}"),
+    ///     None => println!("已知大小: {}", cloud.total_size_known()),
+    /// }
+    /// ```
+    ///
+    pub fn total_size(&self) -> Option<u64> {
+        self.filemap
+            .iter()
+            .map(|(_, objid)| self.sizes.get(objid))
+            .sum::<Option<u64>>()
+    }
+
+    ///
+    /// 计算 `filemap` 中已知大小的文件的总大小 (Byte)，忽略大小未知的文件
+    ///
+    pub fn total_size_known(&self) -> u64 {
+        self.filemap
+            .iter()
+            .filter_map(|(_, objid)| self.sizes.get(objid))
+            .sum()
+    }
+
+    fn touch(&mut self) -> Result<()> {
+        if self.deferred {
+            self.dirty = true;
+            return Ok(());
+        }
+
+        self.update_inner()
+    }
+
+    fn update_inner(&mut self) -> Result<()> {
+        self.inner = self.encode()?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        /*  File:
+         *  3, 3, 4, 21,   //  [0, 4]    FileHeader
+         *  7, 23, 10, 8   //  [4, 8]    FileHeader
+         *  2, 5, 1, 3,    //  [8, 12]   Password
+         *  25, 0, 0, 3,   //  [12, 16]  ETX (V1)
+         *  25, 2, 0, 3,   //  [12, 16]  ETX (V2)
+         *  ...........    //  [16, ..]  EnCodedData
+         *
+         * EnCodedData (V1):
+         *  ...........    //  [16, 144]   BaseData
+         *  ...........    //  [144, ..]   ListData
+         *
+         * DeCodedData (V1):
+         *  ...........    //  [0, 64]   BaseData
+         *  ...........    //  [64, ..]  ListData
+         *
+         * DeCodedData (V2):
+         *  len, uid, len, token, len, dirid,       // BaseData
+         *  len, name, len, objid, ............     // ListData
+         *
+         * */
+
+        if self.inner.len() < 16 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Len of Data to Short: [16..]",
+            ));
+        }
+
+        let [_, _, passwd, _] = self.inner.chunks(4).take(4).collect::<Vec<&[u8]>>()[..] else {
+            return Err(Error::other(format!("Unknown: {}", line!())));
+        };
+
+        let passwd: &[u8; 4] = match passwd.try_into() {
+            Ok(x) => x,
+            Err(_) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "Wrong Password Type: Unsupported Password Type",
+                ))
+            }
+        };
+
+        let mut data = match self.format {
+            Format::V1 => self.plain_v1(),
+            Format::V2 => self.plain_v2()?,
+        };
+
+        let encoded = Self::matrix_encode(passwd, &data);
+        wipe(&mut data);
+        let data = Self::sixteen_to_eight(&encoded?);
+
+        let mut inner = vec![3, 3, 4, 21, 7, 23, 10, 8];
+        inner.extend_from_slice(passwd);
+        inner.extend_from_slice(match self.format {
+            Format::V1 => &[25, 0, 0, 3],
+            Format::V2 => &[25, 2, 0, 3],
+        });
+        inner.extend_from_slice(&data);
+
+        Ok(inner)
+    }
+
+    // BaseData (V1)：以 \u{1B} 分隔并补零至64字节
+    // 一次分配足够的空间 (`extra` 为之后追加的长度)，避免扩容时遗留未清零的旧缓冲
+    fn base_v1(uid: &str, token: &str, dirid: &str, extra: usize) -> Vec<u8> {
+        let len = uid.len() + token.len() + dirid.len() + 2;
+        let mut data = Vec::with_capacity(len.max(64) + extra);
+
+        data.extend_from_slice(uid.as_bytes()); // puid
+        data.push(27);
+        data.extend_from_slice(token.as_bytes()); // _token
+        data.push(27);
+        data.extend_from_slice(dirid.as_bytes()); // fldid
+        data.resize(len.max(64), 0);
+
+        data
+    }
+
+    fn plain_v1(&self) -> Vec<u8> {
+        let list = self
+            .filemap
+            .iter()
+            .map(|(name, objid)| [name.as_bytes(), objid.as_bytes()].join(&[26u8][..]))
+            .collect::<Vec<Vec<u8>>>()
+            .join(&[27u8][..]);
+
+        let mut data = Self::base_v1(&self.uid, &self.token, &self.dirid, list.len());
+        data.extend_from_slice(&list);
+
+        data
+    }
+
+    fn plain_v2(&self) -> Result<Vec<u8>> {
+        let base = [&self.uid, &self.token, &self.dirid];
+        let list = self.filemap.iter().flat_map(|(name, objid)| [name, objid]);
+
+        let len = base.iter().map(|x| x.len() + 2).sum::<usize>()
+            + self.filemap.iter().map(|(x, y)| x.len() + y.len() + 4).sum::<usize>();
+        let mut data = Vec::with_capacity(len); // 同 `base_v1`，一次分配
+
+        for value in base.into_iter().chain(list) {
+            let Ok(len) = u16::try_from(value.len()) else {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Data Too Long: [..65536]",
+                ));
+            };
+            data.extend_from_slice(&len.to_be_bytes());
+            data.extend_from_slice(value.as_bytes());
+        }
+
+        Ok(data)
+    }
+
+    // 解密后的数据应以可读的 `uid` 开头，否则多为密码与数据不匹配
+    // V1 的第一个分隔符须在前64字节之内，V2 的第一个值长度不超过64字节
+    fn check_plain(format: Format, data: &[u8]) -> Result<()> {
+        let uid = match format {
+            Format::V1 => data
+                .iter()
+                .take(64)
+                .position(|x| x == &27)
+                .map(|place| &data[..place]),
+            Format::V2 => match data {
+                [x, y, tail @ ..] => {
+                    let len = u16::from_be_bytes([*x, *y]) as usize;
+                    tail.get(..len).filter(|_| len <= 64)
+                }
+                _ => None,
+            },
+        };
+
+        match uid {
+            Some(uid) if std::str::from_utf8(uid).is_ok() => Ok(()),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong Password: Likely Wrong Password or Corrupt File",
+            )),
+        }
+    }
+
+    fn parse_v1(data: &[u8]) -> Result<Plain> {
+        let (base, list) = data.split_at(64); // len >= 64
+        let mut base_raw = Vec::with_capacity(64);
+        base_raw.extend(base.iter().filter(|x| x != &&0));
+        let list: Vec<u8> = list.iter().filter(|x| x != &&0).copied().collect();
+
+        let mut base_data: [String; 3] = Default::default();
+        for (index, value) in String::from_utf8_lossy(&base_raw).splitn(3, '\u{1B}').enumerate() {
+            base_data[index] = value.trim().to_string();
+        }
+        wipe(&mut base_raw);
+
+        let mut list_res = Vec::new();
+        if !list.is_empty() {
+            for val in String::from_utf8_lossy(&list).split('\u{1B}') {
+                let [name, objid] = val.splitn(2, "\u{1A}").collect::<Vec<&str>>()[..] else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "Wrong File Data: Unsupported File Type",
+                    ));
+                };
+                list_res.push((name.into(), objid.into()))
+            }
+        }
+
+        Ok((base_data, list_res))
+    }
+
+    fn parse_v2(data: &[u8]) -> Result<Plain> {
+        let mut rest = data;
+        let base_data = [
+            Self::next_v2(&mut rest)?,
+            Self::next_v2(&mut rest)?,
+            Self::next_v2(&mut rest)?,
+        ];
+
+        let mut list_res = Vec::new();
+        // 奇数长度的数据在编码时会补充一个 `0`
+        while rest.len() > 1 {
+            list_res.push((Self::next_v2(&mut rest)?, Self::next_v2(&mut rest)?));
+        }
+
+        Ok((base_data, list_res))
+    }
+
+    fn next_v2(rest: &mut &[u8]) -> Result<String> {
+        let value = match rest {
+            [x, y, tail @ ..] => {
+                let len = u16::from_be_bytes([*x, *y]) as usize;
+                tail.get(..len).map(|value| (value, &tail[len..]))
+            }
+            _ => None,
+        };
+
+        let Some((value, tail)) = value else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Wrong File Data: Unsupported File Type",
+            ));
+        };
+        *rest = tail;
+
+        Ok(String::from_utf8_lossy(value).into())
+    }
+
+    // 查找 `key = '...'` `"key":"..."` 等形式的字符串值，分隔符两侧可有空白，结果经过 JSON 反转义
+    fn page_value(data: &str, key: &str) -> Option<String> {
+        Self::page_keys(data, key).find_map(|place| {
+            let rest = Self::page_separator(&data[place + key.len()..])?;
+
+            let quote = rest.chars().next().filter(|x| matches!(x, '"' | '\''))?;
+            let rest = &rest[1..];
+
+            let mut escaped = false;
+            let (end, _) = rest.char_indices().find(|(_, x)| {
+                let end = !escaped && *x == quote;
+                escaped = !escaped && *x == '\\';
+                end
+            })?;
+
+            Some(Self::page_string(&rest[..end], quote))
+        })
+    }
+
+    // 以 JSON 字符串解析引号内的内容；单引号字符串中 `\'` 无需转义，`"` 需要转义
+    fn page_string(value: &str, quote: char) -> String {
+        let mut raw = String::with_capacity(value.len() + 2);
+        raw.push('"');
+        let mut escaped = false;
+        for x in value.chars() {
+            match (escaped, x) {
+                (true, '\'') if quote == '\'' => {
+                    raw.pop();
+                    raw.push('\'');
+                }
+                (false, '"') if quote == '\'' => raw.push_str("\\\""),
+                _ => raw.push(x),
+            }
+            escaped = !escaped && x == '\\';
+        }
+        raw.push('"');
+
+        match Value::parse(&raw) {
+            Some(Value::String(x)) => x,
+            _ => value.into(), // 非法的转义序列，保留原样
+        }
+    }
+
+    // 与 `page_value` 相同，但值为整数，可带引号，如 `"size":1024` `size = '1024'`
+    fn page_number(data: &str, key: &str) -> Option<u64> {
+        Self::page_keys(data, key).find_map(|place| {
+            let rest = Self::page_separator(&data[place + key.len()..])?;
+            let rest = rest.strip_prefix(['"', '\'']).unwrap_or(rest);
+
+            let end = rest.find(|x: char| !x.is_ascii_digit()).unwrap_or(rest.len());
+            rest[..end].parse().ok()
+        })
+    }
+
+    // 与 `page_value` 相同，但值为布尔值，可带引号，如 `"isfile":false` `isFolder = 'true'`
+    fn page_flag(data: &str, key: &str) -> Option<bool> {
+        Self::page_keys(data, key).find_map(|place| {
+            let rest = Self::page_separator(&data[place + key.len()..])?;
+            let rest = rest.strip_prefix(['"', '\'']).unwrap_or(rest);
+
+            if rest.starts_with("true") {
+                Some(true)
+            } else if rest.starts_with("false") {
+                Some(false)
+            } else {
+                None
+            }
+        })
+    }
+
+    // 完整的键名出现的位置，如 `fileSize` 不会匹配 `maxFileSize` 或 `data-fileSize`
+    fn page_keys<'d>(data: &'d str, key: &'d str) -> impl Iterator<Item = usize> + 'd {
+        data.match_indices(key).map(|(place, _)| place).filter(|place| {
+            !data[..*place].ends_with(|x: char| x.is_alphanumeric() || matches!(x, '_' | '-'))
+        })
+    }
+
+    // 跳过键名的右引号与 `=` `:` 分隔符，返回值的开头
+    fn page_separator(rest: &str) -> Option<&str> {
+        let rest = rest.strip_prefix(['"', '\'']).unwrap_or(rest).trim_start();
+        Some(rest.strip_prefix(['=', ':'])?.trim_start())
+    }
+
+    fn field<'d>(file: &'d str, key: &str) -> Option<&'d str> {
+        let key = format!("\"{key}\":");
+        let value = &file[file.find(&key)? + key.len()..];
+        let value = match value.strip_prefix('"') {
+            Some(x) => &x[..x.find('"')?],
+            None => &value[..value.find(',').unwrap_or(value.len())],
+        };
+
+        Some(value.trim())
+    }
+
+    // `objid` 在拼接进请求时将被URL编码，因此仅拒绝控制字符
+    fn validate_objid(object_id: &str) -> Result<()> {
+        if object_id.trim().is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Empty ObjectID!"));
+        }
+        if object_id.chars().any(char::is_control) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Invalid ObjectID!"));
+        }
+
+        Ok(())
+    }
+
+    fn empty_response() -> CloudError {
+        Error::new(
+            ErrorKind::UnexpectedEof,
+            "Empty Response Received from Server: Connection Closed or No Content",
+        )
+        .into()
+    }
+
+    // 按服务器返回的 `code` 与 `msg` 判断 `_token` 是否失效，而非在整个应答中查找关键字
+    fn server_error(data: &str) -> CloudError {
+        let value = Value::parse(data.trim());
+        let field = |key: &str| value.as_ref().and_then(|x| x.get(key));
+
+        let code = field("code").and_then(Value::as_f64);
+        let msg = field("msg").and_then(Value::as_str).map(str::trim);
+        if code == Some(401.0) || msg.is_some_and(|x| TOKEN_EXPIRED.iter().any(|t| x.eq_ignore_ascii_case(t))) {
+            return CloudError::TokenExpired;
+        }
+
+        CloudError::ServerError(data.into())
+    }
+
+    // 确保对应的流已连接，另一个流保持不变
+    fn switch_stream(&self, status: StreamStatus) -> Result<String> {
+        let (opened, host) = match status {
+            StreamStatus::Link => (&self.link_stream, &self.hosts.1),
+            _ => (&self.scan_stream, &self.hosts.0),
+        };
+
+        if !opened.get() {
+            match self.transport.try_borrow_mut() {
+                Ok(mut x) => x.connect(host)?,
+                Err(_) => {
+                    return Err(Error::new(
+                        ErrorKind::WouldBlock,
+                        "Transport is Busy!",
+                    ))
+                }
+            };
+            opened.set(true);
+        }
+
+        Ok(host.clone())
+    }
+
+    // 仅结束对应的流，另一个流保持连接
+    fn end_stream(&mut self, status: StreamStatus) {
+        let (opened, host) = match status {
+            StreamStatus::Link => (&self.link_stream, &self.hosts.1),
+            _ => (&self.scan_stream, &self.hosts.0),
+        };
+
+        opened.set(false);
+        self.transport.get_mut().disconnect(host);
+    }
+
+    fn request(&self, host: &str, raw: &str) -> Result<Vec<u8>> {
+        let mut delay = RETRY_DELAY;
+        for _ in 0..self.retries {
+            self.wait_interval();
+
+            let Ok(mut transport) = self.transport.try_borrow_mut() else {
+                break; // 交由最后一次请求返回错误
+            };
+            match transport.request(host, raw.as_bytes()) {
+                Err(e) if Self::interrupted(&e) => {
+                    transport.disconnect(host);
+                    thread::sleep(delay);
+                    delay *= 2;
+                    let _ = transport.connect(host); // 失败时由下次请求返回错误
+                }
+                res => return res,
+            }
+        }
+
+        self.wait_interval();
+
+        match self.transport.try_borrow_mut() {
+            Ok(mut x) => x.request(host, raw.as_bytes()),
+            Err(_) => Err(Error::new(
+                ErrorKind::WouldBlock,
+                "Transport is Busy!",
+            )),
+        }
+    }
+
+    // 连接被重置或中断，可重新连接后重试
+    fn interrupted(e: &Error) -> bool {
+        matches!(
+            e.kind(),
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe
+        )
+    }
+
+    fn request_stream(&self, host: &str, head: &str, body: &mut dyn Read) -> Result<Vec<u8>> {
+        self.wait_interval();
+
+        match self.transport.try_borrow_mut() {
+            Ok(mut x) => x.request_stream(host, head.as_bytes(), body),
+            Err(_) => Err(Error::new(
+                ErrorKind::WouldBlock,
+                "Transport is Busy!",
+            )),
+        }
+    }
+
+    fn wait_interval(&self) {
+        if let Some(last) = self.last.get() {
+            if let Some(wait) = self.interval.checked_sub(last.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        self.last.set(Some(Instant::now()));
+    }
+
+    fn build_scan_request(&self, dirid: &str, page: usize, size: usize) -> String {
+        format!(
+            "GET /api/getMyDirAndFiles\
+            ?puid={}&_token={}&fldid={}\
+            &page={page}&size={size} HTTP/1.1\r\n\
+            Connection: Keep-Alive\r\n\
+            User-Agent: {}\r\n\
+            Host: {}\r\n\r\n",
+            urlcode::encode(&self.uid),
+            urlcode::encode(&self.token),
+            urlcode::encode(dirid),
+            self.agent,
+            Self::host_header(&self.hosts.0)
+        )
+    }
+
+    fn build_upload_request(&self, name: &str, len: u64) -> (String, String, String) {
+        let name = name.replace('"', "%22").replace(['\r', '\n'], "");
+        let prefix = format!(
+            "--{BOUNDARY}\r\n\
+            Content-Disposition: form-data; name=\"puid\"\r\n\r\n\
+            {}\r\n\
+            --{BOUNDARY}\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n",
+            self.uid
+        );
+        let suffix = format!("\r\n--{BOUNDARY}--\r\n");
+
+        let head = format!(
+            "POST {UPLOAD_PATH}\
+            &_token={}&fldid={} HTTP/1.1\r\n\
+            Connection: Keep-Alive\r\n\
+            User-Agent: {}\r\n\
+            Host: {}\r\n\
+            Content-Type: multipart/form-data; boundary={BOUNDARY}\r\n\
+            Content-Length: {}\r\n\r\n",
+            urlcode::encode(&self.token),
+            urlcode::encode(&self.dirid),
+            self.agent,
+            Self::host_header(&self.hosts.0),
+            prefix.len() as u64 + len + suffix.len() as u64
+        );
+
+        (head, prefix, suffix)
+    }
+
+    fn build_link_request(&self, object_id: &str) -> String {
+        format!(
+            "GET /share/download/{} HTTP/1.1\r\n\
+            User-Agent: {}\r\n\
+            Host: {}\r\n\r\n",
+            urlcode::encode(object_id),
+            self.agent,
+            Self::host_header(&self.hosts.1)
+        )
+    }
+
+    fn build_delete_request(&self, resid: &[String]) -> String {
+        format!(
+            "GET /api/delete\
+            ?puid={}&_token={}\
+            &resids={} HTTP/1.1\r\n\
+            User-Agent: {}\r\n\
+            Host: {}\r\n\r\n",
+            urlcode::encode(&self.uid),
+            urlcode::encode(&self.token),
+            resid.iter().map(|x| urlcode::encode(x)).collect::<Vec<String>>().join(","),
+            self.agent,
+            Self::host_header(&self.hosts.0)
+        )
+    }
+
+    // `Host` 头部省略默认端口，请求均为 `http`，因此仅省略 `80`
+    fn host_header(host: &str) -> &str {
+        host.strip_suffix(":80").unwrap_or(host)
+    }
+
+    fn delete(&self, host: &str, resid: &[String]) -> Result<bool> {
+        if resid.is_empty() {
+            return Ok(true);
+        }
+
+        let data = self.request(host, &self.build_delete_request(resid))?;
+
+        let data = String::from_utf8_lossy(&data);
+        let data = match data.split_once("\r\n\r\n") {
+            Some((_, x)) => x,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::ConnectionReset,
+                    "InvalidData Received from Server",
+                ))
+            }
+        };
+
+        if data.contains("\"result\":true") {
+            if data.contains("\"success\":false") {
+                return Ok(false);
+            }
+        } else {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Error Received: {}", data),
+            ));
+        }
+
+        Ok(true)
+    }
+
+    fn matrix_encode(passwd: &[u8; 4], data: &[u8]) -> Result<Vec<u16>> {
+        let [a, b, c, d] = passwd.map(|x| x as u16);
+        Self::validate_passwd(passwd)?;
+
+        let mut res = Vec::new();
+        let len = data.len();
+        let max = len >> 1;
+
+        let mut i = 0;
+        while i < max {
+            res.push(a * data[2 * i] as u16 + b * data[2 * i + 1] as u16);
+            res.push(c * data[2 * i] as u16 + d * data[2 * i + 1] as u16);
+
+            i += 1;
+        }
+
+        if len % 2 == 1 {
+            res.push(a * data[len - 1] as u16);
+            res.push(c * data[len - 1] as u16);
+        }
+
+        Ok(res)
+    }
+
+    // 密码的唯一校验：每个值不超过 `128`，且行列式 `a*d - b*c` 为正 (矩阵可逆，解码结果唯一)
+    fn validate_passwd(passwd: &[u8; 4]) -> Result<()> {
+        let [a, b, c, d] = passwd.map(|x| x as u16);
+
+        for p in passwd {
+            if p > &128 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Passwd Too Big: 0..=128",
+                ));
+            }
+        }
+
+        if a * d <= b * c {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Wrong Passwd: the Val MUST be POSITIVE",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn matrix_decode(passwd: &[u8; 4], data: &[u16]) -> Result<Vec<u8>> {
+        let [a, b, c, d] = passwd.map(|x| x as u32);
+        Self::validate_passwd(passwd)?;
+
+        if data.len() % 2 == 1 {
+            return Err(Error::new(ErrorKind::InvalidInput, "Wrong Len of Data"));
+        }
+
+        let mut res = Vec::with_capacity(data.len()); // 结果为明文，同样一次分配
+        let max = data.len() >> 1;
+        let val = a * d - b * c;
+
+        let mut i = 0;
+        while i < max {
+            // 密码错误时结果可能为负，此处不做检查，交由 `check_plain` 判断
+            res.push(((d * data[2 * i] as u32).wrapping_sub(b * data[2 * i + 1] as u32) / val) as u8);
+            res.push(((a * data[2 * i + 1] as u32).wrapping_sub(c * data[2 * i] as u32) / val) as u8);
+
+            i += 1;
         }
 
         Ok(res)
     }
 
-    fn matrix_decode(passwd: &[u8; 4], data: &[u16]) -> Result<Vec<u8>> {
-        let [a, b, c, d] = passwd.map(|x| x as u32);
+    fn sixteen_to_eight(from: &[u16]) -> Vec<u8> {
+        let mut res = Vec::with_capacity(from.len() * 2);
+        for x in from {
+            res.extend_from_slice(&x.to_be_bytes());
+        }
+
+        res
+    }
+
+    fn eight_to_sixteen(from: &[u8]) -> Vec<u16> {
+        let chunks = from.chunks_exact(2);
+        let rest = chunks.remainder();
+
+        let mut res = Vec::with_capacity(from.len().div_ceil(2));
+        res.extend(chunks.map(|x| u16::from_be_bytes([x[0], x[1]])));
+
+        if let [last] = rest {
+            res.push(*last as u16); // 奇数长度时，最后一个字节单独作为一个值
+        }
+
+        res
+    }
+}
+
+impl CloudFileBuilder {
+    ///
+    /// 设置 `puid`，必须设置
+    ///
+    pub fn uid<T: Into<String>>(mut self, uid: T) -> Self {
+        self.uid = Some(uid.into());
+        self
+    }
+
+    ///
+    /// 设置 `_token`，必须设置
+    ///
+    pub fn token<T: Into<String>>(mut self, token: T) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    ///
+    /// 设置 `fldid`，默认为账号根目录
+    ///
+    pub fn dirid<T: Into<String>>(mut self, dirid: T) -> Self {
+        self.dirid = dirid.into();
+        self
+    }
+
+    ///
+    /// 设置本地储存数据时所使用的密码，必须设置
+    ///
+    pub fn passwd(mut self, passwd: &[u8; 4]) -> Self {
+        self.passwd = Some(*passwd);
+        self
+    }
+
+    ///
+    /// 设置连接与读写的超时时间，必须大于零
+    ///
+    /// > 注意，仅作用于默认的 `TcpTransport`，`set_transport` 之后不再生效
+    ///
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    ///
+    /// 设置请求时所使用的 `User-Agent`
+    ///
+    pub fn user_agent(mut self, agent: &str) -> Self {
+        self.agent = Some(agent.into());
+        self
+    }
+
+    ///
+    /// 设置扫描与获取链接时所使用的主机，未指定端口时默认为 `80`
+    ///
+    pub fn hosts(mut self, scan: &str, link: &str) -> Self {
+        self.hosts = Some((scan.into(), link.into()));
+        self
+    }
+
+    ///
+    /// 设置请求的最小间隔
+    ///
+    pub fn rate_limit(mut self, min_interval: Duration) -> Self {
+        self.interval = min_interval;
+        self
+    }
+
+    ///
+    /// 设置连接中断时的最大重试次数
+    ///
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    ///
+    /// 构建 `CloudFile` 实例
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(CloudFile)
+    /// - Err(CloudError)
+    ///     - 未设置 `uid` `token` `passwd`，或 `timeout` 为零时，返回 `ErrorKind::InvalidInput`
+    ///     - 密码不符合要求时，与 `CloudFile::new` 相同
+    ///
+    pub fn build(self) -> CloudResult<CloudFile> {
+        let (Some(uid), Some(token), Some(passwd)) = (self.uid, self.token, self.passwd) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Uid, Token and Passwd are Required!",
+            )
+            .into());
+        };
+        let (dirid, passwd) = (self.dirid, &passwd);
+
+        CloudFile::validate_passwd(passwd)?;
+        if self.timeout == Some(Duration::ZERO) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Timeout MUST be POSITIVE").into());
+        }
+
+        let mut data = CloudFile::base_v1(&uid, &token, &dirid, 0);
+
+        let encoded = CloudFile::matrix_encode(passwd, &data);
+        wipe(&mut data);
+        let data = &CloudFile::sixteen_to_eight(&encoded?);
+
+        let mut inner = Vec::new();
+        inner.extend_from_slice(&[3, 3, 4, 21, 7, 23, 10, 8]);
+        inner.extend_from_slice(passwd);
+        inner.extend_from_slice(&[25, 0, 0, 3]);
+        inner.extend_from_slice(data);
+
+        let mut cloud = CloudFile {
+            uid,
+            token,
+            dirid,
+            inner,
+            scan_stream: Cell::new(false),
+            link_stream: Cell::new(false),
+            hosts: (HOST_SCAN.into(), HOST_LINK.into()),
+            transport: RefCell::new(Box::new(TcpTransport { conns: Vec::new(), timeout: self.timeout })),
+            interval: self.interval,
+            retries: self.retries.unwrap_or(RETRIES),
+            last: Cell::new(None),
+            agent: self.agent.unwrap_or_else(|| USER_AGENT.into()),
+            format: Format::V1,
+            resids: Vec::new(),
+            version: None,
+            filemap: Vec::new(),
+            sizes: HashMap::new(),
+            folders: RefCell::default(),
+            deferred: false,
+            dirty: false,
+        };
+
+        if let Some((scan, link)) = self.hosts {
+            cloud.set_hosts(&scan, &link);
+        }
+
+        Ok(cloud)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // 按顺序返回预设的响应 (或错误)，并记录收到的请求
+    #[derive(Debug, Clone, Default)]
+    struct Canned {
+        responses: Arc<Mutex<Vec<std::result::Result<String, ErrorKind>>>>,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Canned {
+        fn push(&self, response: String) -> &Self {
+            self.responses.lock().unwrap().push(Ok(response));
+            self
+        }
+
+        fn fail(&self, kind: ErrorKind) -> &Self {
+            self.responses.lock().unwrap().push(Err(kind));
+            self
+        }
+
+        fn requests(&self) -> Vec<(String, String)> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn sent(&self, path: &str) -> bool {
+            self.requests().iter().any(|(_, x)| x.contains(path))
+        }
+    }
+
+    impl Transport for Canned {
+        fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>> {
+            self.requests.lock().unwrap().push((host.into(), String::from_utf8_lossy(raw).into()));
+            let mut responses = self.responses.lock().unwrap();
+            match responses.is_empty() {
+                true => Err(Error::new(ErrorKind::ConnectionRefused, "No Response!")),
+                false => responses.remove(0).map(String::into_bytes).map_err(Error::from),
+            }
+        }
+    }
+
+    const PASSWD: [u8; 4] = [127, 97, 112, 128];
+
+    fn cloud() -> CloudFile {
+        CloudFile::new("29001".into(), "b8bd0001".into(), "94555".into(), &PASSWD).unwrap()
+    }
+
+    fn mocked() -> (CloudFile, Canned) {
+        let mut cloud = cloud();
+        let canned = Canned::default();
+        cloud.set_transport(Box::new(canned.clone()));
+        cloud.set_retries(0);
+        (cloud, canned)
+    }
+
+    fn http(body: &str) -> String {
+        format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    // (name, objid, resid)
+    fn listing(files: &[(&str, &str, &str)]) -> String {
+        let data: Vec<String> = files
+            .iter()
+            .map(|(name, objid, resid)| {
+                format!(
+                    r#"{{"isfile":true,"name":"{name}","objectId":"{objid}","residstr":"{resid}","suffix":"bin","size":{}}}"#,
+                    name.len()
+                )
+            })
+            .collect();
+        http(&format!(r#"{{"result":true,"data":[{}],"msg":"ok"}}"#, data.join(",")))
+    }
+
+    fn deleted() -> String {
+        http(r#"{"result":true,"success":true}"#)
+    }
+
+    #[test]
+    fn scan_preserve_lists_without_deleting() {
+        let (mut cloud, canned) = mocked();
+        cloud.filemap.push(("a.txt".into(), "o1".into()));
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2"), ("c.txt", "o3", "r3")]));
+
+        let added = cloud.scan_preserve().unwrap();
+        assert_eq!(added.iter().map(|x| x.objid.as_str()).collect::<Vec<_>>(), ["o2", "o3"]);
+        assert_eq!(cloud.last_scan_resids(), ["r1", "r2", "r3"]);
+        assert_eq!(cloud.len(), 3);
+        assert!(!canned.sent("/api/delete"));
+
+        // 服务器上的文件仍在，再次扫描不会重复添加
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2"), ("c.txt", "o3", "r3")]));
+        assert!(cloud.scan_preserve().unwrap().is_empty());
+        assert_eq!(cloud.len(), 3);
+    }
+
+    #[test]
+    fn scan_preserve_pages() {
+        let (mut cloud, canned) = mocked();
+        let names: Vec<String> = (0..SCAN_SIZE + 20).map(|x| format!("f{x}")).collect();
+        let files: Vec<(&str, &str, &str)> = names.iter().map(|x| (x.as_str(), x.as_str(), x.as_str())).collect();
+        canned.push(listing(&files[..SCAN_SIZE]));
+        canned.push(listing(&files[SCAN_SIZE..]));
+
+        assert_eq!(cloud.scan_preserve().unwrap().len(), SCAN_SIZE + 20);
+        let requests = canned.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].1.contains("&page=1&size=100 "));
+        assert!(requests[1].1.contains("&page=2&size=100 "));
+    }
+
+    // 每次请求都会 `panic` 的传输
+    #[derive(Debug)]
+    struct Panicky;
+
+    impl Transport for Panicky {
+        fn request(&mut self, _host: &str, _raw: &[u8]) -> Result<Vec<u8>> {
+            panic!("transport panicked");
+        }
+    }
+
+    #[test]
+    fn scan_all_reports_panics() {
+        let (first, canned) = mocked();
+        canned.push(listing(&[("a.txt", "o1", "r1")])).push(deleted());
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#)).push(deleted());
+
+        let mut second = cloud();
+        second.set_transport(Box::new(Panicky));
+        second.set_retries(0);
+
+        let (third, _) = mocked(); // 无响应，连接被拒绝
+
+        let res = scan_all(vec![first, second, third]);
+        assert_eq!(res.len(), 3);
+        assert!(matches!(res[0].1, Ok(1)));
+        assert_eq!(res[0].0.len(), 1);
+        assert!(matches!(&res[1].1, Err(CloudError::Io(e)) if e.to_string() == "Scan Panicked!"));
+        assert!(matches!(&res[2].1, Err(CloudError::Io(e)) if e.kind() == ErrorKind::ConnectionRefused));
+    }
+
+    fn uploaded(objid: &str) -> String {
+        http(&format!(r#"{{"result":true,"msg":"success","data":{{"objectId":"{objid}","name":"a.bin"}}}}"#))
+    }
+
+    #[test]
+    fn upload_stream_from_cursor() {
+        let (mut cloud, canned) = mocked();
+        canned.push(uploaded("o9"));
+
+        let data = b"0123456789".repeat(100);
+        let objid = cloud.upload_stream("a.bin", std::io::Cursor::new(&data), data.len() as u64, |_, _| {}).unwrap();
+        assert_eq!(objid, "o9");
+        assert_eq!(cloud.get_filemap(), [("a.bin".to_string(), "o9".to_string())]);
+
+        let (_, request) = &canned.requests()[0];
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /upload?_from=mobilelearn&_token=b8bd0001&fldid=94555 HTTP/1.1\r\n"), "{head}");
+        assert!(head.contains(&format!("\r\nContent-Length: {}", body.len())));
+        assert!(body.contains("name=\"puid\"\r\n\r\n29001\r\n"));
+        assert!(body.contains(&format!("filename=\"a.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n{}\r\n", "0123456789".repeat(100))));
+    }
+
+    #[test]
+    fn upload_stream_progress() {
+        let (mut cloud, canned) = mocked();
+        canned.push(uploaded("o9"));
+
+        let data = vec![7u8; 20000];
+        let mut events = Vec::new();
+        cloud
+            .upload_stream("a.bin", std::io::Cursor::new(&data), data.len() as u64, |done, total| events.push((done, total)))
+            .unwrap();
+
+        assert!(events.len() > 1); // 发送过程中多次报告
+        assert!(events.windows(2).all(|x| x[0].0 <= x[1].0));
+        assert!(events.iter().all(|(_, total)| *total == Some(20000)));
+        assert_eq!(events.last(), Some(&(20000, Some(20000))));
+        assert_eq!(events.iter().filter(|(done, _)| *done == 20000).count(), 1); // 完成时恰好一次
+    }
+
+    #[test]
+    fn upload_stream_short_read() {
+        let (mut cloud, canned) = mocked();
+        canned.push(uploaded("o9"));
+
+        let res = cloud.upload_stream("a.bin", &b"short"[..], 10, |_, _| {});
+        assert!(matches!(res, Err(CloudError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+        assert!(cloud.get_filemap().is_empty());
+    }
+
+    #[test]
+    fn name_and_objid_lookup() {
+        let mut cloud = cloud();
+        cloud.filemap.extend([("a.txt".into(), "o1".into()), ("b.txt".into(), "o2".into()), ("a.txt".into(), "o3".into())]);
+
+        assert_eq!(cloud.name_of("o2"), Some("b.txt"));
+        assert_eq!(cloud.name_of("o4"), None);
+        assert_eq!(cloud.objid_of("a.txt"), Some("o1")); // 重名时返回第一个
+        assert_eq!(cloud.objid_of("c.txt"), None);
+    }
+
+    #[test]
+    fn merge_three_backups() {
+        let backup = |files: &[(&str, &str)]| {
+            let mut cloud = cloud();
+            cloud.filemap.extend(files.iter().map(|&(n, o)| (n.into(), o.into())));
+            cloud.update_inner().unwrap();
+            cloud.as_ref().to_vec()
+        };
+        let base = backup(&[("a.txt", "o1"), ("b.txt", "o2")]);
+        let old = backup(&[("b.old", "o2"), ("c.txt", "o3")]);
+        let older = backup(&[("a.txt", "o1"), ("d.txt", "o4"), ("c.txt", "o3")]);
 
-        for p in passwd {
-            if p > &128 {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    "Passwd Too Big: 0..=128",
-                ));
+        let merged = CloudFile::merge(&base, &[&old, &older], &PASSWD).unwrap();
+        let files: Vec<(&str, &str)> = merged.get_filemap().iter().map(|(n, o)| (n.as_str(), o.as_str())).collect();
+        assert_eq!(files, [("a.txt", "o1"), ("b.txt", "o2"), ("c.txt", "o3"), ("d.txt", "o4")]);
+        assert_eq!(CloudFile::from_raw(merged.as_ref()).unwrap().get_filemap(), merged.get_filemap());
+
+        let res = CloudFile::merge(&base, &[&old, b"broken"], &PASSWD);
+        assert!(matches!(res, Err(e) if e.to_string().contains("[2]")));
+    }
+
+    // 重写前的实现，用于逐字节比对
+    fn old_sixteen_to_eight(from: &[u16]) -> Vec<u8> {
+        let mut res = Vec::new();
+        let (len, max) = (from.len(), from.len() >> 1);
+        for i in 0..max {
+            res.push((from[2 * i] / 256) as u8);
+            res.push((from[2 * i] % 256) as u8);
+            res.push((from[2 * i + 1] / 256) as u8);
+            res.push((from[2 * i + 1] % 256) as u8);
+        }
+        if len % 2 == 1 {
+            res.push((from[len - 1] / 256) as u8);
+            res.push((from[len - 1] % 256) as u8);
+        }
+        res
+    }
+
+    fn old_eight_to_sixteen(from: &[u8]) -> Vec<u16> {
+        let mut res = Vec::new();
+        let (len, max) = (from.len(), from.len() >> 1);
+        for i in 0..max {
+            res.push(256 * from[2 * i] as u16 + from[2 * i + 1] as u16);
+        }
+        if len % 2 == 1 {
+            res.push(from[len - 1] as u16);
+        }
+        res
+    }
+
+    // xorshift64，避免引入随机数依赖
+    fn random(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn conversions_match_old() {
+        let mut seed = 0x9E37_79B9_7F4A_7C15;
+        let lens: Vec<usize> = (0..64).chain((0..200).map(|_| random(&mut seed) as usize % 4096)).collect();
+        for len in lens {
+            let bytes: Vec<u8> = (0..len).map(|_| random(&mut seed) as u8).collect();
+            let words: Vec<u16> = (0..len).map(|_| random(&mut seed) as u16).collect();
+
+            assert_eq!(CloudFile::eight_to_sixteen(&bytes), old_eight_to_sixteen(&bytes), "len {len}");
+            assert_eq!(CloudFile::sixteen_to_eight(&words), old_sixteen_to_eight(&words), "len {len}");
+        }
+        assert_eq!(CloudFile::eight_to_sixteen(&[1, 2, 3]), [0x0102, 3]); // 奇数长度
+    }
+
+    // 基准测试：`cargo test --release -- --ignored --nocapture conversions_bench`
+    #[test]
+    #[ignore]
+    fn conversions_bench() {
+        fn bench<T>(name: &str, f: impl Fn() -> T) {
+            let start = Instant::now();
+            for _ in 0..20 {
+                std::hint::black_box(f());
             }
+            println!("{name}: {:?}/iter", start.elapsed() / 20);
         }
 
-        if a * d <= b * c {
-            return Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Wrong Passwd: the Val MUST be POSITIVE",
-            ));
+        let mut seed = 7;
+        let bytes: Vec<u8> = (0..(1 << 22) + 1).map(|_| random(&mut seed) as u8).collect();
+        let words = CloudFile::eight_to_sixteen(&bytes);
+
+        bench("eight_to_sixteen (old)", || old_eight_to_sixteen(&bytes));
+        bench("eight_to_sixteen", || CloudFile::eight_to_sixteen(&bytes));
+        bench("sixteen_to_eight (old)", || old_sixteen_to_eight(&words));
+        bench("sixteen_to_eight", || CloudFile::sixteen_to_eight(&words));
+    }
+
+    #[test]
+    fn builder_options() {
+        let plain = CloudFile::builder().uid("29001").token("b8bd0001").passwd(&PASSWD).build().unwrap();
+        assert_eq!((plain.dirid.as_str(), plain.retries, plain.interval), ("", RETRIES, Duration::ZERO));
+        assert_eq!((plain.hosts.0.as_str(), plain.agent.as_str()), (HOST_SCAN, USER_AGENT));
+        assert_eq!(plain.as_ref(), CloudFile::new("29001".into(), "b8bd0001".into(), String::new(), &PASSWD).unwrap().as_ref());
+
+        let full = CloudFile::builder()
+            .uid("29001")
+            .token("b8bd0001")
+            .dirid("94555")
+            .passwd(&PASSWD)
+            .timeout(Duration::from_secs(5))
+            .user_agent("sal/1.0")
+            .hosts("127.0.0.1:8080", "127.0.0.1")
+            .rate_limit(Duration::from_millis(100))
+            .retries(0)
+            .build()
+            .unwrap();
+        assert_eq!(full.as_ref(), cloud().as_ref());
+        assert_eq!((full.retries, full.interval), (0, Duration::from_millis(100)));
+        assert_eq!(full.hosts, ("127.0.0.1:8080".into(), "127.0.0.1:80".into()));
+        assert_eq!(full.agent, "sal/1.0");
+
+        assert!(matches!(CloudFile::builder().uid("29001").passwd(&PASSWD).build(), Err(CloudError::Io(_))));
+        let res = CloudFile::builder().uid("1").token("2").passwd(&PASSWD).timeout(Duration::ZERO).build();
+        assert!(matches!(res, Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn wipe_zeroes_buffers() {
+        // 释放后的内存无法安全读取，这里验证 `Drop` 所用的清零过程
+        let mut buf = b"dd1c8a-token".to_vec();
+        wipe(&mut buf);
+        assert_eq!(buf, [0; 12]);
+
+        let mut inner = cloud().as_ref().to_vec();
+        assert!(inner.iter().any(|&x| x != 0));
+        wipe(&mut inner);
+        assert!(inner.iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn error_variants() {
+        let (mut cloud, canned) = mocked();
+        canned.push(http(r#"{"result":false,"msg":"token无效"}"#));
+        canned.push(http(r#"{"result":false,"code":401,"msg":"Unauthorized"}"#));
+        canned.push(http(r#"{"result":false,"msg":"文件 token.txt 不存在，请登录后重试"}"#));
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#)).push(deleted());
+
+        assert!(matches!(cloud.scan(), Err(CloudError::TokenExpired)));
+        assert!(matches!(cloud.scan(), Err(CloudError::TokenExpired)));
+        assert!(matches!(cloud.scan(), Err(CloudError::ServerError(x)) if x.contains("token.txt")));
+        assert!(matches!(cloud.scan(), Err(CloudError::ScanFinished)));
+
+        assert!(matches!(CloudFile::from_raw(&[0; 8]), Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput));
+        let e: Error = CloudError::ScanFinished.into();
+        assert_eq!(e.kind(), ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn scan_resids_match_response() {
+        let (mut cloud, canned) = mocked();
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2")])).push(deleted());
+
+        assert_eq!(cloud.scan().unwrap(), 2);
+        assert_eq!(cloud.last_scan_resids(), ["r1", "r2"]);
+        assert!(canned.requests()[1].1.contains("&resids=r1,r2 "));
+    }
+
+    #[test]
+    fn scan_preserve_returns_delta() {
+        let (mut cloud, canned) = mocked();
+        let server = [("a.txt", "o1", "r1"), ("b.txt", "o2", "r2")];
+        cloud.filemap.push(("a.txt".into(), "o1".into()));
+        canned.push(listing(&server));
+
+        let added = cloud.scan_preserve().unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!((added[0].name.as_str(), added[0].objid.as_str(), added[0].resid.as_str()), ("b.txt", "o2", "r2"));
+        assert_eq!(added[0].size, Some(5));
+        assert!(!canned.sent("/api/delete"));
+
+        // 服务器上的文件仍在：再次列出相同的文件，没有新文件时返回空的 `Vec`
+        canned.push(listing(&server));
+        assert_eq!(cloud.scan_preserve().unwrap(), Vec::new());
+        assert_eq!(cloud.last_scan_resids(), ["r1", "r2"]);
+        assert!(!canned.sent("/api/delete"));
+    }
+
+    fn served() -> (CloudFile, mock_server::MockServer) {
+        let server = mock_server::MockServer::new();
+        let mut cloud = cloud();
+        cloud.set_hosts(&server.host(), &server.host());
+        cloud.set_retries(0);
+        (cloud, server)
+    }
+
+    #[test]
+    fn tcp_marker_across_reads() {
+        let (cloud, server) = served();
+        let body = ["<script>var downl", "oadUrl = 'http://d0.ananas.chaoxing.com/a.bin';</script>"];
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.concat().len());
+        server.push_parts([head.as_str(), body[0], body[1]]);
+
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.ananas.chaoxing.com/a.bin");
+        assert!(server.last_request().unwrap().starts_with(b"GET /share/download/o1 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn tcp_connection_close_without_length() {
+        let (cloud, server) = served();
+        server.push_parts([
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n<script>",
+            "var downloadUrl = 'http://d0.ananas.chaoxing.com/b.bin';</script>",
+        ]);
+
+        assert_eq!(cloud.get_link("o2").unwrap(), "http://d0.ananas.chaoxing.com/b.bin");
+    }
+
+    #[test]
+    fn tcp_huge_content_length() {
+        let (cloud, server) = served();
+        server.push_response(format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\nvar", u64::MAX));
+
+        // 按实际收到的数据读取，不会按声明的长度分配内存
+        let Err(CloudError::Io(e)) = cloud.get_link("o3") else {
+            panic!("Expected Io Error");
+        };
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    fn link_page(cloud: &CloudFile, canned: &Canned, page: &str) -> CloudResult<String> {
+        canned.push(format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{page}"));
+        cloud.get_link("o1")
+    }
+
+    #[test]
+    fn link_page_variants() {
+        let (cloud, canned) = mocked();
+        let url = "http://d0.ananas.chaoxing.com/download/o1?at=1&fn=a b";
+
+        let pages = [
+            r#"<script>var downloadUrl='http://d0.ananas.chaoxing.com/download/o1?at=1&fn=a b';</script>"#,
+            r#"{"result":true,"downloadUrl":"http:\/\/d0.ananas.chaoxing.com\/download\/o1?at=1&fn=a b"}"#,
+            r#"<script>downloadUrl = "http://d0.ananas.chaoxing.com/download/o1?at=1&fn=a b";</script>"#,
+            "<script>\n  var downloadUrl\t=\n 'http://d0.ananas.chaoxing.com/download/o1?at=1&fn=a b'\n</script>",
+        ];
+        for page in pages {
+            assert_eq!(link_page(&cloud, &canned, page).unwrap(), url, "{page}");
         }
+    }
 
-        if data.len() % 2 == 1 {
-            return Err(Error::new(ErrorKind::InvalidInput, "Wrong Len of Data"));
+    #[test]
+    fn link_page_escapes() {
+        let (cloud, canned) = mocked();
+        let page = r#"var downloadUrl = 'http://d0.x/it\'s "ok"é😀';"#;
+        assert_eq!(link_page(&cloud, &canned, page).unwrap(), "http://d0.x/it's \"ok\"é😀");
+
+        let page = r#"var downloadUrl = "http://d0.x/\"q\"\\\t";"#;
+        assert_eq!(link_page(&cloud, &canned, page).unwrap(), "http://d0.x/\"q\"\\\t");
+    }
+
+    #[test]
+    fn link_page_missing() {
+        let (cloud, canned) = mocked();
+        assert!(matches!(link_page(&cloud, &canned, "<p>获取下载地址失败</p>"), Err(CloudError::NotFound)));
+
+        let Err(CloudError::Parse(e)) = link_page(&cloud, &canned, "<p>Hello</p>") else {
+            panic!("Expected Parse Error");
+        };
+        assert!(e.contains("var downloadUrl='...'") && e.contains("\"downloadUrl\":\"...\""));
+    }
+
+    #[test]
+    fn link_objid_encoded() {
+        let (cloud, canned) = mocked();
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        cloud.get_link("a/b c&d").unwrap();
+
+        let request = &canned.requests()[0].1;
+        assert!(request.starts_with("GET /share/download/a%2Fb%20c%26d HTTP/1.1\r\n"), "{request}");
+
+        for objid in ["", "a\r\nX-Evil: 1"] {
+            let res = cloud.get_link(objid);
+            assert!(matches!(res, Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput));
         }
+        assert_eq!(canned.requests().len(), 1); // 无效的 `object_id` 不发送请求
+    }
 
-        let mut res = Vec::new();
-        let max = data.len() >> 1;
-        let val = a * d - b * c;
+    #[test]
+    fn custom_hosts_header() {
+        let (mut cloud, canned) = mocked();
+        cloud.set_hosts("127.0.0.1:8080", "files.example:443");
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        cloud.list_folder("f1").unwrap();
+        cloud.get_link("o1").unwrap();
 
-        let mut i = 0;
-        while i < max {
-            res.push(((d * data[2 * i] as u32 - b * data[2 * i + 1] as u32) / val) as u8);
-            res.push(((a * data[2 * i + 1] as u32 - c * data[2 * i] as u32) / val) as u8);
+        cloud.set_hosts("pan.example", "files.example:80");
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        cloud.list_folder("f1").unwrap();
+        cloud.get_link("o1").unwrap();
 
-            i += 1;
+        let requests = canned.requests();
+        let hosts: Vec<(&str, &str)> = requests.iter().map(|(host, raw)| {
+            let header = raw.lines().find_map(|x| x.strip_prefix("Host: ")).unwrap();
+            (host.as_str(), header)
+        }).collect();
+        assert_eq!(hosts, [
+            ("127.0.0.1:8080", "127.0.0.1:8080"),
+            ("files.example:443", "files.example:443"),
+            ("pan.example:80", "pan.example"),
+            ("files.example:80", "files.example"),
+        ]);
+    }
+
+    #[test]
+    fn clear_to_base_only() {
+        let base = cloud().as_ref().to_vec();
+        let mut cloud = cloud();
+        cloud.filemap.extend([("a.txt".into(), "o1".into()), ("b.txt".into(), "o2".into())]);
+        cloud.update_inner().unwrap();
+        let full = cloud.as_ref().len();
+
+        cloud.clear_filemap().unwrap();
+        assert!(cloud.as_ref().len() < full);
+        assert_eq!(cloud.as_ref(), &base[..]);
+        assert!(CloudFile::from_raw(cloud.as_ref()).unwrap().is_empty());
+
+        cloud.filemap.push(("c.txt".into(), "o3".into()));
+        cloud.set_deferred(true).unwrap();
+        cloud.reset_to_empty().unwrap(); // 不受 `set_deferred` 影响
+        assert_eq!(cloud.as_ref(), &base[..]);
+    }
+
+    #[test]
+    fn total_size_cases() {
+        let mut cloud = cloud();
+        assert_eq!((cloud.total_size(), cloud.total_size_known()), (Some(0), 0));
+
+        cloud.filemap.extend([("a.txt".into(), "o1".into()), ("b.txt".into(), "o2".into())]);
+        cloud.sizes.extend([("o1".into(), 12), ("o2".into(), 30)]);
+        assert_eq!((cloud.total_size(), cloud.total_size_known()), (Some(42), 42));
+
+        cloud.filemap.push(("c.txt".into(), "o3".into()));
+        assert_eq!((cloud.total_size(), cloud.total_size_known()), (None, 42));
+    }
+
+    #[test]
+    fn v2_round_trip() {
+        let files = [
+            ("a\u{1A}b\u{1B}c.txt", "o1"),
+            ("", "o2"),
+            ("中文 名称.pdf", "o3\u{1B}"),
+            ("odd", "o"), // 奇数长度，编码时补 `0`
+        ];
+        let mut cloud = cloud();
+        cloud.set_format(Format::V2).unwrap();
+        cloud.filemap.extend(files.iter().map(|&(n, o)| (n.into(), o.into())));
+        cloud.update_inner().unwrap();
+
+        let parsed = CloudFile::from_raw(cloud.as_ref()).unwrap();
+        assert_eq!(parsed.format, Format::V2);
+        assert_eq!(parsed.get_filemap(), cloud.get_filemap());
+        assert_eq!((parsed.uid.as_str(), parsed.token.as_str(), parsed.dirid.as_str()), ("29001", "b8bd0001", "94555"));
+
+        // 没有文件时同样可以还原
+        cloud.clear_filemap().unwrap();
+        assert!(CloudFile::from_raw(cloud.as_ref()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn list_folder_entries() {
+        let (cloud, canned) = mocked();
+        let body = r#"{"result":true,"data":[{"isfile":false,"name":"docs","objectId":"f1","residstr":"r1","suffix":"","size":0},{"isfile":true,"name":"a.txt","objectId":"o2","residstr":"r2","suffix":"txt","size":12}],"msg":"ok"}"#;
+        canned.push(http(body));
+
+        let files = cloud.list_folder("shared 1").unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].is_folder && !files[1].is_folder);
+        assert_eq!((files[1].name.as_str(), files[1].objid.as_str(), files[1].size), ("a.txt", "o2", Some(12)));
+        assert!(cloud.is_empty()); // 不会修改 `filemap`
+
+        let request = &canned.requests()[0].1;
+        assert!(request.starts_with("GET /api/getMyDirAndFiles?puid=29001&_token=b8bd0001&fldid=shared%201&page=1&size=100 "));
+        assert!(!canned.sent("/api/delete"));
+
+        // 已知的文件夹不会发送请求
+        assert!(matches!(cloud.get_link("f1"), Err(CloudError::IsFolder)));
+        assert_eq!(canned.requests().len(), 1);
+    }
+
+    #[test]
+    fn link_folder_page() {
+        let (cloud, canned) = mocked();
+        let page = r#"<script>var isfile = false; var fileName = "docs";</script>"#;
+        assert!(matches!(link_page(&cloud, &canned, page), Err(CloudError::IsFolder)));
+        assert_eq!(Error::from(CloudError::IsFolder).kind(), ErrorKind::IsADirectory);
+
+        // 之后不再请求
+        assert!(matches!(cloud.get_link("o1"), Err(CloudError::IsFolder)));
+        assert_eq!(canned.requests().len(), 1);
+    }
+
+    const SHARE_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>文件分享</title>
+<style>
+    body { font-size: 14px; }
+    .name { font-size: 16px; }
+    .tip[data-expire="0"] { display: none; }
+</style>
+</head>
+<body>
+<form name="share" action="/share/save">
+    <input type="hidden" name="objectid" value="o1">
+    <input type="text" size="30" maxFileSize="999">
+</form>
+<span class="name">report.pdf</span>
+<script>
+    var expire = 0;
+    var size = 'large';
+    var maxFileSize = 1048576;
+    var fileName = "report & \"final\".pdf";
+    var fileSize = '2048';
+    var expireTime = 1767225600;
+    var downloadUrl = 'http://d0.ananas.chaoxing.com/download/o1?at=1';
+</script>
+</body>
+</html>"#;
+
+    #[test]
+    fn share_info_full_page() {
+        let (cloud, canned) = mocked();
+        canned.push(format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{SHARE_PAGE}"));
+
+        let info = cloud.get_share_info("o1").unwrap();
+        assert_eq!(info.url, "http://d0.ananas.chaoxing.com/download/o1?at=1");
+        assert_eq!(info.name.as_deref(), Some("report & \"final\".pdf"));
+        assert_eq!(info.size, Some(2048));
+        assert_eq!(info.expires, Some(1767225600));
+    }
+
+    #[test]
+    fn export_links_collects_failures() {
+        let (mut cloud, canned) = mocked();
+        cloud.filemap.extend([("a.pdf".into(), "o1".into()), ("b.pdf".into(), "o2".into())]);
+        canned.push(format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{SHARE_PAGE}"));
+        canned.push("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<p>获取下载地址失败</p>".into());
+
+        let (links, failures): ExportedLinks = cloud.export_links().unwrap();
+        assert_eq!(links, [("a.pdf".to_string(), "http://d0.ananas.chaoxing.com/download/o1?at=1".to_string())]);
+        assert_eq!(failures.len(), 1);
+        assert!(matches!(&failures[0], (name, CloudError::NotFound) if name == "b.pdf"));
+    }
+
+    #[test]
+    fn share_info_ignores_markup() {
+        // 仅有 HTML/CSS 中的 `name` `size` `expire`，不会被当作文件信息
+        let page = SHARE_PAGE
+            .lines()
+            .filter(|x| !x.contains("var fileName") && !x.contains("var fileSize") && !x.contains("var expireTime"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (cloud, canned) = mocked();
+        canned.push(format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{page}"));
+
+        let info = cloud.get_share_info("o1").unwrap();
+        assert_eq!(info.url, "http://d0.ananas.chaoxing.com/download/o1?at=1");
+        assert_eq!((info.name, info.size, info.expires), (None, None, None));
+    }
+
+    // 记录推送的请求，并返回成功的响应
+    #[derive(Clone, Default)]
+    struct Pushed(Arc<Mutex<Vec<String>>>);
+
+    impl sal_notice::Transport for Pushed {
+        fn request(&mut self, _host: &str, raw: &[u8]) -> Result<Vec<u8>> {
+            self.0.lock().unwrap().push(String::from_utf8_lossy(raw).into());
+            Ok(http(r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#).into_bytes())
         }
+    }
 
-        Ok(res)
+    fn notice() -> (Notice<'static>, Pushed) {
+        let pushed = Pushed::default();
+        let mut notice = Notice::builder().token("dd1c8a").build().unwrap();
+        notice.set_transport(Box::new(pushed.clone()));
+        (notice, pushed)
     }
 
-    fn sixteen_to_eight(from: &[u16]) -> Vec<u8> {
-        let mut res = Vec::new();
+    #[test]
+    fn notify_new_files_pushes_names() {
+        let (mut cloud, canned) = mocked();
+        let (notice, pushed) = notice();
+        cloud.filemap.push(("old.txt".into(), "o1".into()));
+        canned.push(listing(&[("old.txt", "o1", "r1"), ("new.txt", "o2", "r2")]));
 
-        let len = from.len();
-        let max = len >> 1;
+        assert_eq!(notify_new_files(&mut cloud, &notice, "云盘\"新\"文件").unwrap(), 1);
+        assert!(!canned.sent("/api/delete"));
 
-        let mut i = 0;
+        let pushed = pushed.0.lock().unwrap();
+        assert_eq!(pushed.len(), 1);
+        assert!(pushed[0].contains(r#""template":"markdown""#));
+        assert!(pushed[0].contains(r#""title":"云盘\"新\"文件""#));
+        assert!(pushed[0].contains(r#""content":"- new.txt\n""#));
+    }
 
-        while i < max {
-            res.push((from[2 * i] / 256) as u8);
-            res.push((from[2 * i] % 256) as u8);
-            res.push((from[2 * i + 1] / 256) as u8);
-            res.push((from[2 * i + 1] % 256) as u8);
+    #[test]
+    fn notify_new_files_nothing_new() {
+        let (mut cloud, canned) = mocked();
+        let (notice, pushed) = notice();
+        cloud.filemap.push(("old.txt".into(), "o1".into()));
+        canned.push(listing(&[("old.txt", "o1", "r1")]));
 
-            i += 1;
+        assert_eq!(notify_new_files(&mut cloud, &notice, "云盘新文件").unwrap(), 0);
+        assert!(pushed.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("sal-file-{}.bin", std::process::id()));
+        let mut cloud = cloud();
+        cloud.filemap.extend([("a.txt".into(), "o1".into()), ("b c.txt".into(), "o2".into())]);
+        cloud.update_inner().unwrap();
+        cloud.to_file(&path).unwrap();
+
+        let loaded = CloudFile::from_file(&path).unwrap();
+        assert_eq!(loaded.get_filemap(), cloud.get_filemap());
+        assert_eq!(loaded.as_ref(), cloud.as_ref());
+
+        // 延迟编码时同样写出最新的文件表
+        cloud.set_deferred(true).unwrap();
+        cloud.filemap.push(("d.txt".into(), "o3".into()));
+        cloud.touch().unwrap();
+        cloud.to_file(&path).unwrap();
+        assert_eq!(CloudFile::from_file(&path).unwrap().len(), 3);
+        let _ = fs::remove_file(&path);
+
+        let Err(CloudError::Io(e)) = CloudFile::from_file(&path) else {
+            panic!("Expected Io Error");
+        };
+        assert!(e.kind() == ErrorKind::NotFound && e.to_string().contains(&*path.to_string_lossy()));
+    }
+
+    #[test]
+    fn user_agent_in_requests() {
+        let mut cloud = cloud();
+        assert!(cloud.build_link_request("o1").contains(&format!("\r\nUser-Agent: {USER_AGENT}\r\n")));
+
+        cloud.set_user_agent("Mozilla Curl Saloxy");
+        let (upload, _, _) = cloud.build_upload_request("a.txt", 1);
+        for request in [
+            cloud.build_scan_request("94555", 1, 100),
+            cloud.build_link_request("o1"),
+            cloud.build_delete_request(&["r1".into()]),
+            upload,
+        ] {
+            assert!(request.contains("\r\nUser-Agent: Mozilla Curl Saloxy\r\n"), "{request}");
         }
+    }
 
-        if len % 2 == 1 {
-            res.push((from[len - 1] / 256) as u8);
-            res.push((from[len - 1] % 256) as u8);
+    #[test]
+    fn request_bytes() {
+        let mut cloud = CloudFile::new("29 001".into(), "b8+bd/01".into(), "94555".into(), &PASSWD).unwrap();
+        cloud.set_user_agent("sal");
+
+        assert_eq!(
+            cloud.build_scan_request("f&1", 2, 100),
+            "GET /api/getMyDirAndFiles?puid=29%20001&_token=b8%2Bbd%2F01&fldid=f%261&page=2&size=100 HTTP/1.1\r\n\
+            Connection: Keep-Alive\r\nUser-Agent: sal\r\nHost: pan-yz.chaoxing.com\r\n\r\n"
+        );
+        assert_eq!(
+            cloud.build_link_request("o 1/2"),
+            "GET /share/download/o%201%2F2 HTTP/1.1\r\nUser-Agent: sal\r\nHost: sharewh.xuexi365.com\r\n\r\n"
+        );
+        assert_eq!(
+            cloud.build_delete_request(&["r1".into(), "r,2".into()]),
+            "GET /api/delete?puid=29%20001&_token=b8%2Bbd%2F01&resids=r1,r%2C2 HTTP/1.1\r\n\
+            User-Agent: sal\r\nHost: pan-yz.chaoxing.com\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn mocked_scan_and_link() {
+        let (mut cloud, canned) = mocked();
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2")])).push(deleted());
+        canned.push(http(r#"var downloadUrl='http://d0.x/a.txt';"#));
+        assert_eq!(cloud.scan().unwrap(), 2);
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.x/a.txt");
+        assert_eq!(cloud.get_filemap(), [("a.txt".into(), "o1".into()), ("b.txt".into(), "o2".into())]);
+
+        let parse = |res: CloudResult<usize>| matches!(res, Err(CloudError::Parse(e)) if e.contains("InvalidData"));
+        // 没有头部结尾、没有文件列表、缺少 `objectId`
+        let malformed = [
+            "HTTP/1.1 200 OK".to_string(),
+            http(r#"{"result":true,"data":{},"msg":"ok"}"#),
+            http(r#"{"result":true,"data":[{"name":"a","residstr":"r1"}],"msg":"ok"}"#),
+        ];
+        for response in malformed {
+            let (mut cloud, canned) = mocked();
+            canned.push(response);
+            assert!(parse(cloud.scan()));
+            assert!(cloud.is_empty() && !canned.sent("/api/delete"));
         }
 
-        res
+        let (mut cloud, canned) = mocked();
+        canned.push(http(r#"{"result":false,"msg":"参数错误"}"#));
+        assert!(matches!(cloud.scan(), Err(CloudError::ServerError(x)) if x.contains("参数错误")));
+
+        // 删除失败时，文件已记录，错误单独返回
+        canned.push(listing(&[("a.txt", "o1", "r1")])).push(http(r#"{"result":false,"msg":"x"}"#));
+        assert!(matches!(cloud.scan(), Err(CloudError::Io(e)) if e.kind() == ErrorKind::PermissionDenied));
+        canned.push(listing(&[("b.txt", "o2", "r2")])).push("HTTP/1.1 200 OK".into());
+        assert!(matches!(cloud.scan(), Err(CloudError::Io(e)) if e.kind() == ErrorKind::ConnectionReset));
+        assert_eq!(cloud.len(), 2);
     }
 
-    fn eight_to_sixteen(from: &[u8]) -> Vec<u16> {
-        let mut res = Vec::new();
+    #[test]
+    fn scan_all_three_accounts() {
+        let accounts: Vec<(CloudFile, Canned)> = (1..=3).map(|n| {
+            let (cloud, canned) = mocked();
+            let files: Vec<(String, String, String)> = (0..n).map(|i| (format!("{n}-{i}.txt"), format!("o{n}{i}"), format!("r{n}{i}"))).collect();
+            let files: Vec<(&str, &str, &str)> = files.iter().map(|(a, b, c)| (a.as_str(), b.as_str(), c.as_str())).collect();
+            canned.push(listing(&files)).push(deleted());
+            canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+            (cloud, canned)
+        }).collect();
+        let (accounts, canned): (Vec<_>, Vec<_>) = accounts.into_iter().unzip();
 
-        let len = from.len();
-        let max = len >> 1;
+        let res = scan_all(accounts);
+        let counts: Vec<usize> = res.iter().map(|(_, x)| *x.as_ref().unwrap()).collect();
+        assert_eq!(counts, [1, 2, 3]); // 与传入的顺序相同
+        for (n, (cloud, _)) in res.iter().enumerate() {
+            assert!(cloud.get_filemap().iter().all(|(name, _)| name.starts_with(&format!("{}-", n + 1))));
+        }
+        assert!(canned.iter().all(|x| x.sent("/api/delete") && x.responses.lock().unwrap().is_empty()));
+    }
 
-        let mut i = 0;
-        while i < max {
-            res.push(256 * from[2 * i] as u16 + from[2 * i + 1] as u16);
-            i += 1;
+    #[test]
+    fn view_and_share_urls() {
+        assert_eq!(CloudFile::view_url("a1b2"), "http://cloud.ananas.chaoxing.com/view/fileview?objectid=a1b2");
+        assert_eq!(CloudFile::view_url("a/b c&d=e"), "http://cloud.ananas.chaoxing.com/view/fileview?objectid=a%2Fb%20c%26d%3De");
+        assert_eq!(CloudFile::share_url("a1b2"), "http://sharewh.xuexi365.com/share/download/a1b2");
+        assert_eq!(CloudFile::share_url("a/b?c"), "http://sharewh.xuexi365.com/share/download/a%2Fb%3Fc");
+    }
+
+    #[test]
+    fn stream_status_transitions() {
+        let (mut cloud, canned) = mocked();
+        assert_eq!(cloud.stream_status(), StreamStatus::Disconnected);
+
+        cloud.set_stream(Stream::Scan).unwrap();
+        assert_eq!(cloud.stream_status(), StreamStatus::Scan);
+        cloud.set_stream(Stream::Link).unwrap();
+        assert_eq!(cloud.stream_status(), StreamStatus::Both);
+
+        // 扫描至结束时自动结束 `Scan` 流
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+        assert!(matches!(cloud.scan(), Err(CloudError::ScanFinished)));
+        assert_eq!(cloud.stream_status(), StreamStatus::Link);
+
+        cloud.set_stream(Stream::None).unwrap();
+        assert_eq!(cloud.stream_status(), StreamStatus::Disconnected);
+    }
+
+    #[test]
+    fn auto_connect_streams() {
+        let (mut cloud, canned) = mocked();
+        cloud.set_hosts("scan.example", "link.example");
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.x/a");
+        assert_eq!(cloud.stream_status(), StreamStatus::Link);
+
+        canned.push(listing(&[("a.txt", "o1", "r1")])).push(deleted());
+        assert_eq!(cloud.scan().unwrap(), 1);
+        let hosts: Vec<String> = canned.requests().into_iter().map(|(host, _)| host).collect();
+        assert_eq!(hosts, ["link.example:80", "scan.example:80", "scan.example:80"]);
+
+        // 真实连接同样自动建立
+        let (cloud, server) = served();
+        server.push_response("HTTP/1.1 200 OK\r\nContent-Length: 31\r\n\r\nvar downloadUrl='http://d0.x/b';");
+        assert_eq!(cloud.get_link("o2").unwrap(), "http://d0.x/b");
+    }
+
+    #[test]
+    fn rate_limit_interval() {
+        let (mut cloud, canned) = mocked();
+        cloud.set_rate_limit(Duration::from_millis(200));
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        canned.push(http(r#"var downloadUrl='http://d0.x/b';"#));
+
+        let start = Instant::now();
+        cloud.get_link("o1").unwrap();
+        assert!(start.elapsed() < Duration::from_millis(200)); // 首个请求不等待
+        cloud.get_link("o2").unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn diff_added_and_removed() {
+        let (mut old, canned) = mocked();
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2")])).push(deleted());
+        old.scan().unwrap();
+
+        let (mut cloud, canned) = mocked();
+        canned.push(listing(&[("b.txt", "o2", "r2"), ("c.txt", "o3", "r3")])).push(deleted());
+        cloud.scan().unwrap();
+
+        let diff = cloud.diff(&old);
+        assert_eq!(diff.added, [("c.txt".to_string(), "o3".to_string())]);
+        assert_eq!(diff.removed, [("a.txt".to_string(), "o1".to_string())]);
+        assert!(cloud.diff(&cloud).added.is_empty());
+    }
+
+    #[test]
+    fn scan_fifty_across_pages() {
+        let (mut cloud, canned) = mocked();
+        let names: Vec<String> = (0..50).map(|x| format!("f{x}")).collect();
+        let files: Vec<(&str, &str, &str)> = names.iter().map(|x| (x.as_str(), x.as_str(), x.as_str())).collect();
+        canned.push(listing(&files[..30])).push(deleted());
+        canned.push(listing(&files[30..])).push(deleted());
+        canned.push(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+
+        assert_eq!(cloud.scan_all_pages().unwrap(), 50);
+        assert_eq!(cloud.len(), 50);
+        assert_eq!(cloud.name_of("f49"), Some("f49"));
+        assert_eq!(cloud.stream_status(), StreamStatus::Disconnected);
+
+        let scans: Vec<String> = canned.requests().into_iter().map(|(_, x)| x).filter(|x| x.contains("getMyDirAndFiles")).collect();
+        assert_eq!(scans.len(), 3);
+        assert!(scans.iter().all(|x| x.contains(&format!("&page=1&size={SCAN_SIZE} "))));
+    }
+
+    #[test]
+    fn local_mock_host_header() {
+        let (cloud, server) = served();
+        server.push_response(http(r#"var downloadUrl='http://d0.x/a';"#));
+        server.push_response(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.x/a");
+        cloud.list_folder("f1").unwrap();
+
+        let host = format!("\r\nHost: {}\r\n", server.host());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|x| String::from_utf8_lossy(x).contains(&host)), "{host}");
+    }
+
+    #[test]
+    fn objid_validation() {
+        let (cloud, canned) = mocked();
+        let invalid = |res: CloudResult<String>| matches!(res, Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput);
+
+        assert!(invalid(cloud.get_link("")));
+        assert!(invalid(cloud.get_link(" \t ")));
+        assert!(invalid(cloud.get_link("o1\r\nX-Evil: 1")));
+        assert!(matches!(cloud.list_folder(" "), Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput));
+        assert!(canned.requests().is_empty()); // 校验失败时不发送请求
+
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        assert_eq!(cloud.get_link("0a1b2c").unwrap(), "http://d0.x/a");
+    }
+
+    #[test]
+    fn deferred_thousand_files_encode_once() {
+        let (mut cloud, canned) = mocked();
+        let names: Vec<String> = (0..1000).map(|x| format!("f{x:04}")).collect();
+        let files: Vec<(&str, &str, &str)> = names.iter().map(|x| (x.as_str(), x.as_str(), x.as_str())).collect();
+        for page in files.chunks(SCAN_SIZE) {
+            canned.push(listing(page)).push(deleted());
         }
 
-        if len % 2 == 1 {
-            res.push(from[len - 1] as u16);
+        let base = cloud.as_ref().to_vec();
+        let start = Instant::now();
+        cloud.set_deferred(true).unwrap();
+        for _ in 0..10 {
+            assert_eq!(cloud.scan().unwrap(), SCAN_SIZE);
+            assert_eq!(cloud.as_ref(), base); // 扫描期间不编码
         }
+        cloud.finalize().unwrap(); // 结束时编码一次
+        let elapsed = start.elapsed();
 
-        res
+        assert_ne!(cloud.as_ref(), base);
+        let parsed = CloudFile::from_raw(cloud.as_ref()).unwrap();
+        assert_eq!((parsed.len(), parsed.name_of("f0999")), (1000, Some("f0999")));
+        assert!(elapsed < Duration::from_secs(10), "{elapsed:?}");
+    }
+
+    #[test]
+    fn passwd_rotate_round_trip() {
+        let (mut cloud, canned) = mocked();
+        canned.push(listing(&[("a.txt", "o1", "r1")])).push(deleted());
+        cloud.scan().unwrap();
+        let old = cloud.as_ref().to_vec();
+
+        let new_passwd = [100, 3, 7, 90];
+        cloud.passwd_rotate(&new_passwd).unwrap();
+        assert_eq!(&cloud.as_ref()[8..12], new_passwd);
+        assert_ne!(cloud.as_ref(), old);
+
+        let parsed = CloudFile::from_raw(cloud.as_ref()).unwrap();
+        assert_eq!(parsed.get_filemap(), cloud.get_filemap());
+        assert_eq!((parsed.uid.as_str(), parsed.dirid.as_str()), ("29001", "94555"));
+
+        let rotated = cloud.as_ref().to_vec();
+        assert!(cloud.passwd_rotate(&[1, 1, 1, 1]).is_err()); // 行列式为零
+        assert_eq!(cloud.as_ref(), rotated);
+
+        cloud.format = Format::V2;
+        cloud.filemap.push(("a".repeat(70000), "o2".into())); // 超出 `V2` 的长度限制，编码失败
+        assert!(matches!(cloud.passwd_rotate(&[127, 97, 112, 128]), Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidInput));
+        assert_eq!(cloud.as_ref(), rotated);
+    }
+
+    #[test]
+    fn mismatched_password_detected() {
+        let wrong = |cloud: &CloudFile| {
+            let mut raw = cloud.as_ref().to_vec();
+            raw[8..12].copy_from_slice(&[100, 3, 7, 90]); // 合法但与数据不匹配的密码
+            matches!(CloudFile::from_raw(&raw), Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidData)
+        };
+
+        let mut cloud = cloud();
+        cloud.filemap.push(("a.txt".into(), "o1".into()));
+        cloud.update_inner().unwrap();
+        assert!(wrong(&cloud));
+
+        cloud.set_format(Format::V2).unwrap();
+        assert!(wrong(&cloud));
+        assert!(CloudFile::from_raw(cloud.as_ref()).is_ok());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut cloud = cloud();
+        cloud.set_format(Format::V2).unwrap();
+        cloud.filemap.extend([("a \"q\".txt".into(), "o1".into()), ("中文.pdf".into(), "o2".into())]);
+        cloud.update_inner().unwrap();
+
+        let parsed = CloudFile::from_json(&cloud.to_json_full(), &PASSWD).unwrap();
+        assert_eq!((parsed.uid.as_str(), parsed.token.as_str(), parsed.dirid.as_str()), ("29001", "b8bd0001", "94555"));
+        assert_eq!((parsed.format, parsed.get_filemap()), (Format::V2, cloud.get_filemap()));
+        assert_eq!(parsed.as_ref(), cloud.as_ref()); // 同一密码下编码结果一致
+
+        assert!(cloud.to_json().starts_with(r#"{"uid":"29***","#), "{}", cloud.to_json());
+        assert!(matches!(CloudFile::from_json(&cloud.to_json(), &PASSWD), Err(CloudError::Io(e)) if e.kind() == ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn scan_if_changed_unchanged() {
+        let (mut cloud, canned) = mocked();
+        let tagged = |raw: String| raw.replacen("\r\n", "\r\nETag: \"v1\"\r\n", 1);
+        canned.push(tagged(listing(&[("a.txt", "o1", "r1")]))).push(deleted());
+        canned.push(String::from("HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n"));
+        canned.push(tagged(listing(&[("b.txt", "o2", "r2")])));
+
+        assert_eq!(cloud.scan_if_changed().unwrap(), Some(1));
+        assert_eq!(cloud.scan_if_changed().unwrap(), None); // `304`
+        assert_eq!(cloud.scan_if_changed().unwrap(), None); // 相同的 `ETag`
+        assert_eq!(cloud.len(), 1);
+        assert_eq!(cloud.stream_status(), StreamStatus::Disconnected);
+
+        let requests = canned.requests();
+        assert_eq!(requests.len(), 4); // 未改变时不删除
+        assert!(!requests[0].1.contains("If-None-Match"));
+        assert!(requests[2..].iter().all(|(_, x)| x.contains("\r\nIf-None-Match: \"v1\"\r\n\r\n")));
+    }
+
+    #[test]
+    fn scan_and_link_interleaved() {
+        let (mut cloud, canned) = mocked();
+        cloud.set_hosts("scan.example", "link.example");
+        cloud.set_stream(Stream::Scan).unwrap();
+        cloud.set_stream(Stream::Link).unwrap();
+        assert_eq!(cloud.stream_status(), StreamStatus::Both);
+
+        canned.push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        canned.push(listing(&[("b.txt", "o2", "r2")])).push(deleted());
+        canned.push(http(r#"var downloadUrl='http://d0.x/b';"#));
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.x/a");
+        assert_eq!(cloud.scan().unwrap(), 1);
+        assert_eq!(cloud.get_link("o2").unwrap(), "http://d0.x/b");
+        assert_eq!(cloud.stream_status(), StreamStatus::Both);
+
+        let hosts: Vec<String> = canned.requests().into_iter().map(|(host, _)| host).collect();
+        assert_eq!(hosts, ["link.example:80", "scan.example:80", "scan.example:80", "link.example:80"]);
+
+        // 两个真实的服务器，各自只收到对应的请求
+        let (scan, link) = (mock_server::MockServer::new(), mock_server::MockServer::new());
+        let mut cloud = self::cloud();
+        cloud.set_hosts(&scan.host(), &link.host());
+        cloud.set_stream(Stream::Scan).unwrap();
+        cloud.set_stream(Stream::Link).unwrap();
+        for x in ["a", "b"] {
+            scan.push_response(http(r#"{"result":true,"data":[],"msg":"ok"}"#));
+            link.push_response(http(&format!("var downloadUrl='http://d0.x/{x}';")));
+            cloud.list_folder("f1").unwrap();
+            assert_eq!(cloud.get_link(x).unwrap(), format!("http://d0.x/{x}"));
+        }
+        assert_eq!((scan.requests().len(), link.requests().len()), (2, 2));
+        assert!(link.requests().iter().all(|x| x.starts_with(b"GET /share/download/")));
+        assert!(scan.requests().iter().all(|x| !x.starts_with(b"GET /share/download/")));
+    }
+
+    #[test]
+    fn reset_once_then_success() {
+        let (mut cloud, canned) = mocked();
+        cloud.set_retries(1);
+        canned.fail(ErrorKind::ConnectionReset).push(http(r#"var downloadUrl='http://d0.x/a';"#));
+
+        let start = Instant::now();
+        assert_eq!(cloud.get_link("o1").unwrap(), "http://d0.x/a");
+        assert!(start.elapsed() >= RETRY_DELAY);
+        assert_eq!(canned.requests().len(), 2);
+
+        // 重试用尽后返回最后的错误
+        canned.fail(ErrorKind::ConnectionReset).fail(ErrorKind::BrokenPipe);
+        assert!(matches!(cloud.get_link("o1"), Err(CloudError::Io(e)) if e.kind() == ErrorKind::BrokenPipe));
+
+        // 其他错误不重试
+        canned.fail(ErrorKind::PermissionDenied).push(http(r#"var downloadUrl='http://d0.x/a';"#));
+        assert!(matches!(cloud.get_link("o1"), Err(CloudError::Io(e)) if e.kind() == ErrorKind::PermissionDenied));
+        assert_eq!(canned.requests().len(), 5);
+    }
+
+    #[test]
+    fn scan_short_responses() {
+        let (mut cloud, canned) = mocked();
+        canned.push(String::new());
+        canned.push(String::from("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n"));
+        canned.push(String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n  \r\n"));
+
+        for _ in 0..3 {
+            let res = cloud.scan();
+            assert!(matches!(&res, Err(CloudError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof), "{res:?}");
+        }
+        assert!(cloud.is_empty());
+        assert!(!canned.sent("/api/delete")); // 不与认证失败混淆，也不删除
+    }
+
+    #[test]
+    fn len_and_is_empty() {
+        let (mut cloud, canned) = mocked();
+        assert_eq!((cloud.len(), cloud.is_empty()), (0, true));
+        assert_eq!((&cloud).into_iter().count(), 0);
+
+        canned.push(listing(&[("a.txt", "o1", "r1"), ("b.txt", "o2", "r2")])).push(deleted());
+        cloud.scan().unwrap();
+        assert_eq!((cloud.len(), cloud.is_empty()), (2, false));
+        let files: Vec<_> = (&cloud).into_iter().collect();
+        assert_eq!(files.len(), cloud.len());
+    }
+
+    #[test]
+    fn from_env_credentials() {
+        // 使用独有的前缀，避免与其他测试互相影响
+        std::env::set_var("SAL_TEST_ENV_UID", " 29001 ");
+        std::env::set_var("SAL_TEST_ENV_TOKEN", "b8bd0001");
+        std::env::set_var("SAL_TEST_ENV_DIRID", "94555");
+        let cloud = CloudFile::from_env("SAL_TEST_ENV", &PASSWD).unwrap();
+        assert_eq!((cloud.uid.as_str(), cloud.token.as_str(), cloud.dirid.as_str()), ("29001", "b8bd0001", "94555"));
+
+        std::env::set_var("SAL_TEST_MISSING_UID", "29001");
+        std::env::set_var("SAL_TEST_MISSING_TOKEN", " ");
+        let e = match CloudFile::from_env("SAL_TEST_MISSING", &PASSWD) {
+            Err(CloudError::Io(e)) => e,
+            _ => panic!("expected an io error"),
+        };
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+        assert!(e.to_string().contains("SAL_TEST_MISSING_TOKEN"), "{e}"); // 错误信息包含变量名
+
+        std::env::set_var("SAL_TEST_ROOT_UID", "29001");
+        std::env::set_var("SAL_TEST_ROOT_TOKEN", "b8bd0001");
+        let cloud = CloudFile::from_env("SAL_TEST_ROOT", &PASSWD).unwrap(); // 未设置 `DIRID`
+        assert_eq!(cloud.dirid, "");
+        std::env::set_var("SAL_TEST_ROOT_DIRID", " ");
+        let cloud = CloudFile::from_env("SAL_TEST_ROOT", &PASSWD).unwrap();
+        assert_eq!(cloud.dirid, "");
+    }
+
+    #[test]
+    fn passwd_table() {
+        let valid: [[u8; 4]; 5] = [PASSWD, [1, 0, 0, 1], [128, 0, 0, 128], [128, 127, 127, 128], [2, 1, 1, 1]];
+        let invalid: [[u8; 4]; 7] = [
+            [0, 0, 0, 0],
+            [1, 1, 1, 1],         // 行列式为零
+            [1, 2, 3, 4],         // 行列式为负
+            [0, 128, 128, 0],
+            [129, 0, 0, 1],       // 超出范围
+            [100, 128, 128, 100], // 乘积超出 `u8`，行列式为负
+            [255, 255, 255, 255],
+        ];
+
+        for passwd in valid {
+            assert!(CloudFile::validate_passwd(&passwd).is_ok(), "{passwd:?}");
+            assert!(CloudFile::new("1".into(), "2".into(), "3".into(), &passwd).is_ok(), "{passwd:?}");
+        }
+        for passwd in invalid {
+            let e = CloudFile::validate_passwd(&passwd).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput, "{passwd:?}");
+            assert!(CloudFile::new("1".into(), "2".into(), "3".into(), &passwd).is_err(), "{passwd:?}");
+        }
     }
 }
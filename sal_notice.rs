@@ -1,13 +1,51 @@
 //!
 //! 一个基于 `PushPlus` 的微信信息推送方案
 //!
+//! 与上级模块共用 `httpconn` 与 `json`，单独使用时需在同级声明 `mod httpconn;` `mod json;`
+//!
 
-use std::io::{BufRead as _, Write as _};
-use std::io::{BufReader, BufWriter};
-use std::{fmt, net::TcpStream};
+use std::{borrow::Cow, fmt, sync::{Mutex, PoisonError}, time::{Duration, Instant}};
+use super::httpconn::HttpConn;
+use super::json::Value;
 
 pub use std::io::{Error, ErrorKind, Result};
 const HOST: &str = "www.pushplus.plus:80";
+const RATE_LIMITED: &str = "900"; // 用户账号使用受限 (请求过于频繁)
+const AUTH_FAILED: &str = "903"; // 无效的用户令牌
+const ACCESS_KEY_TTL: u64 = 7200; // 服务器未返回 `expiresIn` 时 `access-key` 的有效期 (秒)
+
+///
+/// 推送错误枚举
+///
+/// - NoticeError::RateLimited => 被限流 (`429` 或 `code: 900`)
+/// - NoticeError::Auth => 令牌无效 (`401` 或 `code: 903`)
+/// - NoticeError::Parse => 无法解析服务器的返回内容
+/// - NoticeError::Transport => 网络错误，或传输层正被占用
+/// - NoticeError::InvalidInput => 参数错误，如未设置 `token`
+///
+/// **Example:**
+/// ```
+/// mod sal_notice;
+/// use sal_notice::{Channel, Notice, NoticeError, Template};
+///
+/// let noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+/// match noter.send("Title", "Content".into()) {
+///     Ok(res) => println!("{res}"),
+///     Err(NoticeError::RateLimited(_)) => println!("稍后再试"),
+///     Err(e) => println!("{e}"),
+/// }
+/// ```
+///
+#[derive(Debug)]
+pub enum NoticeError {
+    RateLimited(String),
+    Auth(String),
+    Parse(String),
+    Transport(Error),
+    InvalidInput(String),
+}
+
+pub type NoticeResult<T> = std::result::Result<T, NoticeError>;
 
 ///
 /// Notice 通知数据结构体
@@ -21,9 +59,14 @@ const HOST: &str = "www.pushplus.plus:80";
 /// ```
 ///
 pub struct Notice<'a> {
-    token: &'a str,
+    token: Cow<'a, str>,
     template: Template,
     channel: Channel,
+    host: String, // 默认为 `HOST`
+    to: Option<Cow<'a, str>>, // 好友令牌，为 `None` 时推送给自己
+    secret_key: Option<Cow<'a, str>>, // 开放接口的 `secretKey`，用于获取 `access-key`
+    access_key: Mutex<Option<(String, Instant)>>, // 缓存的 `access-key` 及其过期时间
+    transport: Mutex<Box<dyn Transport>>, // 多线程共享实例时依次发送
 }
 
 ///
@@ -36,8 +79,8 @@ pub struct Notice<'a> {
 ///     data: String
 ///
 /// > 数据来源于服务器的返回内容,
-/// 具体信息请查询 `PushPlus` 官方文档
-/// http://pushplus.plus/doc/guide/code.html
+/// > 具体信息请查询 `PushPlus` 官方文档
+/// > http://pushplus.plus/doc/guide/code.html
 ///
 pub struct Response {
     pub code: String,
@@ -45,17 +88,44 @@ pub struct Response {
     pub data: String,
 }
 
+///
+/// DeliveryStatus 投递状态枚举
+///
+/// 用于储存 `query_status` 查询到的 ***消息投递状态***
+///
+/// - DeliveryStatus::Pending => 未投递或投递中 (`sendStatus: 0 | 1`)
+/// - DeliveryStatus::Delivered => 投递成功 (`sendStatus: 2`)
+/// - DeliveryStatus::Failed => 投递失败 (`sendStatus: 3`)，附带服务器返回的错误信息
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed(String),
+}
+
 ///
 /// Template 模板数据枚举
 ///
 /// 用于储存 ***推送模板*** 信息
 ///
+/// - Template::Custom => 原样使用所给的模板名，用于 `PushPlus` 新增的模板，如 `cloudMonitor`
+///
+/// **Example:**
+/// ```
+/// mod sal_notice;
+/// use sal_notice::Template;
+///
+/// assert_eq!(Template::Custom("cloudMonitor".into()).to_string(), "cloudMonitor");
+/// ```
+///
 #[allow(dead_code)]
 pub enum Template {
     HTML,
     TXT,
     JSON,
     MD,
+    Custom(String),
 }
 
 ///
@@ -69,6 +139,105 @@ pub enum Channel {
     Email,
 }
 
+///
+/// NoticeBuilder 构建器结构体
+///
+/// 由 `Notice::builder` 创建，用于避免位置参数，并允许使用 `String` 作为 `token`
+///
+/// - template: 默认为 `Template::TXT`
+/// - channel: 默认为 `Channel::Wechat`
+/// - secret_key: 默认不设置，见 `Notice::with_secret_key`
+///
+pub struct NoticeBuilder<'a> {
+    token: Option<Cow<'a, str>>,
+    template: Template,
+    channel: Channel,
+    secret_key: Option<Cow<'a, str>>,
+}
+
+///
+/// Transport 网络传输特征
+///
+/// 用于替换 `Notice` 与服务器交流的方式，默认为 `TcpTransport`
+///
+/// - request: 发送原始请求，并返回服务器的原始响应
+///
+/// **Example:**
+/// ```
+/// mod sal_notice;
+/// use sal_notice::{Channel, Notice, Result, Template, Transport};
+///
+/// struct Mock;
+///
+/// impl Transport for Mock {
+///     fn request(&mut self, _host: &str, _raw: &[u8]) -> Result<Vec<u8>> {
+///         Ok(Vec::from(r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#))
+///     }
+/// }
+///
+/// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+/// noter.set_transport(Box::new(Mock));
+/// ```
+///
+pub trait Transport: Send {
+    fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>>;
+}
+
+///
+/// TcpTransport 默认的网络传输实现
+///
+/// 基于 `HttpConn` 保持连接，读取完整的响应，连接断开时自动重新连接
+///
+/// - 根据 `Content-Length` 或 `Transfer-Encoding: chunked` 读取主体，
+///   头部与主体分多次到达时也会等待其完整到达
+/// - 默认不限制等待时间，可使用 `with_timeout` 设置
+///
+#[derive(Default)]
+pub struct TcpTransport {
+    conn: Option<HttpConn>,
+    timeout: Option<Duration>,
+}
+
+impl Transport for TcpTransport {
+    fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>> {
+        if !matches!(&self.conn, Some(x) if x.host() == host) {
+            self.conn = Some(self.new_conn(host));
+        }
+
+        match &mut self.conn {
+            Some(conn) => conn.request(raw),
+            None => Err(Error::new(ErrorKind::NotConnected, "Not Connected!")),
+        }
+    }
+}
+
+impl TcpTransport {
+    ///
+    /// 创建一个带有超时时间的 `TcpTransport`，作用于连接与读写
+    ///
+    /// 响应未能在超时时间内完整到达时，返回 `NoticeError::Transport`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, TcpTransport, Template};
+    /// use std::time::Duration;
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.set_transport(Box::new(TcpTransport::with_timeout(Duration::from_secs(10))));
+    /// ```
+    ///
+    pub fn with_timeout(timeout: Duration) -> TcpTransport {
+        TcpTransport { conn: None, timeout: Some(timeout) }
+    }
+
+    fn new_conn(&self, host: &str) -> HttpConn {
+        let mut conn = HttpConn::new(host);
+        let _ = conn.set_timeout(self.timeout); // 尚未连接，不会失败
+        conn
+    }
+}
+
 impl<'a> Notice<'a> {
     ///
     /// 创建一个新的 `Notice` 实例
@@ -102,12 +271,147 @@ impl<'a> Notice<'a> {
     ///
     pub fn new(token: &'a str, template: Template, channel: Channel) -> Notice<'a> {
         Self {
-            token,
+            token: token.into(),
             template,
             channel,
+            host: HOST.into(),
+            to: None,
+            secret_key: None,
+            access_key: Mutex::new(None),
+            transport: Mutex::new(Box::new(TcpTransport::default())),
+        }
+    }
+
+    ///
+    /// 创建一个 `NoticeBuilder` 构建器
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let token = String::from("dd1c8a......");
+    ///
+    /// let noter = Notice::builder()
+    ///     .token(token)
+    ///     .template(Template::MD)
+    ///     .channel(Channel::Wechat)
+    ///     .build()?;
+    /// ```
+    ///
+    pub fn builder() -> NoticeBuilder<'a> {
+        NoticeBuilder {
+            token: None,
+            template: Template::TXT,
+            channel: Channel::Wechat,
+            secret_key: None,
         }
     }
 
+    ///
+    /// 替换实例与服务器交流的方式
+    ///
+    /// 参数：
+    /// - transport: `Box<dyn Transport>` 新的网络传输实现
+    ///     - 默认为 `TcpTransport`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, TcpTransport, Template};
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.set_transport(Box::new(TcpTransport::default()));
+    /// ```
+    ///
+    pub fn set_transport(&mut self, transport: Box<dyn Transport>) {
+        self.transport = Mutex::new(transport);
+    }
+
+    ///
+    /// 替换推送服务器，用于私有部署或代理的 `PushPlus`
+    ///
+    /// 参数：
+    /// - host: `host` 或 `host:port`，省略端口时为 `80`
+    ///     - 默认为 `www.pushplus.plus:80`
+    ///
+    /// 作用于连接目标与请求的 `Host` 头部
+    ///
+    /// 返回一个 `NoticeResult<()>` 枚举
+    /// - Ok(())
+    /// - Err(NoticeError::InvalidInput): 主机名或端口不合法
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.with_host("push.example.com:8080")?;
+    /// ```
+    ///
+    pub fn with_host(&mut self, host: &str) -> NoticeResult<()> {
+        let (name, port) = match host.rsplit_once(':') {
+            Some((name, port)) if !host.ends_with(']') => (name, Some(port)),
+            _ => (host, None),
+        };
+
+        let valid = match name.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+            Some(ip) => ip.parse::<std::net::Ipv6Addr>().is_ok(),
+            None => !name.is_empty() && name.bytes().all(|x| x.is_ascii_alphanumeric() || x == b'-' || x == b'.'),
+        };
+        if !valid || port.is_some_and(|x| x.parse::<u16>().is_err()) {
+            return Err(NoticeError::InvalidInput(format!("Invalid Host: {host}")));
+        };
+
+        self.host = format!("{name}:{}", port.unwrap_or("80"));
+        Ok(())
+    }
+
+    ///
+    /// 设置推送的接收者，用于向好友推送消息
+    ///
+    /// 参数：
+    /// - to: 好友令牌，可以为 `&str` 或 `String`
+    ///
+    /// 设置后，请求体中会包含 `to` 字段；未设置时不会出现该字段
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.with_to("c2b1e7......");
+    /// ```
+    ///
+    pub fn with_to<T: Into<Cow<'a, str>>>(&mut self, to: T) {
+        self.to = Some(to.into());
+    }
+
+    ///
+    /// 设置开放接口的 `secretKey`，`query_status` 需要使用
+    ///
+    /// 参数：
+    /// - secret_key: 在 `PushPlus` 的开发设置中获取，可以为 `&str` 或 `String`
+    ///
+    /// `PushPlus` 的开放接口以 `access-key` 鉴权，首次查询时以 `token` 与 `secretKey` 获取，
+    /// 过期前复用；设置新的 `secretKey` 时清除已缓存的 `access-key`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.with_secret_key("5e2f9a......");
+    /// ```
+    ///
+    pub fn with_secret_key<T: Into<Cow<'a, str>>>(&mut self, secret_key: T) {
+        self.secret_key = Some(secret_key.into());
+        *self.access_key.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
     ///
     /// 在构建完成之后发送数据
     ///
@@ -115,11 +419,11 @@ impl<'a> Notice<'a> {
     /// - title: 所发送的标题
     /// - content: 所发送的内容
     ///
-    /// 返回一个 `io::Result<Response>` 枚举
+    /// 返回一个 `NoticeResult<Response>` 枚举
     /// - 成功：
     ///     - Ok(Response): Response
     /// - 失败：
-    ///     - Err(io::Error): io::ErrorKind 枚举
+    ///     - Err(NoticeError): 详见 `NoticeError`
     ///
     /// **Example:**
     /// ```
@@ -142,76 +446,364 @@ impl<'a> Notice<'a> {
     ///
     /// *请注意：该方法会阻塞运行！*
     ///
-    pub fn send<'f>(&self, title: &'f str, content: String) -> Result<Response> {
-        let stream = TcpStream::connect(HOST)?;
-        let mut reader = BufReader::new(&stream);
-        let mut writer = BufWriter::new(&stream);
-        let _ = writer.write(self.structen(title, content).as_bytes())?;
-        let _ = writer.flush()?;
+    pub fn send(&self, title: &str, content: String) -> NoticeResult<Response> {
+        self.send_as(&self.template, title, content)
+    }
+
+    ///
+    /// 以 `markdown` 模板发送数据
+    ///
+    /// 参数：
+    /// - title: 所发送的标题
+    /// - md: 所发送的 `markdown` 内容
+    ///
+    /// 返回值与 `send` 相同
+    ///
+    /// > 注意，该方法仅在本次发送中覆盖实例的模板，不会修改实例
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// let res = noter.send_markdown("Newest Data!!! 🤤", "# Hello\n- World").unwrap();
+    /// ```
+    ///
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn send_markdown(&self, title: &str, md: &str) -> NoticeResult<Response> {
+        self.send_as(&Template::MD, title, md.into())
+    }
+
+    ///
+    /// 以 `html` 模板发送数据
+    ///
+    /// 参数：
+    /// - title: 所发送的标题
+    /// - html: 所发送的 `html` 内容
+    ///
+    /// 返回值与 `send` 相同
+    ///
+    /// > 注意，该方法仅在本次发送中覆盖实例的模板，不会修改实例
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, Notice, Template};
+    ///
+    /// let noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// let res = noter.send_html("Newest Data!!! 🤤", "<b>Hello World</b>").unwrap();
+    /// ```
+    ///
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn send_html(&self, title: &str, html: &str) -> NoticeResult<Response> {
+        self.send_as(&Template::HTML, title, html.into())
+    }
 
-        let buffer = reader.fill_buf()?.to_vec();
+    ///
+    /// 查询消息的投递状态
+    ///
+    /// 参数：
+    /// - message_id: `send` 返回的 `Response.data`，即消息流水号
+    ///
+    /// 返回一个 `NoticeResult<DeliveryStatus>` 枚举
+    /// - 成功：
+    ///     - Ok(DeliveryStatus): 详见 `DeliveryStatus`
+    /// - 失败：
+    ///     - Err(NoticeError::InvalidInput): 消息流水号为空，或未设置 `secretKey`
+    ///     - Err(NoticeError::Auth): 无法获取 `access-key`，或 `access-key` 无效
+    ///     - Err(NoticeError::Parse): 服务器未返回投递状态，如流水号不存在
+    ///     - Err(NoticeError): 其他错误与 `send` 相同
+    ///
+    /// > 注意，需要先以 `with_secret_key` 设置 `secretKey`，用于获取 `access-key`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod sal_notice;
+    /// use sal_notice::{Channel, DeliveryStatus, Notice, Template};
+    ///
+    /// let mut noter = Notice::new("dd1c8a......", Template::TXT, Channel::Wechat);
+    /// noter.with_secret_key("5e2f9a......");
+    /// let res = noter.send("Alert!!! 🚨", "Disk Full".into())?;
+    ///
+    /// match noter.query_status(&res.data)? {
+    ///     DeliveryStatus::Delivered => println!("已送达"),
+    ///     DeliveryStatus::Pending => println!("投递中"),
+    ///     DeliveryStatus::Failed(e) => println!("投递失败: {e}"),
+    /// }
+    /// ```
+    ///
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn query_status(&self, message_id: &str) -> NoticeResult<DeliveryStatus> {
+        if message_id.is_empty() || !message_id.bytes().all(|x| x.is_ascii_alphanumeric()) {
+            return Err(NoticeError::InvalidInput(format!("Invalid Message Id: {message_id}")));
+        };
 
-        let _ = drop(reader);
-        let _ = drop(writer);
-        let _ = drop(stream);
+        let request = format!(
+            "GET /api/open/message/sendMessageResult?shortCode={} HTTP/1.1\r\n\
+            Host: {}\r\n\
+            User-Agent: Mozilla Curl Saloxy\r\n\
+            access-key: {}\r\n\r\n",
+            message_id,
+            self.host.strip_suffix(":80").unwrap_or(&self.host),
+            self.access_key()?
+        );
+        let body = self.exchange(&request)?;
 
-        let buffer = String::from_utf8_lossy(&buffer);
-        let Some(fron) = buffer.find('{') else {
-            return Err(Error::from(ErrorKind::InvalidData));
+        let (res, value) = match Self::handler(&body) {
+            Err(NoticeError::Auth(msg)) => {
+                *self.access_key.lock().unwrap_or_else(PoisonError::into_inner) = None; // 下次查询时重新获取
+                return Err(NoticeError::Auth(msg));
+            },
+            x => x?,
+        };
+
+        let data = value.get("data");
+        match data.and_then(|x| x.get("sendStatus")).and_then(|x| x.as_f64()) {
+            Some(0.0 | 1.0) => Ok(DeliveryStatus::Pending),
+            Some(2.0) => Ok(DeliveryStatus::Delivered),
+            Some(3.0) => {
+                let error = data.and_then(|x| x.get("errorMessage")).and_then(|x| x.as_str());
+                Ok(DeliveryStatus::Failed(error.unwrap_or_default().into()))
+            }
+            _ => Err(NoticeError::Parse(format!("Missing Status: {}", res.msg))),
+        }
+    }
+
+    // 获取开放接口的 `access-key`，过期前复用缓存
+    fn access_key(&self) -> NoticeResult<String> {
+        let Some(secret_key) = &self.secret_key else {
+            return Err(NoticeError::InvalidInput(String::from("SecretKey is Required, See `with_secret_key`!")));
         };
-        let Some(back) = buffer.find('}') else {
-            return Err(Error::from(ErrorKind::InvalidData));
+
+        let mut cache = self.access_key.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((key, expire)) = cache.as_ref() {
+            if Instant::now() < *expire {
+                return Ok(key.clone());
+            };
         };
 
-        Self::handler(&buffer[fron + 1..back])
+        let body = format!(
+            r#"{{"token":{},"secretKey":{}}}"#,
+            Value::String(self.token.to_string()),
+            Value::String(secret_key.to_string())
+        );
+        let body = self.exchange(&self.post("/api/common/openApi/getAccessKey", &body))?;
+
+        let (res, value) = Self::handler(&body)?;
+        let data = value.get("data");
+        match (res.code.as_str(), data.and_then(|x| x.get("accessKey")).and_then(|x| x.as_str())) {
+            ("200", Some(key)) if !key.is_empty() => {
+                let ttl = data.and_then(|x| x.get("expiresIn")).and_then(|x| x.as_f64());
+                let ttl = ttl.filter(|x| *x > 0.0).map_or(ACCESS_KEY_TTL, |x| x as u64);
+                let expire = Instant::now() + Duration::from_secs(ttl.saturating_sub(60).min(ACCESS_KEY_TTL)); // 提前一分钟过期
+                *cache = Some((key.to_string(), expire));
+                Ok(key.to_string())
+            },
+            _ => Err(NoticeError::Auth(format!("Fail to Get Access Key: {}", res.msg))),
+        }
     }
 
-    fn structen<'s>(&self, title: &'s str, content: String) -> String {
-        let content = content.replace('\"', "\\\"");
+    fn send_as(&self, template: &Template, title: &str, content: String) -> NoticeResult<Response> {
+        let request = self.structen(template, title, content);
+        let body = self.exchange(&request)?;
+
+        Self::handler(&body).map(|(res, _)| res)
+    }
+
+    // 发送请求，处理 `429` `401` 状态码，并返回第一个 `{` 到最后一个 `}` 之间的内容
+    fn exchange(&self, request: &str) -> NoticeResult<String> {
+        let buffer = self.transport.lock().unwrap_or_else(PoisonError::into_inner).request(&self.host, request.as_bytes())?;
+
+        let buffer = String::from_utf8_lossy(&buffer);
+        match buffer.get(..12) {
+            Some("HTTP/1.1 429" | "HTTP/1.0 429") => return Err(NoticeError::RateLimited(String::from("Too Many Requests"))),
+            Some("HTTP/1.1 401" | "HTTP/1.0 401") => return Err(NoticeError::Auth(String::from("Unauthorized"))),
+            _ => {},
+        };
+
+        match (buffer.find('{'), buffer.rfind('}')) {
+            (Some(fron), Some(back)) if fron < back => Ok(buffer[fron..=back].to_string()),
+            _ => Err(NoticeError::Parse(String::from("Missing Body!"))),
+        }
+    }
+
+    fn structen(&self, template: &Template, title: &str, content: String) -> String {
+        let title = Value::String(title.into()); // 输出时转义，包括引号与控制字符
+        let content = Value::String(content);
+
+        let to = match &self.to {
+            Some(x) => format!(r#","to":{}"#, Value::String(x.to_string())),
+            None => String::new(),
+        };
+
+        let token = Value::String(self.token.to_string());
+        let template = Value::String(template.to_string()); // `Custom` 可能包含特殊字符
+        let channel = Value::String(self.channel.to_string());
 
         let data_body_json = format!(
-            r#"{{"token":"{}","template":"{}","channel":"{}","title":"{}","content":"{}"}}"#,
-            self.token, self.template, self.channel, title, content
+            r#"{{"token":{},"template":{},"channel":{},"title":{},"content":{}{}}}"#,
+            token, template, channel, title, content, to
         );
 
+        self.post("/send", &data_body_json)
+    }
+
+    // 以 `Value` 解析响应主体，读取 `code` `msg` `data`，并将限流与鉴权失败的错误码转换为对应的错误
+    fn handler(body: &str) -> NoticeResult<(Response, Value)> {
+        let Some(value) = Value::parse(body) else {
+            return Err(NoticeError::Parse(String::from("Invalid Json!")));
+        };
+        let code = value.get("code").and_then(|x| x.as_f64()).map(|x| x.to_string());
+        let msg = value.get("msg").and_then(|x| x.as_str());
+        let (Some(code), Some(msg), Some(data)) = (code, msg, value.get("data")) else {
+            return Err(NoticeError::Parse(String::from("Missing Fields!")));
+        };
+        match code.as_str() {
+            RATE_LIMITED => return Err(NoticeError::RateLimited(msg.into())),
+            AUTH_FAILED => return Err(NoticeError::Auth(msg.into())),
+            _ => {},
+        };
+
+        let data = match data {
+            Value::String(x) => x.clone(),
+            x => x.to_string(), // `null` 或对象等原样保留
+        };
+        Ok((Response { code, msg: msg.into(), data }, value))
+    }
+
+    // 构建以 `JSON` 为主体的 `POST` 请求
+    fn post(&self, path: &str, body: &str) -> String {
         format!(
-            "POST /send HTTP/1.1\r\n\
-            Host: www.pushplus.plus\r\n\
+            "POST {path} HTTP/1.1\r\n\
+            Host: {2}\r\n\
             User-Agent: Mozilla Curl Saloxy\r\n\
             Content-Type: application/json\r\n\
             Content-Length: {1}\r\n\r\n{0}",
-            data_body_json,
-            data_body_json.len()
+            body,
+            body.len(),
+            self.host.strip_suffix(":80").unwrap_or(&self.host) // 省略默认端口
         )
     }
+}
 
-    fn handler(buff: &str) -> Result<Response> {
-        if buff.contains("code") && buff.contains("data") && buff.contains("msg") {
-            let buff = buff.replace(' ', "");
-            let mut code = String::new();
-            let mut msg = String::new();
-            let mut data = String::new();
-
-            for buff in buff.split(",\"") {
-                let buff = buff.replace('\"', "");
-                let Some((key, val)) = buff.split_once(':') else {
-                    return Err(Error::from(ErrorKind::InvalidData));
-                };
-                match key {
-                    "code" => code = val.to_string(),
-                    "msg" => msg = val.to_string(),
-                    "data" => data = val.to_string(),
-                    _ => {}
-                };
+// 仅能清除自身持有的 `token`，借用的字符串由调用者负责
+impl Drop for Notice<'_> {
+    fn drop(&mut self) {
+        for secret in [Some(&mut self.token), self.secret_key.as_mut()].into_iter().flatten() {
+            if let Cow::Owned(secret) = secret {
+                let mut secret = std::mem::take(secret).into_bytes();
+                secret.fill(0);
+                std::hint::black_box(&mut secret); // 避免清零被优化掉
             }
+        }
+    }
+}
+
+impl<'a> NoticeBuilder<'a> {
+    ///
+    /// 设置 PushPlus 的 token，可以为 `&str` 或 `String`
+    ///
+    pub fn token<T: Into<Cow<'a, str>>>(mut self, token: T) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    ///
+    /// 设置模板
+    ///
+    pub fn template(mut self, template: Template) -> Self {
+        self.template = template;
+        self
+    }
+
+    ///
+    /// 设置渠道
+    ///
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    ///
+    /// 设置开放接口的 `secretKey`，见 `Notice::with_secret_key`
+    ///
+    pub fn secret_key<T: Into<Cow<'a, str>>>(mut self, secret_key: T) -> Self {
+        self.secret_key = Some(secret_key.into());
+        self
+    }
+
+    ///
+    /// 构建 `Notice` 实例
+    ///
+    /// 返回一个 `NoticeResult<Notice>` 枚举
+    /// - 成功：
+    ///     - Ok(Notice)
+    /// - 失败：
+    ///     - Err(NoticeError::InvalidInput): 未设置 `token`
+    ///
+    pub fn build(self) -> NoticeResult<Notice<'a>> {
+        let Some(token) = self.token else {
+            return Err(NoticeError::InvalidInput(String::from("Token is Required!")));
+        };
+
+        Ok(Notice {
+            token,
+            template: self.template,
+            channel: self.channel,
+            host: HOST.into(),
+            to: None,
+            secret_key: self.secret_key,
+            access_key: Mutex::new(None),
+            transport: Mutex::new(Box::new(TcpTransport::default())),
+        })
+    }
+}
+
+impl fmt::Display for NoticeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RateLimited(x) => write!(f, "Rate Limited: {x}"),
+            Self::Auth(x) => write!(f, "Auth Failed: {x}"),
+            Self::Parse(x) => write!(f, "Parse Error: {x}"),
+            Self::Transport(x) => write!(f, "Transport Error: {x}"),
+            Self::InvalidInput(x) => write!(f, "Invalid Input: {x}"),
+        }
+    }
+}
 
-            Ok(Response { code, msg, data })
-        } else {
-            Err(Error::from(ErrorKind::InvalidData))
+impl std::error::Error for NoticeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(x) => Some(x),
+            _ => None,
         }
     }
 }
 
+impl From<Error> for NoticeError {
+    fn from(e: Error) -> NoticeError {
+        NoticeError::Transport(e)
+    }
+}
+
+impl From<NoticeError> for Error {
+    fn from(e: NoticeError) -> Error {
+        let kind = match e {
+            NoticeError::Transport(x) => return x,
+            NoticeError::RateLimited(_) => ErrorKind::Other,
+            NoticeError::Auth(_) => ErrorKind::PermissionDenied,
+            NoticeError::Parse(_) => ErrorKind::InvalidData,
+            NoticeError::InvalidInput(_) => ErrorKind::InvalidInput,
+        };
+
+        Error::new(kind, e.to_string())
+    }
+}
+
 impl fmt::Display for Response {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -225,10 +817,11 @@ impl fmt::Display for Response {
 impl fmt::Display for Template {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match self {
-            &Self::HTML => "html",
-            &Self::TXT => "txt",
-            &Self::JSON => "json",
-            &Self::MD => "markdown",
+            Self::HTML => "html",
+            Self::TXT => "txt",
+            Self::JSON => "json",
+            Self::MD => "markdown",
+            Self::Custom(x) => x,
         })
     }
 }
@@ -236,8 +829,335 @@ impl fmt::Display for Template {
 impl fmt::Display for Channel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match self {
-            &Self::Wechat => "wechat",
-            &Self::Email => "mail",
+            Self::Wechat => "wechat",
+            Self::Email => "mail",
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mock_server::MockServer;
+    use std::sync::{Arc, Mutex};
+
+    // 按顺序返回预设的响应，并记录收到的请求
+    #[derive(Clone, Default)]
+    struct Canned {
+        responses: Arc<Mutex<Vec<String>>>,
+        requests: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    impl Transport for Canned {
+        fn request(&mut self, host: &str, raw: &[u8]) -> Result<Vec<u8>> {
+            self.requests.lock().unwrap().push((host.into(), String::from_utf8_lossy(raw).into()));
+            let mut responses = self.responses.lock().unwrap();
+            match responses.is_empty() {
+                true => Err(Error::new(ErrorKind::ConnectionReset, "No Response!")),
+                false => Ok(responses.remove(0).into_bytes()),
+            }
+        }
+    }
+
+    fn json_response(body: &str) -> String {
+        format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    fn noter(responses: &[&str]) -> (Notice<'static>, Canned) {
+        let canned = Canned::default();
+        canned.responses.lock().unwrap().extend(responses.iter().map(|x| json_response(x)));
+
+        let mut noter = Notice::new("dd1c8a", Template::TXT, Channel::Wechat);
+        noter.set_transport(Box::new(canned.clone()));
+        (noter, canned)
+    }
+
+    #[test]
+    fn query_status_states() {
+        let (mut noter, canned) = noter(&[
+            r#"{"code":200,"msg":"请求成功","data":{"accessKey":"ak-9f3e","expiresIn":7200}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":0}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":1}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":2}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":3,"errorMessage":"用户未关注"}}"#,
+            r#"{"code":999,"msg":"流水号不存在","data":null}"#,
+        ]);
+        noter.with_secret_key("5e2f9a");
+
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Pending);
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Pending);
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Delivered);
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Failed("用户未关注".into()));
+        assert!(matches!(noter.query_status("a1b2"), Err(NoticeError::Parse(_))));
+
+        let requests = canned.requests.lock().unwrap();
+        assert_eq!(requests.len(), 6); // `access-key` 只获取一次
+        let (_, request) = &requests[1];
+        assert!(request.starts_with("GET /api/open/message/sendMessageResult?shortCode=a1b2 HTTP/1.1\r\n"));
+        assert!(request.contains("\r\naccess-key: ak-9f3e\r\n"));
+    }
+
+    #[test]
+    fn query_status_uses_access_key() {
+        let (mut noter, canned) = noter(&[
+            r#"{"code":200,"msg":"请求成功","data":{"accessKey":"ak-9f3e","expiresIn":7200}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":2}}"#,
+            r#"{"code":903,"msg":"无效的访问令牌","data":null}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"accessKey":"ak-0c71"}}"#,
+            r#"{"code":200,"msg":"请求成功","data":{"sendStatus":2}}"#,
+        ]);
+        assert!(matches!(noter.query_status("a1b2"), Err(NoticeError::InvalidInput(_)))); // 未设置 `secretKey`
+        assert!(canned.requests.lock().unwrap().is_empty());
+
+        noter.with_secret_key("5e\"2f");
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Delivered);
+        assert!(matches!(noter.query_status("a1b2"), Err(NoticeError::Auth(_))));
+        assert_eq!(noter.query_status("a1b2").unwrap(), DeliveryStatus::Delivered); // 鉴权失败后重新获取
+
+        let requests = canned.requests.lock().unwrap();
+        let (_, request) = &requests[0];
+        assert!(request.starts_with("POST /api/common/openApi/getAccessKey HTTP/1.1\r\n"), "{request}");
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let value = Value::parse(body).unwrap();
+        assert_eq!(value.get("token").and_then(|x| x.as_str()), Some("dd1c8a"));
+        assert_eq!(value.get("secretKey").and_then(|x| x.as_str()), Some("5e\"2f"));
+
+        for (index, key) in [(1, "ak-9f3e"), (2, "ak-9f3e"), (4, "ak-0c71")] {
+            let (_, request) = &requests[index];
+            assert!(request.starts_with("GET /api/open/message/sendMessageResult?shortCode=a1b2 "), "{request}");
+            assert!(request.contains(&format!("\r\naccess-key: {key}\r\n")), "{request}");
+            assert!(!request.contains("dd1c8a"), "{request}");
+        }
+        assert!(requests[3].1.starts_with("POST /api/common/openApi/getAccessKey "));
+    }
+
+    #[test]
+    fn structen_escapes_title_and_content() {
+        let (noter, _) = noter(&[]);
+        let request = noter.structen(&Template::TXT, "a\"b\n\u{1}", "c\\d\te".into());
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+
+        assert!(body.contains(r#""title":"a\"b\n\u0001""#));
+        assert!(body.contains(r#""content":"c\\d\te""#));
+        let value = Value::parse(body).unwrap();
+        assert_eq!(value.get("title").and_then(|x| x.as_str()), Some("a\"b\n\u{1}"));
+    }
+
+    #[test]
+    fn with_to_escapes_control_characters() {
+        let (mut noter, _) = noter(&[]);
+        let request = noter.structen(&Template::TXT, "t", "c".into());
+        assert!(!request.contains(r#""to""#)); // 未设置时不出现
+
+        noter.with_to("a\"b\\c\n\t\u{0}");
+        let request = noter.structen(&Template::Custom("x\"y".into()), "t", "c".into());
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+
+        assert!(body.ends_with(r#","to":"a\"b\\c\n\t\u0000"}"#), "{body}");
+        let value = Value::parse(body).unwrap();
+        assert_eq!(value.get("to").and_then(|x| x.as_str()), Some("a\"b\\c\n\t\u{0}"));
+        assert_eq!(value.get("template").and_then(|x| x.as_str()), Some("x\"y"));
+    }
+
+    #[test]
+    fn tcp_headers_and_body_apart() {
+        let server = MockServer::new();
+        let body = r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#;
+        server.push_parts([
+            format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n", body.len()),
+            body[..10].to_string(),
+            body[10..].to_string(),
+        ]);
+
+        let mut noter = Notice::new("dd1c8a", Template::TXT, Channel::Wechat);
+        noter.set_transport(Box::new(TcpTransport::with_timeout(Duration::from_secs(5))));
+        noter.host = server.host();
+
+        let res = noter.send("t", "c".into()).unwrap();
+        assert_eq!((res.code.as_str(), res.data.as_str()), ("200", "a1b2"));
+        assert!(server.last_request().unwrap().starts_with(b"POST /send HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn send_error_variants() {
+        let (noter, canned) = noter(&[
+            r#"{"code":900,"msg":"用户账号使用受限","data":null}"#,
+            r#"{"code":903,"msg":"无效的用户令牌","data":null}"#,
+        ]);
+        canned.responses.lock().unwrap().extend([
+            String::from("HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\n\r\n"),
+            String::from("HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n"),
+            String::from("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+        ]);
+
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::RateLimited(x)) if x == "用户账号使用受限"));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::Auth(x)) if x == "无效的用户令牌"));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::RateLimited(_))));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::Auth(_))));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::Parse(_))));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::Transport(e)) if e.kind() == ErrorKind::ConnectionReset));
+    }
+
+    #[test]
+    fn typed_helpers_template() {
+        let ok = r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#;
+        let (noter, canned) = noter(&[ok, ok, ok]);
+        noter.send_markdown("t", "# md").unwrap();
+        noter.send_html("t", "<b>html</b>").unwrap();
+        noter.send("t", "txt".into()).unwrap();
+
+        let templates: Vec<_> = canned.requests.lock().unwrap().iter().map(|(_, request)| {
+            let (_, body) = request.split_once("\r\n\r\n").unwrap();
+            let value = Value::parse(body).unwrap();
+            value.get("template").and_then(|x| x.as_str()).map(String::from)
+        }).collect();
+        assert_eq!(templates, [Some("markdown".into()), Some("html".into()), Some("txt".into())]);
+    }
+
+    #[test]
+    fn custom_template() {
+        let (noter, _) = noter(&[]);
+        let request = noter.structen(&Template::Custom("foo".into()), "t", "c".into());
+        assert!(request.contains(r#","template":"foo""#), "{request}");
+        assert_eq!(Template::Custom("foo".into()).to_string(), "foo");
+    }
+
+    #[test]
+    fn query_status_invalid_id() {
+        let (noter, canned) = noter(&[]);
+        assert!(matches!(noter.query_status(""), Err(NoticeError::InvalidInput(_))));
+        assert!(matches!(noter.query_status("a1&b=2"), Err(NoticeError::InvalidInput(_))));
+        assert!(canned.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn send_through_transport() {
+        let (noter, canned) = noter(&[
+            r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#,
+            r#"{"code":999,"msg":"服务端验证错误","data":null}"#,
+            r#"{"code":200,"data":"a1b2"}"#,
+        ]);
+
+        let res = noter.send("标题", "内容".into()).unwrap();
+        assert_eq!((res.code.as_str(), res.msg.as_str(), res.data.as_str()), ("200", "请求成功", "a1b2"));
+        let res = noter.send("t", "c".into()).unwrap(); // 其他错误码原样返回
+        assert_eq!((res.code.as_str(), res.msg.as_str()), ("999", "服务端验证错误"));
+        assert!(matches!(noter.send("t", "c".into()), Err(NoticeError::Parse(_))));
+
+        let (host, request) = &canned.requests.lock().unwrap()[0];
+        assert_eq!(host, HOST);
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /send HTTP/1.1\r\nHost: www.pushplus.plus\r\n"), "{head}");
+        assert!(head.ends_with(&format!("\r\nContent-Length: {}", body.len())));
+        let value = Value::parse(body).unwrap();
+        let field = |key| value.get(key).and_then(|x| x.as_str()).map(String::from);
+        assert_eq!(field("token").as_deref(), Some("dd1c8a"));
+        assert_eq!((field("title").as_deref(), field("content").as_deref()), (Some("标题"), Some("内容")));
+        assert_eq!((field("template").as_deref(), field("channel").as_deref()), (Some("txt"), Some("wechat")));
+    }
+
+    #[test]
+    fn rate_limit_classified() {
+        let (mut noter, canned) = noter(&[
+            r#"{"code":200,"msg":"请求成功","data":{"accessKey":"ak-9f3e"}}"#,
+            r#"{"code":900,"msg":"用户账号使用受限","data":null}"#,
+        ]);
+        canned.responses.lock().unwrap().push(String::from("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 60\r\n\r\n"));
+        noter.with_secret_key("5e2f9a");
+
+        assert!(matches!(noter.query_status("a1b2"), Err(NoticeError::RateLimited(x)) if x == "用户账号使用受限"));
+        assert!(matches!(noter.query_status("a1b2"), Err(NoticeError::RateLimited(_))));
+        assert_eq!(NoticeError::RateLimited("x".into()).to_string(), "Rate Limited: x");
+    }
+
+    #[test]
+    fn builder_owned_token() {
+        let token = String::from("dd1c8a");
+        let mut noter = Notice::builder().token(token).template(Template::MD).channel(Channel::Email).build().unwrap();
+        assert!(matches!(noter.token, Cow::Owned(ref x) if x == "dd1c8a"));
+
+        let canned = Canned::default();
+        canned.responses.lock().unwrap().push(json_response(r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#));
+        noter.set_transport(Box::new(canned.clone()));
+        noter.send("t", "c".into()).unwrap();
+        let (_, request) = &canned.requests.lock().unwrap()[0];
+        assert!(request.contains(r#""token":"dd1c8a","template":"markdown","channel":"mail""#), "{request}");
+
+        assert!(matches!(Notice::builder().build(), Err(NoticeError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn custom_host_target() {
+        let ok = r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#;
+        let (mut noter, canned) = noter(&[ok, ok]);
+
+        noter.with_host("push.example.com:8080").unwrap();
+        noter.send("t", "c".into()).unwrap();
+        noter.with_host("push.example.com").unwrap();
+        noter.send("t", "c".into()).unwrap();
+
+        let requests = canned.requests.lock().unwrap();
+        let hosts: Vec<(&str, &str)> = requests.iter().map(|(host, raw)| {
+            let header = raw.lines().find_map(|x| x.strip_prefix("Host: ")).unwrap();
+            (host.as_str(), header)
+        }).collect();
+        assert_eq!(hosts, [
+            ("push.example.com:8080", "push.example.com:8080"),
+            ("push.example.com:80", "push.example.com"), // 省略默认端口
+        ]);
+
+        assert!(matches!(noter.with_host("push.example.com:http"), Err(NoticeError::InvalidInput(_))));
+        assert!(matches!(noter.with_host(""), Err(NoticeError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let response = r#"{"code":200,"msg":"请求成功","data":"a1b2"}"#;
+        let (noter, canned) = noter(&[response; 8]);
+
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| assert_eq!(noter.send("t", "c".into()).unwrap().data, "a1b2"));
+            }
+        });
+        assert_eq!(canned.requests.lock().unwrap().len(), 8);
+    }
+
+    #[test]
+    fn send_body_with_braces() {
+        let (noter, _) = noter(&[
+            r#"{"code":200,"msg":"请求{成功}","data":"a}1,\"b2"}"#,
+            r#"{"data":{"id":"a1b2"},"msg":"ok","code":200}"#,
+        ]);
+
+        let res = noter.send("t", "c".into()).unwrap();
+        assert_eq!((res.code.as_str(), res.msg.as_str(), res.data.as_str()), ("200", "请求{成功}", "a}1,\"b2"));
+        let res = noter.send("t", "c".into()).unwrap(); // 字段顺序与嵌套对象不影响解析
+        assert_eq!((res.code.as_str(), res.msg.as_str(), res.data.as_str()), ("200", "ok", r#"{"id":"a1b2"}"#));
+    }
+
+    #[test]
+    fn handler_variants() {
+        let handler = |body| Notice::handler(body).map(|(res, _)| (res.code, res.msg, res.data));
+        assert!(matches!(handler(r#"{"code":900,"msg":"受限","data":null}"#), Err(NoticeError::RateLimited(x)) if x == "受限"));
+        assert!(matches!(handler(r#"{"code":903,"msg":"无效","data":null}"#), Err(NoticeError::Auth(x)) if x == "无效"));
+        assert!(matches!(handler(r#"{"code":200,"msg":"ok"}"#), Err(NoticeError::Parse(_))));
+        assert!(matches!(handler(r#"{"code":"200","msg":"ok","data":""}"#), Err(NoticeError::Parse(_))));
+        assert!(matches!(handler(r#"{"code":200,"msg":"ok","data":"a", "#), Err(NoticeError::Parse(_))));
+
+        let res = handler(r#"{ "code" : 999 , "msg" : "a, \"b\": c" , "data" : [1, {"x": "y"}] }"#).ok();
+        assert_eq!(res, Some(("999".into(), r#"a, "b": c"#.into(), r#"[1,{"x":"y"}]"#.into())));
+    }
+
+    #[test]
+    fn structen_escapes_token() {
+        let noter = Notice::new("dd\"1c\\8a\n", Template::TXT, Channel::Wechat);
+        let request = noter.structen(&Template::TXT, "t", "c".into());
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+
+        assert!(body.starts_with(r#"{"token":"dd\"1c\\8a\n","#), "{body}");
+        let value = Value::parse(body).unwrap();
+        assert_eq!(value.get("token").and_then(|x| x.as_str()), Some("dd\"1c\\8a\n"));
+        assert_eq!(value.get("channel").and_then(|x| x.as_str()), Some("wechat"));
+    }
+}
@@ -2,21 +2,49 @@
 //! 这是一个简易的略有性能的轻量级服务器
 //!
 
+mod crypto;
+mod httpline;
+pub mod mime;
+#[cfg(any(test, feature = "test-utils"))] // 测试用的模拟服务器
+pub mod mock_server;
 mod thread_limit;
+mod websocket;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::net::{TcpListener, TcpStream, ToSocketAddrs};
-use std::panic::UnwindSafe;
+use std::fmt;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use self::thread_limit::ThreadLimit;
 
+pub use self::websocket::{Message, WsStream};
+
+type Upgrade = Arc<dyn Fn(WsStream) + Send + Sync + RefUnwindSafe>;
+type Handler = Box<dyn Fn(Request) -> Result<Response, HttpError> + Send + Sync + RefUnwindSafe>;
+
+const MAX_CONTINUE: usize = 16 * 1024 * 1024; // `100-continue` 请求主体的最大长度
+const SERVER: &str = "SalServer"; // 默认的 `Server` 头部
+const READ_TIMEOUT: Duration = Duration::from_secs(30); // 请求开始后读取头部与主体的超时
+static REQUEST_ID: AtomicU64 = AtomicU64::new(0); // 生成请求ID的计数器
+
 ///
 /// 服务器实例结构体
 ///
 /// 用于储存 **线程（thread）** 和 **监听（listener）** 信息
 ///
 /// - thread: ThreadLimit
-/// - listener: TcpListener
+/// - listener: TcpListener 或 UnixListener (仅 `unix`)
+/// - guard: 请求检查 (限流、认证) 及 `Server` 头部，作用于 `route_http` `route_result` `route_router` 与 `route_stream`
+/// - upgrades: `WebSocket` 路径及处理函数，作用于 `route_result` 与 `route_router`
+///
+/// > 注意，不支持作为代理使用：`CONNECT` 请求将返回 `405`，
+/// > absolute-form 的请求仅使用其路径部分，不会转发至目标主机
 ///
 /// **Example:**
 /// ```
@@ -26,248 +54,1817 @@ use self::thread_limit::ThreadLimit;
 ///
 pub struct SalServer {
     thread: ThreadLimit,
-    listener: TcpListener,
+    listener: Listener,
+    guard: Guard,
+    upgrades: Arc<Vec<(String, Upgrade)>>,
 }
 
-impl SalServer {
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf), // 关闭时删除套接字文件
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+#[derive(Clone)]
+struct Guard {
+    trust_proxy: bool,
+    limiter: Option<Arc<Mutex<Limiter>>>,
+    auth: Option<Arc<str>>, // user:pass
+    server: Option<Arc<str>>, // `Server` 头部，默认为 `SERVER`
+    idle: Option<Duration>,   // 等待请求的空闲超时
+    active: Option<Duration>, // 请求开始后的读取超时
+}
+
+impl Default for Guard {
+    fn default() -> Self {
+        Guard { trust_proxy: false, limiter: None, auth: None, server: None, idle: None, active: Some(READ_TIMEOUT) }
+    }
+}
+
+struct Limiter {
+    rate: f64,
+    burst: f64,
+    buckets: HashMap<IpAddr, (f64, Instant)>, // ip: (tokens, last)
+}
+
+///
+/// 请求数据结构体
+///
+/// 用于储存 **解析后的HTTP请求**，数据借用自原始请求
+///
+/// - method: 请求方法
+/// - path: 请求路径，absolute-form (`http://host/files` `http://host?x=1`) 将被转换为 `/files` `/?x=1`
+/// - version: HTTP版本，如 `HTTP/1.1`
+/// - head: 请求头部 (Header)，含有已废弃的多行头部 (obs-fold) 的请求将返回 `400`
+/// - body: 请求主体，原样保留，`chunked` 主体及其 trailer 不会被解码
+/// - peer: 客户端地址，无法获取时为 `0.0.0.0:0`
+/// - params: 路径参数，由 `Router` 填充，如 `/users/:id` 中的 `id`
+///
+/// 每个请求带有一个请求ID，见 `request_id`
+///
+pub struct Request<'r> {
+    pub method: &'r str,
+    pub path: &'r str,
+    pub version: &'r str,
+    pub head: HashMap<&'r str, &'r str>,
+    pub body: &'r str,
+    pub peer: SocketAddr,
+    pub params: HashMap<String, &'r str>,
+    id: Cow<'r, str>,
+}
+
+///
+/// 应答数据结构体
+///
+/// 用于构建 **HTTP应答**，由服务器自动添加 `Content-Length` 与 `Connection`，
+/// 以及未设置时的 `Date` 与 `Server`
+///
+/// 主体为空且已设置 `Content-Length` 时保留该值，用于应答 `HEAD` 请求
+///
+/// - status: 状态码
+/// - head: 应答头部 (Header)
+/// - body: 应答主体
+/// - keep_alive: 是否保持持续连接 (`Keep-Alive`)
+/// - file: 作为应答主体的文件，见 `Response::file`，仅作用于 `route_result` 与 `route_router`
+///
+/// **Example:**
+/// ```
+/// mod salfa_server;
+/// use salfa_server::Response;
+///
+/// let res = Response::new(200)
+///     .header("Content-Type", "text/plain; charset=utf-8")
+///     .body("Hello World");
+/// ```
+///
+pub struct Response {
+    pub status: u16,
+    pub head: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub keep_alive: bool,
+    pub file: Option<PathBuf>,
+}
+
+///
+/// 错误数据结构体
+///
+/// 路由函数返回 `Err(HttpError)` 时，服务器自动将其转换为对应状态码的应答
+///
+/// - status: 状态码，应为 `4xx` 或 `5xx`
+/// - msg: 错误信息，作为应答主体
+///
+/// 实现了 `From<std::io::Error>`，可在路由函数中直接使用 `?` (500)
+///
+pub struct HttpError {
+    pub status: u16,
+    pub msg: String,
+}
+
+///
+/// 分块应答结构体
+///
+/// 由 `route_stream` 提供给路由函数，用于在不知道主体总长度时逐块写出应答
+///
+/// - 使用 `Transfer-Encoding: chunked` 写出，`HTTP/1.0` 请求则直接写出数据
+/// - 写出第一个分块时发送状态行与头部，之后不能再修改
+/// - 应答结束后关闭连接
+///
+/// **Example:**
+/// ```
+/// mod salfa_server;
+/// use salfa_server::{Request, ResponseWriter, SalServer};
+///
+/// let server = SalServer::new("127.0.0.1:4998", 16);
+/// server.route_stream(|_request: Request, writer: &mut ResponseWriter| {
+///     writer.header("Content-Type", "text/plain; charset=utf-8");
+///     for x in 0..3 {
+///         writer.write_chunk(format!("Chunk {x}\n").as_bytes())?;
+///     };
+///     Ok(writer.finish()?)
+/// });
+/// ```
+///
+pub struct ResponseWriter<'w> {
+    writer: &'w mut dyn Write,
+    res: Response, // 状态码与头部，不使用主体
+    version: &'static str,
+    server: String,
+    id: String, // 请求ID，开始写出时添加为 `X-Request-Id`
+    chunked: bool,
+    started: bool,
+    finished: bool,
+}
+
+///
+/// 路由表结构体
+///
+/// 按请求方法与路径分发请求，路径中以 `:` 开头的段为路径参数，
+/// 以 `*` 开头的段为通配段，捕获剩余的全部路径
+///
+/// 多个路由同时匹配时，从左至右逐段比较：精确段 > 参数段 > 通配段，
+/// 优先级相同时按注册顺序
+///
+/// 未注册 `HEAD` 的路径将使用 `GET` 路由应答，并移除主体，保留 `Content-Length`
+///
+/// - routes: 已注册的路由 (method, path, handler)
+/// - strict: 是否严格区分路径末尾的 `/`，默认为 `false`
+///
+/// **Example:**
+/// ```
+/// mod salfa_server;
+/// use salfa_server::{Response, Router, SalServer};
+///
+/// let router = Router::new()
+///     .route("GET", "/", |_| Ok(Response::new(200).body("Index")))
+///     .route("GET", "/users/:id", |request| {
+///         let id = request.param("id").unwrap_or_default();
+///         Ok(Response::new(200).body(format!("User {id}")))
+///     })
+///     .route("GET", "/static/*path", |request| {
+///         let path = request.param("path").unwrap_or_default(); // `a/b/c`
+///         Ok(Response::new(200).body(path.to_string()))
+///     });
+///
+/// let server = SalServer::new("127.0.0.1:4998", 16);
+/// server.route_router(router);
+/// ```
+///
+pub struct Router {
+    routes: Vec<(String, String, Handler)>,
+    strict: bool,
+}
+
+impl<'r> Request<'r> {
+    fn parse(buffer: &'r str, peer: SocketAddr) -> Option<Request<'r>> {
+        let (headers, body) = buffer.split_once("\r\n\r\n")?;
+
+        let mut headers = headers.lines();
+        let (method, path, version) = httpline::parse_request_line(headers.next()?)?;
+        let (path, version) = (Self::origin_form(path), version.as_str());
+
+        let mut head = HashMap::new();
+        for header in headers {
+            if header.starts_with([' ', '\t']) {
+                return None; // 已废弃的多行头部 (obs-fold)，拒绝而非误解析为新的头部
+            };
+            if let Some(place) = header.find(':') {
+                let key = header[..place].trim();
+                let value = header[place+1..].trim();
+                head.insert(key, value);
+            };
+        };
+
+        let id = match head.iter().find(|(k, _)| k.eq_ignore_ascii_case("X-Request-Id")) {
+            Some((_, x)) if !x.is_empty() && x.len() <= 128 && x.bytes().all(|x| x.is_ascii_graphic()) => Cow::Borrowed(*x),
+            _ => Cow::Owned(Self::new_id()),
+        };
+
+        Some(Request { method, path, version, head, body, peer, params: HashMap::new(), id })
+    }
+
+    // 转换为文本，absolute-form 仅有主机与查询时 (`http://host?x=1`)，在查询前补全 `/`
+    fn text(buffer: &[u8]) -> Cow<'_, str> {
+        let text = String::from_utf8_lossy(buffer);
+        let Some(start) = text.find(' ').map(|x| x + 1) else {
+            return text;
+        };
+
+        let target = &text[start..];
+        let target = &target[..target.find([' ', '\r', '\n']).unwrap_or(target.len())];
+        let scheme = ["http://", "https://"].into_iter()
+            .find(|x| target.get(..x.len()).is_some_and(|p| p.eq_ignore_ascii_case(x)));
+        let Some(scheme) = scheme else {
+            return text;
+        };
+
+        let rest = &target[scheme.len()..];
+        match rest.find(['/', '?', '#']) {
+            Some(place) if rest[place..].starts_with('?') => {
+                let place = start + scheme.len() + place;
+                let mut text = text.into_owned();
+                text.insert(place, '/');
+                Cow::Owned(text)
+            },
+            _ => text,
+        }
+    }
+
+    // 将代理使用的 absolute-form (`http://host/files`) 转换为路径部分 (`/files`)
+    fn origin_form(path: &str) -> &str {
+        let scheme = ["http://", "https://"].into_iter()
+            .find(|x| path.get(..x.len()).is_some_and(|p| p.eq_ignore_ascii_case(x)));
+        let Some(scheme) = scheme else {
+            return path;
+        };
+
+        let rest = &path[scheme.len()..];
+        match rest.find('/') {
+            Some(place) if !rest[..place].contains(['?', '#']) => &rest[place..],
+            _ => "/", // 仅有主机，如 `http://host`，带有查询时已由 `text` 补全
+        }
+    }
 
     ///
-    /// 创建一个新的 `SalServer` 实例
+    /// 获取路径参数，仅在通过 `Router` 分发时存在
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::{HttpError, Request, Response};
+    ///
+    /// // 注册为 `/users/:id`
+    /// fn user(request: Request) -> Result<Response, HttpError> {
+    ///     let id = request.param("id").ok_or_else(HttpError::not_found)?;
+    ///     Ok(Response::new(200).body(id))
+    /// }
+    /// ```
+    ///
+    pub fn param(&self, key: &str) -> Option<&'r str> {
+        self.params.get(key).copied()
+    }
+
+    ///
+    /// 获取请求ID，用于在日志中关联同一请求的各个阶段
+    ///
+    /// - 请求带有合法的 `X-Request-Id` 头部时，沿用该值
+    /// - 否则由服务器生成，如 `6713a0f2-1f`：当前的Unix时间 (秒) 与递增的序号，均为十六进制
+    ///
+    /// `route_result` `route_router` 与 `route_stream` 的应答中，
+    /// 将以 `X-Request-Id` 头部返回该值 (处理函数已设置时除外)
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::{HttpError, Request, Response};
+    ///
+    /// fn route(request: Request) -> Result<Response, HttpError> {
+    ///     eprintln!("[{}] {} {}", request.request_id(), request.method, request.path);
+    ///     Ok(Response::new(200))
+    /// }
+    /// ```
+    ///
+    pub fn request_id(&self) -> &str {
+        &self.id
+    }
+
+    fn new_id() -> String {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+        format!("{secs:x}-{:x}", REQUEST_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    ///
+    /// 获取客户端的IP地址
     ///
     /// 参数：
-    /// - bind_path: 绑定地址，如：127.0.0.1:8888
-    /// - thread: 线程数量。注意不能为0，否则将***无限期阻塞***
+    /// - trust_proxy: 是否信任 `X-Forwarded-For` 头部
+    ///     - true => 使用 `X-Forwarded-For` 最左侧的地址，仅在反向代理之后使用
+    ///     - false => 使用 `peer` 地址
     ///
-    /// 返回一个新的 `SalServer` 结构体
+    /// 返回一个 `IpAddr`，头部不存在或不是合法的IP地址时，返回 `peer` 地址
     ///
     /// **Example:**
     /// ```
     /// mod salfa_server;
-    /// use salfa_server::SalServer;
+    /// use salfa_server::{HttpError, Request, Response};
     ///
-    /// let server = SalServer::new("0.0.0.0:8888", 16);
+    /// fn route(request: Request) -> Result<Response, HttpError> {
+    ///     println!("{} {}", request.client_ip(true), request.path);
+    ///     Ok(Response::new(204))
+    /// }
     /// ```
     ///
-    pub fn new<T: ToSocketAddrs>(bind_path: T, thread: usize) -> SalServer {
-        let thread = ThreadLimit::new(thread);
-        let listener = TcpListener::bind(bind_path).expect("Error: Couldn't bind port!");
-        SalServer { thread, listener }
+    pub fn client_ip(&self, trust_proxy: bool) -> IpAddr {
+        if trust_proxy {
+            let forwarded = self.head.iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("X-Forwarded-For"))
+                .and_then(|(_, v)| v.split(',').next())
+                .and_then(|x| x.trim().parse::<IpAddr>().ok());
+
+            if let Some(ip) = forwarded {
+                return ip;
+            };
+        };
+
+        self.peer.ip()
+    }
+
+    // `WebSocket` 握手请求的 `Sec-WebSocket-Key` 与 `Sec-WebSocket-Version`
+    fn websocket_key(&self) -> Option<(&'r str, Option<&'r str>)> {
+        let header = |key: &str| self.head.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| *v);
+
+        let upgrade = header("Upgrade").is_some_and(|x| x.eq_ignore_ascii_case("websocket"));
+        if self.method != "GET" || !upgrade {
+            return None;
+        };
+
+        header("Sec-WebSocket-Key").map(|x| (x, header("Sec-WebSocket-Version")))
+    }
+
+    // 应答所使用的HTTP版本，与请求保持一致
+    fn reply_version(&self) -> &'static str {
+        if self.version == "HTTP/1.0" { "HTTP/1.0" } else { "HTTP/1.1" }
     }
 
+    // 客户端是否允许持续连接：`HTTP/1.0` 默认关闭，`HTTP/1.1` 默认开启
+    fn persistent(&self) -> bool {
+        let connection = self.head.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("Connection"))
+            .map(|(_, v)| v.to_ascii_lowercase());
+
+        match connection.as_deref() {
+            Some("close") => false,
+            Some("keep-alive") => true,
+            _ => self.version != "HTTP/1.0",
+        }
+    }
+}
+
+impl Response {
+
     ///
-    /// 为服务提供路由，并提供服务（原始方法）
+    /// 创建一个新的 `Response` 实例
     ///
     /// 参数：
-    /// - route: 路由函数
+    /// - status: 状态码
     ///
-    /// 使用该方法，需要定义一个特殊函数：
+    pub fn new(status: u16) -> Response {
+        Response { status, head: Vec::new(), body: Vec::new(), keep_alive: false, file: None }
+    }
+
+    ///
+    /// 创建一个以文件作为主体的 `Response` 实例，状态码为 `200`
+    ///
+    /// 参数：
+    /// - path: 文件路径
+    ///
+    /// 服务器在写出应答时打开文件并流式发送，不会将其读入内存
+    /// - 未设置 `Content-Type` 时，根据扩展名推断，见 `mime::from_path`
+    /// - `Content-Length` 为文件大小
+    /// - 文件不存在或不是普通文件时，返回 `404 Not Found`
+    /// - `HEAD` 请求仅返回头部
+    ///
+    /// **Example:**
     /// ```
-    /// fn route(buffer: Vec<u8>) -> (Vec<u8>, bool) {}
+    /// mod salfa_server;
+    /// use salfa_server::{Response, Router, SalServer};
+    ///
+    /// let router = Router::new()
+    ///     .route("GET", "/download/:name", |request| {
+    ///         let name = request.param("name").unwrap_or_default();
+    ///         Ok(Response::file(format!("/srv/files/{}.zip", name.replace("..", ""))))
+    ///     });
+    ///
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// server.route_router(router);
     /// ```
+    ///
+    pub fn file<P: AsRef<Path>>(path: P) -> Response {
+        Response { file: Some(path.as_ref().to_path_buf()), ..Response::new(200) }
+    }
+
+    ///
+    /// 添加一个应答头部
+    ///
+    pub fn header(mut self, key: &str, value: &str) -> Response {
+        self.head.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    ///
+    /// 设置应答主体
+    ///
+    pub fn body<T: Into<Vec<u8>>>(mut self, body: T) -> Response {
+        self.body = body.into();
+        self
+    }
+
+    ///
+    /// 设置是否保持持续连接
+    ///
+    pub fn keep_alive(mut self, keep_alive: bool) -> Response {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    fn to_bytes(&self, version: &str, server: &str) -> Vec<u8> {
+        let mut buf = self.head_line(version, server);
+        let length = self.head.iter().any(|(k, _)| k.eq_ignore_ascii_case("Content-Length"));
+        if !(length && self.body.is_empty()) {
+            buf.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        };
+        buf.push_str(if self.keep_alive { "Connection: keep-alive\r\n\r\n" } else { "Connection: close\r\n\r\n" });
+
+        let mut buf = buf.into_bytes();
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    // 处理函数未设置 `X-Request-Id` 时添加
+    fn set_request_id(&mut self, id: &str) {
+        if !self.head.iter().any(|(k, _)| k.eq_ignore_ascii_case("X-Request-Id")) {
+            self.head.push((String::from("X-Request-Id"), id.to_string()));
+        };
+    }
+
+    // 写出应答，设置了 `file` 时流式发送文件，`head_only` 时不发送文件内容
+    fn write_to(&mut self, writer: &mut impl Write, version: &str, server: &str, head_only: bool) -> std::io::Result<()> {
+        let Some(path) = self.file.take() else {
+            return writer.write_all(&self.to_bytes(version, server));
+        };
+
+        let opened = std::fs::File::open(&path).and_then(|x| Ok((x.metadata()?, x)));
+        let Some((meta, file)) = opened.ok().filter(|(x, _)| x.is_file()) else {
+            *self = Response::from(HttpError::not_found()).keep_alive(self.keep_alive);
+            return writer.write_all(&self.to_bytes(version, server));
+        };
+
+        let mut buf = self.head_line(version, server);
+        if !self.head.iter().any(|(k, _)| k.eq_ignore_ascii_case("Content-Type")) {
+            buf.extend(["Content-Type: ", mime::from_path(&path.to_string_lossy()), "\r\n"]);
+        };
+        buf.push_str(&format!("Content-Length: {}\r\n", meta.len()));
+        buf.push_str(if self.keep_alive { "Connection: keep-alive\r\n\r\n" } else { "Connection: close\r\n\r\n" });
+        writer.write_all(buf.as_bytes())?;
+
+        if !head_only && std::io::copy(&mut file.take(meta.len()), writer)? < meta.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "File Truncated While Sending!"));
+        };
+        Ok(())
+    }
+
+    // 状态行与头部，不包含 `Content-Length` `Connection` 及结尾的空行
+    fn head_line(&self, version: &str, server: &str) -> String {
+        let mut buf = format!("{} {} {}\r\n", version, self.status, reason(self.status));
+        for (key, value) in self.head.iter() {
+            buf.extend([key.as_str(), ": ", value.as_str(), "\r\n"]);
+        };
+        let has = |key: &str| self.head.iter().any(|(k, _)| k.eq_ignore_ascii_case(key));
+        if !has("Date") {
+            buf.extend(["Date: ", &http_date(SystemTime::now()), "\r\n"]);
+        };
+        if !has("Server") {
+            buf.extend(["Server: ", server, "\r\n"]);
+        };
+        buf
+    }
+}
+
+impl ResponseWriter<'_> {
+
+    ///
+    /// 设置状态码，默认为 `200`
+    ///
+    /// > 注意，写出第一个分块之后设置无效
+    ///
+    pub fn status(&mut self, status: u16) -> &mut Self {
+        self.res.status = status;
+        self
+    }
+
+    ///
+    /// 添加一个应答头部
+    ///
+    /// > 注意，写出第一个分块之后设置无效
+    ///
+    pub fn header(&mut self, key: &str, value: &str) -> &mut Self {
+        self.res.head.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    ///
+    /// 写出一个分块并立即发送，首次调用时先写出状态行与头部
+    ///
     /// 参数：
-    /// - buffer: 每次请求的原始数据
+    /// - data: 分块数据，为空时仅发送头部
     ///
-    /// 返回一个元组 `(Vec<u8>, bool)`
-    /// - Vec<u8>: 写入流数据所需的原始数据
-    /// - bool: 是否保持持续连接 (`Keep-Alive`)
+    /// 返回一个 `Result` 枚举
+    /// - Ok(())
+    /// - Err(std::io::Error): 客户端已断开，或已调用 `finish`
+    ///
+    pub fn write_chunk(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if self.finished {
+            return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Response Finished!"));
+        };
+        self.start()?;
+
+        if !data.is_empty() { // 空的分块表示结束
+            match self.chunked {
+                true => {
+                    write!(self.writer, "{:x}\r\n", data.len())?;
+                    self.writer.write_all(data)?;
+                    self.writer.write_all(b"\r\n")?;
+                },
+                false => self.writer.write_all(data)?,
+            };
+        };
+
+        self.writer.flush()
+    }
+
+    ///
+    /// 写出一个 `Server-Sent Events` 事件并立即发送
+    ///
+    /// 参数：
+    /// - event: 事件名称，为 `None` 时客户端按 `message` 处理
+    /// - data: 事件数据，多行数据将逐行添加 `data:` 前缀
+    ///
+    /// 首次调用时，若未设置 `Content-Type`，将自动设置为 `text/event-stream`，
+    /// 并添加 `Cache-Control: no-cache`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::{Request, ResponseWriter, SalServer};
+    ///
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// server.route_stream(|_request: Request, writer: &mut ResponseWriter| {
+    ///     writer.sse_event(Some("progress"), "1/2")?;
+    ///     writer.sse_event(None, "Done\nBye")?; // data: Done\ndata: Bye
+    ///     Ok(())
+    /// });
+    /// ```
+    ///
+    pub fn sse_event(&mut self, event: Option<&str>, data: &str) -> std::io::Result<()> {
+        if !self.started {
+            for (key, value) in [("Content-Type", "text/event-stream; charset=utf-8"), ("Cache-Control", "no-cache")] {
+                if !self.res.head.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                    self.header(key, value);
+                };
+            };
+        };
+
+        let mut buf = String::new();
+        if let Some(event) = event {
+            buf.extend(["event: ", &event.replace(['\r', '\n'], ""), "\n"]);
+        };
+        for line in data.split('\n') {
+            buf.extend(["data: ", line.strip_suffix('\r').unwrap_or(line), "\n"]);
+        };
+        buf.push('\n'); // 空行表示事件结束
+
+        self.write_chunk(buf.as_bytes())
+    }
+
+    ///
+    /// 写出结束分块，结束应答
+    ///
+    /// 路由函数返回 `Ok(())` 时若尚未调用，将由服务器自动调用
+    ///
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if self.finished {
+            return Ok(());
+        };
+        self.start()?;
+        self.finished = true;
+
+        if self.chunked {
+            self.writer.write_all(b"0\r\n\r\n")?;
+        };
+        self.writer.flush()
+    }
+
+    fn start(&mut self) -> std::io::Result<()> {
+        if self.started {
+            return Ok(());
+        };
+        self.started = true;
+
+        self.res.set_request_id(&self.id);
+        let mut buf = self.res.head_line(self.version, &self.server);
+        if self.chunked {
+            buf.push_str("Transfer-Encoding: chunked\r\n");
+        };
+        buf.push_str("Connection: close\r\n\r\n");
+        self.writer.write_all(buf.as_bytes())
+    }
+}
+
+impl HttpError {
+
+    ///
+    /// 创建一个新的 `HttpError` 实例
+    ///
+    /// 参数：
+    /// - status: 状态码
+    /// - msg: 错误信息
+    ///
+    pub fn new<T: ToString>(status: u16, msg: T) -> HttpError {
+        HttpError { status, msg: msg.to_string() }
+    }
+
+    ///
+    /// 创建一个 `404 Not Found` 错误
+    ///
+    pub fn not_found() -> HttpError {
+        HttpError::new(404, reason(404))
+    }
+}
+
+impl From<std::io::Error> for HttpError {
+    fn from(e: std::io::Error) -> HttpError {
+        HttpError::new(500, e)
+    }
+}
+
+impl From<HttpError> for Response {
+    fn from(e: HttpError) -> Response {
+        Response::new(e.status)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .body(e.msg)
+    }
+}
+
+impl Router {
+
+    ///
+    /// 创建一个空的 `Router` 实例
+    ///
+    pub fn new() -> Router {
+        Router { routes: Vec::new(), strict: false }
+    }
+
+    ///
+    /// 注册一个路由
+    ///
+    /// 参数：
+    /// - method: 请求方法，如 `GET`，区分大小写
+    /// - path: 请求路径，如 `/users/:id`，`:id` 匹配任意非空的一段
+    ///     - `/static/*path` 中的 `*path` 匹配剩余的全部路径，如 `a/b/c`，可为空
+    ///     - 通配段应为最后一段，其后的内容将被忽略
+    /// - handler: 处理函数，与 `route_result` 的路由函数相同
+    ///
+    /// > 注意，匹配前会移除请求路径中的查询字符串 (`?` 之后的部分)
+    ///
+    pub fn route<F>(mut self, method: &str, path: &str, handler: F) -> Router
+    where
+        F: Fn(Request) -> Result<Response, HttpError> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.routes.push((method.to_string(), path.to_string(), Box::new(handler)));
+        self
+    }
+
+    ///
+    /// 设置是否严格区分路径末尾的 `/`，默认为 `false`
+    ///
+    /// - false => `/files` 与 `/files/` 相互匹配
+    /// - true => `/files` 与 `/files/` 为不同的路径
+    ///
+    /// 对路径参数同样生效：关闭时 `/users/:id` 匹配 `/users/7/`，`id` 为 `7`；
+    /// 开启时需另外注册 `/users/:id/`。末尾的 `/` 不会作为空的参数值匹配
+    ///
+    /// 通配段捕获的路径在关闭时不含末尾的 `/`，开启时保留
+    ///
+    pub fn strict_trailing_slash(mut self, strict: bool) -> Router {
+        self.strict = strict;
+        self
+    }
+
+    ///
+    /// 分发一个请求
+    ///
+    /// - 路径与方法均匹配时，调用对应的处理函数
+    /// - 路径匹配但方法不匹配时，返回 `405 Method Not Allowed`，`Allow` 头部列出该路径已注册的方法
+    /// - 没有匹配的路径时，返回 `404 Not Found`
+    ///
+    /// 可用于在 `route_result` 的路由函数中调用
+    ///
+    /// > 注意，`HEAD` 请求总是返回 `Ok`，错误也将转换为没有主体的应答
+    ///
+    pub fn handle(&self, mut request: Request) -> Result<Response, HttpError> {
+        let path = request.path.split('?').next().unwrap_or_default();
+
+        let head = request.method == "HEAD";
+        let found = match self.find(request.method, path) {
+            None if head => self.find("GET", path),
+            x => x,
+        };
+
+        let res = match found {
+            Some((params, handler)) => {
+                request.params = params;
+                handler(request)
+            },
+            None => match self.allowed(path) {
+                Some(allow) => Ok(Response::from(HttpError::new(405, reason(405))).header("Allow", &allow)),
+                None => Err(HttpError::not_found()),
+            },
+        };
+        if !head {
+            return res;
+        };
+
+        let mut res = res.unwrap_or_else(Response::from);
+        if !res.body.is_empty() { // `HEAD` 不返回主体，但保留对应的长度
+            let length = res.body.len().to_string();
+            res.head.retain(|(k, _)| !k.eq_ignore_ascii_case("Content-Length"));
+            res.head.push((String::from("Content-Length"), length));
+            res.body.clear();
+        };
+        Ok(res)
+    }
+
+    fn find<'r>(&self, method: &str, path: &'r str) -> Option<(HashMap<String, &'r str>, &Handler)> {
+        self.routes.iter()
+            .filter(|(x, _, _)| x == method)
+            .filter_map(|(_, pattern, handler)| Some((Self::matches(pattern, path, self.strict)?, handler)))
+            .min_by(|((x, _), _), ((y, _), _)| x.cmp(y)) // 相同时保留先注册的路由
+            .map(|((_, params), handler)| (params, handler))
+    }
+
+    // 该路径已注册的方法，按注册顺序去重，注册了 `GET` 时包含 `HEAD`
+    fn allowed(&self, path: &str) -> Option<String> {
+        let mut methods: Vec<&str> = Vec::new();
+        for (method, pattern, _) in self.routes.iter() {
+            if Self::matches(pattern, path, self.strict).is_none() {
+                continue;
+            };
+            let head = (method == "GET").then_some("HEAD");
+            for x in std::iter::once(method.as_str()).chain(head) {
+                if !methods.contains(&x) {
+                    methods.push(x);
+                };
+            };
+        };
+
+        (!methods.is_empty()).then(|| methods.join(", "))
+    }
+
+    // 返回匹配的优先级 (每段 0 精确，1 参数，2 通配，越小越优先) 与路径参数
+    fn matches<'r>(pattern: &str, path: &'r str, strict: bool) -> Option<(Vec<u8>, HashMap<String, &'r str>)> {
+        let mut rest = Some(trim_slash(path, strict));
+
+        let (mut rank, mut params) = (Vec::new(), HashMap::new());
+        for x in trim_slash(pattern, strict).split('/') {
+            if let Some(key) = x.strip_prefix('*') {
+                params.insert(key.to_string(), rest.unwrap_or_default());
+                rank.push(2);
+                return Some((rank, params));
+            };
+
+            let (y, tail) = match rest?.split_once('/') {
+                Some((y, tail)) => (y, Some(tail)),
+                None => (rest?, None),
+            };
+            rest = tail;
+
+            match x.strip_prefix(':') {
+                Some(key) if !y.is_empty() => { params.insert(key.to_string(), y); rank.push(1); },
+                None if x == y => rank.push(0),
+                _ => return None,
+            };
+        };
+
+        rest.is_none().then_some((rank, params))
+    }
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
+
+// 非严格模式下移除末尾的一个 `/`，根路径 `/` 保持不变
+fn trim_slash(path: &str, strict: bool) -> &str {
+    if !strict && path.len() > 1 { path.strip_suffix('/').unwrap_or(path) } else { path }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status, reason(self.status), self.msg)
+    }
+}
+
+impl Listener {
+    fn accept(&self) -> std::io::Result<Stream> {
+        match self {
+            Listener::Tcp(x) => x.accept().map(|(x, _)| Stream::Tcp(x)),
+            #[cfg(unix)]
+            Listener::Unix(x, _) => x.accept().map(|(x, _)| Stream::Unix(x)),
+        }
+    }
+}
+
+impl Stream {
+    fn peer_addr(&self) -> SocketAddr {
+        let unspecified = (Ipv4Addr::UNSPECIFIED, 0).into();
+        match self {
+            Stream::Tcp(x) => x.peer_addr().unwrap_or(unspecified),
+            #[cfg(unix)]
+            Stream::Unix(_) => unspecified,
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(x) => x.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(x) => x.set_read_timeout(timeout),
+        }
+    }
+
+    fn try_clone_tcp(&self) -> std::io::Result<TcpStream> {
+        match self {
+            Stream::Tcp(x) => x.try_clone(),
+            #[cfg(unix)]
+            Stream::Unix(_) => Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "WebSocket over Unix Socket!")),
+        }
+    }
+}
+
+impl Read for &Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(x) => (&*x).read(buf),
+            #[cfg(unix)]
+            Stream::Unix(x) => (&*x).read(buf),
+        }
+    }
+}
+
+impl Write for &Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(x) => (&*x).write(buf),
+            #[cfg(unix)]
+            Stream::Unix(x) => (&*x).write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(x) => (&*x).flush(),
+            #[cfg(unix)]
+            Stream::Unix(x) => (&*x).flush(),
+        }
+    }
+}
+
+impl Guard {
+    fn check(&self, request: &Request) -> Option<Response> {
+        if request.method.eq_ignore_ascii_case("CONNECT") { // 不支持代理
+            return Some(Response::from(HttpError::new(405, "CONNECT is Not Supported!")));
+        };
+
+        if let Some(limiter) = &self.limiter {
+            let ip = request.client_ip(self.trust_proxy);
+            if !limiter.lock().map_or(true, |mut x| x.allow(ip)) {
+                let res = Response::from(HttpError::new(429, "Too Many Requests!"));
+                return Some(res.header("Retry-After", "1"));
+            };
+        };
+
+        if let Some(auth) = &self.auth {
+            let passed = request.head.iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("Authorization"))
+                .and_then(|(_, v)| v.strip_prefix("Basic "))
+                .and_then(|x| crypto::base64_decode(x.trim()))
+                .is_some_and(|x| constant_eq(&x, auth.as_bytes()));
+
+            if !passed {
+                let res = Response::from(HttpError::new(401, "Unauthorized!"));
+                return Some(res.header("WWW-Authenticate", "Basic realm=\"SalServer\", charset=\"UTF-8\""));
+            };
+        };
+
+        None
+    }
+
+    fn server(&self) -> &str {
+        self.server.as_deref().unwrap_or(SERVER)
+    }
+}
+
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        diff |= (a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0)) as usize;
+    };
+    diff == 0
+}
+
+impl Limiter {
+    const CAPACITY: usize = 4096; // 最多记录的IP数量
+
+    fn allow(&mut self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let (rate, burst) = (self.rate, self.burst);
+
+        if self.buckets.len() >= Self::CAPACITY && !self.buckets.contains_key(&ip) {
+            // 移除已经回满的令牌桶，与新建的令牌桶等价
+            self.buckets.retain(|_, (tokens, last)| {
+                *tokens + now.duration_since(*last).as_secs_f64() * rate < burst
+            });
+
+            if self.buckets.len() >= Self::CAPACITY {
+                let oldest = self.buckets.iter().min_by_key(|(_, (_, last))| *last).map(|(ip, _)| *ip);
+                if let Some(oldest) = oldest {
+                    self.buckets.remove(&oldest);
+                };
+            };
+        };
+
+        let (tokens, last) = self.buckets.entry(ip).or_insert((burst, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(burst);
+        *last = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else { false }
+    }
+}
+
+// RFC 1123 格式的时间，如 `Sun, 06 Nov 1994 08:49:37 GMT`
+fn http_date(time: SystemTime) -> String {
+    const WEEKS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"]; // 1970-01-01 为周四
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // 由天数计算公历日期 (Howard Hinnant, `civil_from_days`)
+    let z = days + 719468;
+    let (era, doe) = (z / 146097, z % 146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + (month <= 2) as u64;
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKS[(days % 7) as usize], day, MONTHS[month as usize - 1], year,
+        secs / 3600, secs / 60 % 60, secs % 60
+    )
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Content Too Large",
+        426 => "Upgrade Required",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        520 => "LOVE YOU",
+        _ => "Unknown",
+    }
+}
+
+impl SalServer {
+
+    ///
+    /// 创建一个新的 `SalServer` 实例
+    ///
+    /// 参数：
+    /// - bind_path: 绑定地址，如：127.0.0.1:8888
+    /// - thread: 线程数量。注意不能为0，否则将***无限期阻塞***
+    ///
+    /// 返回一个新的 `SalServer` 结构体
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("0.0.0.0:8888", 16);
+    /// ```
+    ///
+    pub fn new<T: ToSocketAddrs>(bind_path: T, thread: usize) -> SalServer {
+        let thread = ThreadLimit::new(thread);
+        let listener = TcpListener::bind(bind_path).expect("Error: Couldn't bind port!");
+        SalServer { thread, listener: Listener::Tcp(listener), guard: Guard::default(), upgrades: Arc::default() }
+    }
+
+    ///
+    /// 创建一个监听 `Unix` 套接字的 `SalServer` 实例 (仅 `unix`)
+    ///
+    /// 参数：
+    /// - path: 套接字文件路径，文件不能已存在
+    /// - thread: 线程数量。注意不能为0，否则将***无限期阻塞***
+    ///
+    /// 返回一个新的 `SalServer` 结构体，实例销毁时将删除套接字文件
+    ///
+    /// > 注意，`Unix` 连接没有客户端地址，`Request::peer` 为 `0.0.0.0:0`，
+    /// > 且不支持 `on_upgrade`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    /// use std::path::Path;
+    ///
+    /// let server = SalServer::bind_unix(Path::new("/tmp/sal.sock"), 4);
+    /// ```
+    ///
+    #[cfg(unix)]
+    pub fn bind_unix(path: &Path, thread: usize) -> SalServer {
+        let thread = ThreadLimit::new(thread);
+        let listener = UnixListener::bind(path).expect("Error: Couldn't bind socket!");
+        let listener = Listener::Unix(listener, path.to_path_buf());
+        SalServer { thread, listener, guard: Guard::default(), upgrades: Arc::default() }
+    }
+
+    ///
+    /// 为服务添加基于IP的限流 (令牌桶)
+    ///
+    /// 参数：
+    /// - requests_per_sec: 每秒恢复的请求数量
+    /// - burst: 允许的突发请求数量，即令牌桶的容量
+    ///
+    /// 超出限制的请求将返回 `429 Too Many Requests`
+    ///
+    /// > 注意，作用于 `route_http` `route_result` `route_router` 与 `route_stream`，
+    /// > 客户端IP由 `Request::client_ip` 获取，参见 `trust_proxy`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("0.0.0.0:8888", 16).with_rate_limit(5, 10);
+    /// ```
+    ///
+    pub fn with_rate_limit(mut self, requests_per_sec: u32, burst: u32) -> SalServer {
+        self.guard.limiter = Some(Arc::new(Mutex::new(Limiter {
+            rate: requests_per_sec as f64,
+            burst: burst.max(1) as f64,
+            buckets: HashMap::new(),
+        })));
+        self
+    }
+
+    ///
+    /// 为服务添加 `HTTP Basic` 认证
+    ///
+    /// 参数：
+    /// - user: 用户名
+    /// - pass: 密码
+    ///
+    /// 认证失败的请求将返回 `401 Unauthorized` 及 `WWW-Authenticate` 头部
+    ///
+    /// > 注意，作用于 `route_http` `route_result` `route_router` 与 `route_stream`，
+    /// > 且 `Basic` 认证为明文传输，请配合 `HTTPS` 反向代理使用
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("0.0.0.0:8888", 16).require_basic_auth("admin", "p@ssw0rd");
+    /// ```
+    ///
+    pub fn require_basic_auth(mut self, user: &str, pass: &str) -> SalServer {
+        self.guard.auth = Some(format!("{user}:{pass}").into());
+        self
+    }
+
+    ///
+    /// 为服务添加 `WebSocket` 路径
+    ///
+    /// 参数：
+    /// - path: 请求路径，不包含查询参数，如：`/ws`
+    /// - handler: 处理函数，握手完成后获得 `WsStream`
+    ///
+    /// 该路径的 `WebSocket` 握手请求将由服务器自动应答 `101`，其余请求仍交给路由函数；
+    /// 握手请求的 `Sec-WebSocket-Version` 不为 `13` 时应答 `426`，并告知所支持的版本
+    ///
+    /// > 注意，仅作用于 `route_result`，处理函数将占用一个线程直至返回
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::{Message, SalServer};
+    ///
+    /// let server = SalServer::new("0.0.0.0:8888", 16).on_upgrade("/ws", |mut ws| {
+    ///     while let Ok(Message::Text(x)) = ws.read() {
+    ///         let _ = ws.send(Message::Text(x));
+    ///     };
+    /// });
+    /// ```
+    ///
+    pub fn on_upgrade<F: Fn(WsStream) + Send + Sync + RefUnwindSafe + 'static>(mut self, path: &str, handler: F) -> SalServer {
+        Arc::make_mut(&mut self.upgrades).push((path.to_string(), Arc::new(handler)));
+        self
+    }
+
+    ///
+    /// 设置持续连接的空闲超时，默认不超时
+    ///
+    /// 参数：
+    /// - timeout: 等待下一个请求的最长时间
+    ///
+    /// 连接在等待请求时超过该时间未收到数据，将被直接关闭并释放线程；
+    /// 已开始接收的请求 (如 `100-continue` 的主体) 改由 `set_read_timeout` 限制
+    ///
+    /// > 注意，仅作用于 `route_http` `route_result` `route_router` 与 `route_stream`
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = SalServer::new("0.0.0.0:8888", 16);
+    /// server.set_keepalive_timeout(Duration::from_secs(15));
+    /// ```
+    ///
+    pub fn set_keepalive_timeout(&mut self, timeout: Duration) {
+        self.guard.idle = Some(timeout).filter(|x| !x.is_zero()); // `0` 在 `set_read_timeout` 中无效
+    }
+
+    ///
+    /// 设置请求开始后的读取超时，默认为 `30` 秒
+    ///
+    /// 参数：
+    /// - timeout: 收到请求的首个字节后，读取其余头部与主体时单次读取的最长等待时间，`0` 为不超时
+    ///
+    /// 超时后将应答 `408 Request Timeout` 并关闭连接，与空闲超时 (`set_keepalive_timeout`) 的静默关闭相区分
+    ///
+    /// > 注意，`route_stream` 中路由函数自行读取连接时同样受此限制
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    /// use std::time::Duration;
+    ///
+    /// let mut server = SalServer::new("0.0.0.0:8888", 16);
+    /// server.set_read_timeout(Duration::from_secs(10));
+    /// ```
+    ///
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.guard.active = Some(timeout).filter(|x| !x.is_zero());
+    }
+
+    ///
+    /// 设置是否信任 `X-Forwarded-For` 头部，默认为 `false`
+    ///
+    /// > 注意，仅在反向代理之后开启，否则客户端可伪造IP
+    ///
+    pub fn trust_proxy(mut self, trust_proxy: bool) -> SalServer {
+        self.guard.trust_proxy = trust_proxy;
+        self
+    }
+
+    ///
+    /// 设置应答的 `Server` 头部，默认为 `SalServer`
+    ///
+    /// > 注意，仅作用于 `Response` 及服务器自动生成的错误应答，
+    /// > 路由函数已设置 `Server` 时不会覆盖
+    ///
+    /// **Example:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("0.0.0.0:8888", 16).server_name("Saloxy/1.0");
+    /// ```
+    ///
+    pub fn server_name(mut self, name: &str) -> SalServer {
+        self.guard.server = Some(name.into());
+        self
+    }
+
+    ///
+    /// 为服务提供路由，并提供服务（原始方法）
+    ///
+    /// 参数：
+    /// - route: 路由函数
+    ///
+    /// 使用该方法，需要定义一个特殊函数：
+    /// ```
+    /// fn route(buffer: Vec<u8>) -> (Vec<u8>, bool) {}
+    /// ```
+    /// 参数：
+    /// - buffer: 每次请求的原始数据
+    ///
+    /// 返回一个元组 `(Vec<u8>, bool)`
+    /// - Vec<u8>: 写入流数据所需的原始数据
+    /// - bool: 是否保持持续连接 (`Keep-Alive`)
+    ///
+    /// 该函数的 `buffer` 参数由 `route_pro` 方法提供
+    ///
+    /// **Example1:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("127.0.0.1:8888", 16);
+    /// server.route_pro(|buffer| {
+    ///     let mut buf = Vec::from(
+    ///         "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"
+    ///     );
+    ///     buf.extend_from_slice(buffer);
+    ///     return (buf, false);
+    /// });
+    /// ```
+    ///
+    /// **Example 2:**
+    /// ```
+    /// mod salfa_server;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("127.0.0.1:8888", 16);
+    /// server.route_pro(route);
+    ///
+    /// fn route(buffer: Vec<u8>) -> (Vec<u8>, bool) {
+    ///     (Vec::from("HTTP/1.1 200 OK\r\n\r\n"), true)
+    /// };
+    /// ```
+    ///
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn route_pro<F: FnOnce(Vec<u8>) -> (Vec<u8>, bool) + Copy + Send + 'static + UnwindSafe>(&self, route: F) {
+        loop {
+            if let Ok(stream) = self.listener.accept() {
+                self.thread.execute(move || Self::handler_pro(stream, route));
+            } else { continue; };
+        };
+    }
+
+    fn handler_pro<F: FnOnce(Vec<u8>) -> (Vec<u8>, bool) + Copy>(stream: Stream, route: F) {
+        let mut reader = BufReader::new(&stream);
+        let mut writer = BufWriter::new(&stream);
+
+        loop {
+
+            let (buffer, lens) = match reader.fill_buf() {
+                Ok([]) => return, // 客户端已断开，无需应答
+                Ok(x) => (x.to_vec(), x.len()),
+                Err(e) if Self::disconnected(&e) => return,
+                Err(e) => return Self::return_error(&mut writer, e.to_string().as_str(), SERVER),
+            };
+
+            let (result, keep_alive) = route(buffer);
+
+            if let Err(e) = writer.write(&result) {
+                return Self::return_error(&mut writer, e.to_string().as_str(), SERVER);
+            }; // 写出处理后的数据
+
+            if keep_alive { // 将数据消耗，防止出现读取重复现象
+                reader.consume(lens);
+            } else { break; };
+
+            if let Err(e) = writer.flush() {
+                return Self::return_error(&mut writer, e.to_string().as_str(), SERVER);
+            } // 立即将数据写出，避免出现无输出现象
+
+        };
+    }
+
+    ///
+    /// 为服务提供路由，并提供服务
+    ///
+    /// 参数：
+    /// - route: 路由函数
+    ///
+    /// 使用该方法，需要定义一个特殊函数：
+    /// ```
+    /// fn route(http_line: (&str, &str), head: HashMap<&str, &str>, body: &str) -> (Vec<u8>, bool) {}
+    /// ```
+    /// 参数：
+    /// - http_line: HTTP请求的头行，包括 `method` `path` `version`
+    ///     - method: 请求方法
+    ///     - path: 请求路径
+    ///     - version: HTTP版本，暂不提供
+    /// - head: HTTP请求的头部信息 (Header)
+    /// - body: 请求主体部分，承载信息
+    ///
+    /// 返回一个元组 `(Vec<u8>, bool)`
+    /// - Vec<u8>: 写入流数据所需的*原始*数据
+    /// - bool: 是否保持持续连接 (`Keep-Alive`)
+    ///
+    /// 该函数的 `http_line` `header` `body` 参数由 `route` 方法提供
+    ///     - http_line: (method: &str, path: &str)
+    ///
+    /// **Example1:**
+    /// ```
+    /// mod salfa_server;
+    /// use std::collections::HashMap;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// serv.route(|http_line: (&str, &str), _header: HashMap<&str, &str>, _body: &str| {
+    ///     (Vec::from("HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"), false)
+    /// });
+    /// ```
+    ///
+    /// **Example 2:**
+    /// ```
+    /// mod salfa_server;
+    /// use std::collections::HashMap;
+    /// use salfa_server::SalServer;
+    ///
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// server.route(route);
+    ///
+    /// fn route(http_line: (&str, &str), head: HashMap<&str, &str>, body: &str) -> (Vec<u8>, bool) {
+    ///     let mut buf = Vec::from("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n");
+    ///     let buff = Vec::from(format!("Http Line: {:?}\r\nHead: {:#?}\r\nBody: {}\r\n", http_line, head, body));
+    ///     buf.extend(buff);
+    ///     return (buf, true)
+    /// }
+    /// ```
+    ///
+    /// > 注意，常见的HTTP方法有：
+    /// > `GET POST PUT HEAD DELETE OPTIONS PATCH CONNECT TRACE`
+    ///
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn route_http<F: FnOnce((&str, &str), HashMap<&str, &str>, &str) -> (Vec<u8>, bool) + Send + 'static + UnwindSafe + Copy>(&self, route: F) {
+        loop {
+            if let Ok(stream) = self.listener.accept() {
+                let guard = self.guard.clone();
+                self.thread.execute(move || Self::handler_http(stream, route, guard));
+            } else { continue; };
+        };
+    }
+
+    fn handler_http<F: FnOnce((&str, &str), HashMap<&str, &str>, &str) -> (Vec<u8>, bool) + Copy>(stream: Stream, route: F, guard: Guard) {
+        let peer = stream.peer_addr();
+        let mut reader = BufReader::new(&stream);
+        let mut writer = BufWriter::new(&stream);
+
+        loop {
+
+            let _ = stream.set_read_timeout(guard.idle); // 等待请求时使用空闲超时
+            let (buffer, lens) = match reader.fill_buf() {
+                Ok([]) => return, // 客户端已断开，无需应答
+                Ok(x) => (x, x.len()),
+                Err(e) if Self::disconnected(&e) || Self::timed_out(&e) => return, // 空闲超时，直接关闭连接
+                Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
+            };
+            let _ = stream.set_read_timeout(guard.active); // 请求已开始，改用读取超时
+
+            let (buffer, lens) = match Self::continue_length(buffer) {
+                Some(len) if len > MAX_CONTINUE => {
+                    let res = Response::from(HttpError::new(413, "Request Body Too Large!"));
+                    let _ = writer.write_all(&res.to_bytes("HTTP/1.1", guard.server()));
+                    break;
+                },
+                Some(len) => {
+                    let mut data = buffer.to_vec();
+                    reader.consume(lens);
+                    match Self::read_continue(&mut reader, &mut writer, &mut data, len) {
+                        Ok(_) => (Cow::Owned(data), 0),
+                        Err(e) if Self::disconnected(&e) => return,
+                        Err(e) if Self::timed_out(&e) => return Self::return_timeout(&mut writer, guard.server()),
+                        Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
+                    }
+                },
+                None => (Cow::Borrowed(buffer), lens),
+            };
+
+            let buffer = Request::text(&buffer);
+            let Some(request) = Request::parse(&buffer, peer) else {
+                return Self::return_error(&mut writer, "Non-Standard HTTP Structure!", guard.server());
+            };
+
+            if let Some(res) = guard.check(&request) {
+                let _ = writer.write_all(&res.to_bytes(request.reply_version(), guard.server()));
+                break;
+            };
+
+            let persistent = request.persistent();
+            let (result, keep_alive) = route((request.method, request.path), request.head, request.body);
+            let keep_alive = keep_alive && persistent;
+
+            if let Err(e) = writer.write(&result) {
+                return Self::return_error(&mut writer, &e.to_string(), guard.server());
+            }; // 写出处理后的数据
+
+            if keep_alive { // 将数据消耗，防止出现读取重复现象
+                reader.consume(lens);
+            } else { break; };
+
+            if let Err(e) = writer.flush() {
+                return Self::return_error(&mut writer, &e.to_string(), guard.server());
+            } // 立即将数据写出，避免出现无输出现象
+
+        };
+
+    }
+
+    ///
+    /// 为服务提供路由，并提供服务（`Result` 方法）
+    ///
+    /// 参数：
+    /// - route: 路由函数
+    ///
+    /// 使用该方法，需要定义一个特殊函数：
+    /// ```
+    /// fn route(request: Request) -> Result<Response, HttpError> {}
+    /// ```
+    /// 参数：
+    /// - request: 解析后的HTTP请求 `Request`
     ///
-    /// 该函数的 `buffer` 参数由 `route_pro` 方法提供
+    /// 返回一个 `Result` 枚举
+    /// - Ok(Response): 写入流的应答
+    /// - Err(HttpError): 自动转换为对应状态码的应答，并结束连接
     ///
-    /// **Example1:**
+    /// 路由函数中可以直接使用 `?`，`std::io::Error` 将被转换为 `500`
+    ///
+    /// **Example:**
     /// ```
     /// mod salfa_server;
-    /// use salfa_server::SalServer;
+    /// use salfa_server::{HttpError, Request, Response, SalServer};
     ///
-    /// let server = SalServer::new("127.0.0.1:8888", 16);
-    /// server.route_pro(|buffer| {
-    ///     let mut buf = Vec::from(
-    ///         "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"
-    ///     );
-    ///     buf.extend_from_slice(buffer);
-    ///     return (buf, false);
-    /// });
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// server.route_result(route);
+    ///
+    /// fn route(request: Request) -> Result<Response, HttpError> {
+    ///     if request.path != "/" {
+    ///         return Err(HttpError::not_found());
+    ///     }
+    ///
+    ///     let body = std::fs::read_to_string("/root/index.html")?;
+    ///     Ok(Response::new(200).header("Content-Type", "text/html").body(body))
+    /// }
     /// ```
     ///
-    /// **Example 2:**
+    /// *请注意：该方法会阻塞运行！*
+    ///
+    pub fn route_result<F: FnOnce(Request) -> Result<Response, HttpError> + Send + 'static + UnwindSafe + Copy>(&self, route: F) {
+        loop {
+            if let Ok(stream) = self.listener.accept() {
+                let (guard, upgrades) = (self.guard.clone(), self.upgrades.clone());
+                self.thread.execute(move || Self::handler_result(stream, route, guard, upgrades));
+            } else { continue; };
+        };
+    }
+
+    ///
+    /// 使用 `Router` 为服务提供路由，并提供服务
+    ///
+    /// 参数：
+    /// - router: 路由表，见 `Router`
+    ///
+    /// 与 `route_result` 相同，`guard` 与 `on_upgrade` 同样生效
+    ///
+    /// **Example:**
     /// ```
     /// mod salfa_server;
-    /// use salfa_server::SalServer;
+    /// use salfa_server::{Response, Router, SalServer};
     ///
-    /// let server = SalServer::new("127.0.0.1:8888", 16);
-    /// server.route_pro(route);
+    /// let router = Router::new()
+    ///     .route("GET", "/files", |_| Ok(Response::new(200).body("Files")));
     ///
-    /// fn route(buffer: Vec<u8>) -> (Vec<u8>, bool) {
-    ///     (Vec::from("HTTP/1.1 200 OK\r\n\r\n"), true)
-    /// };
+    /// let server = SalServer::new("127.0.0.1:4998", 16);
+    /// server.route_router(router);
     /// ```
     ///
     /// *请注意：该方法会阻塞运行！*
     ///
-    pub fn route_pro<F: FnOnce(Vec<u8>) -> (Vec<u8>, bool) + Copy + Send + 'static + UnwindSafe>(&self, route: F) {
-        for stream in self.listener.incoming() {
-            if let Ok(stream) = stream {
-                self.thread.execute(move || Self::handler_pro(stream, route));
+    pub fn route_router(&self, router: Router) {
+        let router = Arc::new(router);
+        loop {
+            if let Ok(stream) = self.listener.accept() {
+                let (guard, upgrades, router) = (self.guard.clone(), self.upgrades.clone(), router.clone());
+                self.thread.execute(move || Self::handler_result(stream, |x| router.handle(x), guard, upgrades));
             } else { continue; };
         };
     }
 
-    fn handler_pro<F: FnOnce(Vec<u8>) -> (Vec<u8>, bool) + Copy>(stream: TcpStream, route: F) {
+    fn handler_result<F: FnOnce(Request) -> Result<Response, HttpError> + Copy>(stream: Stream, route: F, guard: Guard, upgrades: Arc<Vec<(String, Upgrade)>>) {
+        let peer = stream.peer_addr();
         let mut reader = BufReader::new(&stream);
         let mut writer = BufWriter::new(&stream);
 
         loop {
 
+            let _ = stream.set_read_timeout(guard.idle); // 等待请求时使用空闲超时
             let (buffer, lens) = match reader.fill_buf() {
-                Ok(x) => (x.to_vec(), x.len()),
-                Err(e) => return Self::return_error(&mut writer, e.to_string().as_str()),
+                Ok([]) => return, // 客户端已断开，无需应答
+                Ok(x) => (x, x.len()),
+                Err(e) if Self::disconnected(&e) || Self::timed_out(&e) => return, // 空闲超时，直接关闭连接
+                Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
             };
+            let _ = stream.set_read_timeout(guard.active); // 请求已开始，改用读取超时
 
-            let (result, keep_alive) = route(buffer);
+            let (buffer, lens) = match Self::continue_length(buffer) {
+                Some(len) if len > MAX_CONTINUE => {
+                    let res = Response::from(HttpError::new(413, "Request Body Too Large!"));
+                    let _ = writer.write_all(&res.to_bytes("HTTP/1.1", guard.server()));
+                    break;
+                },
+                Some(len) => {
+                    let mut data = buffer.to_vec();
+                    reader.consume(lens);
+                    match Self::read_continue(&mut reader, &mut writer, &mut data, len) {
+                        Ok(_) => (Cow::Owned(data), 0),
+                        Err(e) if Self::disconnected(&e) => return,
+                        Err(e) if Self::timed_out(&e) => return Self::return_timeout(&mut writer, guard.server()),
+                        Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
+                    }
+                },
+                None => (Cow::Borrowed(buffer), lens),
+            };
 
-            if let Err(e) = writer.write(&result) {
-                return Self::return_error(&mut writer, e.to_string().as_str());
+            let buffer = Request::text(&buffer);
+            let Some(request) = Request::parse(&buffer, peer) else {
+                let res = Response::from(HttpError::new(400, "Non-Standard HTTP Structure!"));
+                let _ = writer.write_all(&res.to_bytes("HTTP/1.1", guard.server()));
+                break;
+            };
+
+            let (version, persistent, head_only) = (request.reply_version(), request.persistent(), request.method == "HEAD");
+            let id = request.request_id().to_string();
+            let mut res = match guard.check(&request) {
+                Some(x) => x,
+                None => {
+                    let path = request.path.split('?').next().unwrap_or_default();
+                    let upgrade = upgrades.iter().find(|(x, _)| x == path);
+                    match (upgrade, request.websocket_key()) {
+                        (Some(_), Some((_, version))) if version.map(str::trim) != Some(websocket::VERSION) => {
+                            Response::from(HttpError::new(426, "Unsupported WebSocket Version!"))
+                                .header("Sec-WebSocket-Version", websocket::VERSION)
+                        },
+                        (Some((_, handler)), Some((key, _))) => match stream.try_clone_tcp() {
+                            Ok(x) => {
+                                let res = format!(
+                                    "HTTP/1.1 101 Switching Protocols\r\n\
+                                    Upgrade: websocket\r\n\
+                                    Connection: Upgrade\r\n\
+                                    Sec-WebSocket-Accept: {}\r\n\r\n",
+                                    websocket::accept_key(key)
+                                );
+                                if let Err(e) = writer.write_all(res.as_bytes()).and_then(|_| writer.flush()) {
+                                    return Self::return_error(&mut writer, &e.to_string(), guard.server());
+                                };
+                                return handler(WsStream::new(x));
+                            },
+                            Err(e) => Response::from(HttpError::new(501, e)), // `Unix` 套接字不支持升级
+                        },
+                        _ => match route(request) {
+                            Ok(x) => x,
+                            Err(e) => Response::from(e),
+                        },
+                    }
+                },
+            };
+
+            res.keep_alive &= persistent;
+            res.set_request_id(&id);
+            if let Err(e) = res.write_to(&mut writer, version, guard.server(), head_only) {
+                return Self::return_error(&mut writer, &e.to_string(), guard.server());
             }; // 写出处理后的数据
 
-            if keep_alive { // 将数据消耗，防止出现读取重复现象
+            if res.keep_alive { // 将数据消耗，防止出现读取重复现象
                 reader.consume(lens);
             } else { break; };
 
             if let Err(e) = writer.flush() {
-                return Self::return_error(&mut writer, e.to_string().as_str());
+                return Self::return_error(&mut writer, &e.to_string(), guard.server());
             } // 立即将数据写出，避免出现无输出现象
 
         };
     }
 
     ///
-    /// 为服务提供路由，并提供服务
+    /// 为服务提供路由，并提供服务（分块应答方法）
     ///
     /// 参数：
     /// - route: 路由函数
     ///
     /// 使用该方法，需要定义一个特殊函数：
     /// ```
-    /// fn route(http_line: (&str, &str), head: HashMap<&str, &str>, body: &str) -> (Vec<u8>, bool) {}
+    /// fn route(request: Request, writer: &mut ResponseWriter) -> Result<(), HttpError> {}
     /// ```
     /// 参数：
-    /// - http_line: HTTP请求的头行，包括 `method` `path` `version`
-    ///     - method: 请求方法
-    ///     - path: 请求路径
-    ///     - version: HTTP版本，暂不提供
-    /// - head: HTTP请求的头部信息 (Header)
-    /// - body: 请求主体部分，承载信息
-    ///
-    /// 返回一个元组 `(Vec<u8>, bool)`
-    /// - Vec<u8>: 写入流数据所需的*原始*数据
-    /// - bool: 是否保持持续连接 (`Keep-Alive`)
-    ///
-    /// 该函数的 `http_line` `header` `body` 参数由 `route` 方法提供
-    ///     - http_line: (method: &str, path: &str)
+    /// - request: 解析后的HTTP请求 `Request`
+    /// - writer: 分块应答 `ResponseWriter`，见 `ResponseWriter`
     ///
-    /// **Example1:**
-    /// ```
-    /// mod salfa_server;
-    /// use std::collections::HashMap;
-    /// use salfa_server::SalServer;
+    /// 返回一个 `Result` 枚举
+    /// - Ok(()): 未调用 `finish` 时自动结束应答
+    /// - Err(HttpError): 尚未写出分块时转换为对应状态码的应答，否则直接结束连接
     ///
-    /// let server = SalServer::new("127.0.0.1:4998", 16);
-    /// serv.route(|http_line: (&str, &str), _header: HashMap<&str, &str>, _body: &str| {
-    ///     (Vec::from("HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n"), false)
-    /// });
-    /// ```
+    /// 适用于大文件等无法预先确定长度的应答，每个连接仅处理一个请求，`guard` 同样生效
     ///
-    /// **Example 2:**
+    /// **Example:**
     /// ```
     /// mod salfa_server;
-    /// use std::collections::HashMap;
-    /// use salfa_server::SalServer;
+    /// use std::io::Read;
+    /// use salfa_server::{HttpError, Request, ResponseWriter, SalServer};
     ///
     /// let server = SalServer::new("127.0.0.1:4998", 16);
-    /// server.route(route);
+    /// server.route_stream(route);
     ///
-    /// fn route(http_line: (&str, &str), head: HashMap<&str, &str>, body: &str) -> (Vec<u8>, bool) {
-    ///     let mut buf = Vec::from("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n");
-    ///     let buff = Vec::from(format!("Http Line: {:?}\r\nHead: {:#?}\r\nBody: {}\r\n", http_line, head, body));
-    ///     buf.extend(buff);
-    ///     return (buf, true)
+    /// fn route(_request: Request, writer: &mut ResponseWriter) -> Result<(), HttpError> {
+    ///     let mut file = std::fs::File::open("/root/large.bin")?;
+    ///     let mut buf = [0; 8192];
+    ///     loop {
+    ///         match file.read(&mut buf)? {
+    ///             0 => break Ok(()),
+    ///             n => writer.write_chunk(&buf[..n])?,
+    ///         };
+    ///     }
     /// }
     /// ```
     ///
-    /// > 注意，常见的HTTP方法有：
-    /// `GET POST PUT HEAD DELETE OPTIONS PATCH CONNECT TRACE`
-    ///
     /// *请注意：该方法会阻塞运行！*
     ///
-    pub fn route_http<F: FnOnce((&str, &str), HashMap<&str, &str>, &str) -> (Vec<u8>, bool) + Send + 'static + UnwindSafe + Copy>(&self, route: F) {
-        for stream in self.listener.incoming() {
-            if let Ok(stream) = stream {
-                self.thread.execute(move || Self::handler_http(stream, route));
+    pub fn route_stream<F: FnOnce(Request, &mut ResponseWriter) -> Result<(), HttpError> + Send + 'static + UnwindSafe + Copy>(&self, route: F) {
+        loop {
+            if let Ok(stream) = self.listener.accept() {
+                let guard = self.guard.clone();
+                self.thread.execute(move || Self::handler_stream(stream, route, guard));
             } else { continue; };
         };
     }
 
-    fn handler_http<F: FnOnce((&str, &str), HashMap<&str, &str>, &str) -> (Vec<u8>, bool) + Copy>(stream: TcpStream, route: F) {
+    fn handler_stream<F: FnOnce(Request, &mut ResponseWriter) -> Result<(), HttpError> + Copy>(stream: Stream, route: F, guard: Guard) {
+        let peer = stream.peer_addr();
         let mut reader = BufReader::new(&stream);
         let mut writer = BufWriter::new(&stream);
 
-        loop {
+        let _ = stream.set_read_timeout(guard.idle); // 等待请求时使用空闲超时
+        let (buffer, lens) = match reader.fill_buf() {
+            Ok([]) => return, // 客户端已断开，无需应答
+            Ok(x) => (x, x.len()),
+            Err(e) if Self::disconnected(&e) || Self::timed_out(&e) => return, // 空闲超时，直接关闭连接
+            Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
+        };
+        let _ = stream.set_read_timeout(guard.active); // 请求已开始，改用读取超时
 
-            let (buffer, lens) = match reader.fill_buf() {
-                Ok(x) => (x, x.len()),
-                Err(e) => return Self::return_error(&mut writer, &*e.to_string()),
-            };
+        let buffer = match Self::continue_length(buffer) {
+            Some(len) if len > MAX_CONTINUE => {
+                let res = Response::from(HttpError::new(413, "Request Body Too Large!"));
+                let _ = writer.write_all(&res.to_bytes("HTTP/1.1", guard.server()));
+                return;
+            },
+            Some(len) => {
+                let mut data = buffer.to_vec();
+                reader.consume(lens);
+                match Self::read_continue(&mut reader, &mut writer, &mut data, len) {
+                    Ok(_) => Cow::Owned(data),
+                    Err(e) if Self::disconnected(&e) => return,
+                    Err(e) if Self::timed_out(&e) => return Self::return_timeout(&mut writer, guard.server()),
+                    Err(e) => return Self::return_error(&mut writer, &e.to_string(), guard.server()),
+                }
+            },
+            None => Cow::Borrowed(buffer),
+        };
 
-            if buffer.is_empty() {
-                return Self::return_error(&mut writer, "Empty Input!");
-            };
+        let buffer = Request::text(&buffer);
+        let Some(request) = Request::parse(&buffer, peer) else {
+            let res = Response::from(HttpError::new(400, "Non-Standard HTTP Structure!"));
+            let _ = writer.write_all(&res.to_bytes("HTTP/1.1", guard.server()));
+            return;
+        };
 
-            let buffer = String::from_utf8_lossy(buffer);
-            let Some((headers, body)) = buffer.split_once("\r\n\r\n") else {
-                return Self::return_error(&mut writer, "Non-Standard HTTP Structure!");
-            };
+        let (version, id) = (request.reply_version(), request.request_id().to_string());
+        if let Some(mut res) = guard.check(&request) {
+            res.set_request_id(&id);
+            let _ = writer.write_all(&res.to_bytes(version, guard.server()));
+            return;
+        };
 
-            let mut headers = headers.lines();
-            let Some(http_line) = headers.next() else {
-                return Self::return_error(&mut writer, "Non-Standard HTTP Structure!");
-            };
+        let mut res = ResponseWriter {
+            writer: &mut writer,
+            res: Response::new(200),
+            version,
+            server: guard.server().to_string(),
+            id: id.clone(),
+            chunked: version == "HTTP/1.1",
+            started: false,
+            finished: false,
+        };
 
-            let http_line: Vec<&str> = http_line.split_whitespace().collect();
-            let [method, path, _] = http_line[..] else {
-                return Self::return_error(&mut writer, "Non-Standard HTTP Structure!");
-            };
+        match route(request, &mut res) {
+            Ok(_) => { let _ = res.finish(); },
+            Err(e) if !res.started => {
+                let mut res = Response::from(e);
+                res.set_request_id(&id);
+                let _ = writer.write_all(&res.to_bytes(version, guard.server()));
+            },
+            Err(_) => {}, // 已写出部分应答，直接结束连接
+        };
 
-            let mut head = HashMap::new();
-            for header in headers {
-                if let Some(place) = header.find(':') {
-                    let key = header[..place].trim();
-                    let value = header[place+1..].trim();
-                    head.insert(key, value);
-                };
-            };
+        let _ = writer.flush();
+    }
 
-            let (result, keep_alive) = route((method, path), head, body);
+    // 请求带有 `Expect: 100-continue` 且尚未发送主体时，返回 `Content-Length`
+    fn continue_length(buffer: &[u8]) -> Option<usize> {
+        let place = buffer.windows(4).position(|x| x == b"\r\n\r\n")?;
+        if place + 4 != buffer.len() {
+            return None;
+        };
 
-            if let Err(e) = writer.write(&result) {
-                return Self::return_error(&mut writer, &*e.to_string());
-            }; // 写出处理后的数据
+        let head = String::from_utf8_lossy(&buffer[..place]);
+        let header = |key: &str| head.lines().skip(1)
+            .filter_map(|x| x.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim().to_string());
 
-            if keep_alive { // 将数据消耗，防止出现读取重复现象
-                reader.consume(lens);
-            } else { break; };
+        if !header("Expect").is_some_and(|x| x.eq_ignore_ascii_case("100-continue")) {
+            return None;
+        };
 
-            if let Err(e) = writer.flush() {
-                return Self::return_error(&mut writer, &*e.to_string());
-            } // 立即将数据写出，避免出现无输出现象
+        Some(header("Content-Length").and_then(|x| x.parse().ok()).unwrap_or(0))
+    }
 
-        };
+    // 应答 `100 Continue`，然后将主体读入 `data`
+    fn read_continue(reader: &mut BufReader<&Stream>, writer: &mut BufWriter<&Stream>, data: &mut Vec<u8>, len: usize) -> std::io::Result<()> {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        writer.flush()?;
+
+        let start = data.len();
+        data.resize(start + len, 0);
+        reader.read_exact(&mut data[start..])
+    }
+
+    fn disconnected(e: &std::io::Error) -> bool {
+        use std::io::ErrorKind::*;
+        matches!(e.kind(), ConnectionReset | ConnectionAborted | BrokenPipe | UnexpectedEof)
+    }
+
+    fn timed_out(e: &std::io::Error) -> bool {
+        use std::io::ErrorKind::*;
+        matches!(e.kind(), WouldBlock | TimedOut) // 超时在 `unix` 上为 `WouldBlock`
+    }
 
+    // 请求未在读取超时内读完，应答 `408` 后关闭连接
+    fn return_timeout(writer: &mut BufWriter<&Stream>, server: &str) {
+        let res = Response::from(HttpError::new(408, "Request Read Timed Out!"));
+        let _ = writer.write_all(&res.to_bytes("HTTP/1.1", server));
+        let _ = writer.flush();
     }
 
-    fn return_error(writer: &mut BufWriter<&TcpStream>, err: &str) {
-        let mut res = String::from(
+    fn return_error(writer: &mut BufWriter<&Stream>, err: &str, server: &str) {
+        let mut res = format!(
             "HTTP/1.1 520 LOVE YOU\r\n\
             Content-Type: text/plain; charset=utf-8\r\n\
-            Connection: close\r\n\r\n"
+            Date: {}\r\n\
+            Server: {server}\r\n\
+            Connection: close\r\n\r\n",
+            http_date(SystemTime::now())
         );
         res.extend([err, "\r\n"]); // 构建应答信息
 
@@ -282,3 +1879,562 @@ impl SalServer {
     }
 
 }
+
+impl Drop for SalServer {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = std::fs::remove_file(path);
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 4000);
+
+    fn local_addr(server: &SalServer) -> SocketAddr {
+        match &server.listener {
+            Listener::Tcp(x) => x.local_addr().unwrap(),
+            #[cfg(unix)]
+            Listener::Unix(..) => unreachable!(),
+        }
+    }
+
+    fn serve_result<F>(server: SalServer, route: F) -> SocketAddr
+    where
+        F: FnOnce(Request) -> Result<Response, HttpError> + Send + 'static + UnwindSafe + Copy,
+    {
+        let addr = local_addr(&server);
+        thread::spawn(move || server.route_result(route));
+        addr
+    }
+
+    // 发送原始请求，读取至服务器关闭连接
+    fn exchange(addr: SocketAddr, raw: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(raw).unwrap();
+
+        let mut res = Vec::new();
+        let _ = stream.read_to_end(&mut res);
+        String::from_utf8_lossy(&res).into_owned()
+    }
+
+    fn echo_path(request: Request) -> Result<Response, HttpError> {
+        Ok(Response::new(200).body(format!("{} {}", request.method, request.path)))
+    }
+
+    #[test]
+    fn absolute_form_targets() {
+        let parse = |raw: &str| {
+            let text = Request::text(raw.as_bytes()).into_owned();
+            Request::parse(&text, PEER).map(|x| x.path.to_string())
+        };
+
+        assert_eq!(parse("GET http://host/files?a=1 HTTP/1.1\r\n\r\n").as_deref(), Some("/files?a=1"));
+        assert_eq!(parse("GET http://host?x=1 HTTP/1.1\r\n\r\n").as_deref(), Some("/?x=1"));
+        assert_eq!(parse("GET HTTPS://host:8080 HTTP/1.1\r\n\r\n").as_deref(), Some("/"));
+        assert_eq!(parse("GET http://host#top HTTP/1.1\r\n\r\n").as_deref(), Some("/"));
+        assert_eq!(parse("GET /plain?x=http://a?b HTTP/1.1\r\n\r\n").as_deref(), Some("/plain?x=http://a?b"));
+        assert_eq!(Request::origin_form("http://host?x=1"), "/");
+        assert_eq!(Request::origin_form("/files"), "/files");
+    }
+
+    #[test]
+    fn absolute_form_and_connect() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), echo_path);
+
+        let res = exchange(addr, b"GET http://example.com?x=1 HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.ends_with("\r\n\r\nGET /?x=1"));
+
+        let res = exchange(addr, b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{res}");
+    }
+
+    #[test]
+    fn idle_keepalive_closes_silently() {
+        let mut server = SalServer::new("127.0.0.1:0", 2);
+        server.set_keepalive_timeout(Duration::from_millis(200));
+        let addr = serve_result(server, |_| Ok(Response::new(200).body("ok").keep_alive(true)));
+
+        let start = Instant::now();
+        let res = exchange(addr, b"GET / HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(5)); // 未达到客户端超时，连接由服务器关闭
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(res.ends_with("\r\n\r\nok"), "{res}"); // 空闲超时不追加应答
+    }
+
+    #[test]
+    fn stalled_body_times_out_with_408() {
+        let mut server = SalServer::new("127.0.0.1:0", 2);
+        server.set_keepalive_timeout(Duration::from_secs(10));
+        server.set_read_timeout(Duration::from_millis(200));
+        let addr = serve_result(server, echo_path);
+
+        let start = Instant::now();
+        let res = exchange(addr, b"POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 10\r\n\r\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(res.starts_with("HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 408 Request Timeout\r\n"), "{res}");
+    }
+
+    fn handshake(version: &str) -> String {
+        format!(
+            "GET /ws HTTP/1.1\r\nHost: a\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: {version}\r\n\r\n"
+        )
+    }
+
+    #[test]
+    fn websocket_echo() {
+        let server = SalServer::new("127.0.0.1:0", 2).on_upgrade("/ws", |mut ws| {
+            while let Ok(Message::Text(x)) = ws.read() {
+                let _ = ws.send(Message::Text(x));
+            };
+        });
+        let addr = serve_result(server, echo_path);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(handshake("13").as_bytes()).unwrap();
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+        };
+        assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{head}");
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n")); // RFC 6455 示例
+
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x81, 0x80 | 5];
+        frame.extend(mask);
+        frame.extend(b"hello".iter().zip(mask.iter().cycle()).map(|(x, m)| x ^ m));
+        stream.write_all(&frame).unwrap();
+
+        let mut echo = [0u8; 7];
+        reader.read_exact(&mut echo).unwrap();
+        assert_eq!(&echo, b"\x81\x05hello"); // 服务端帧不加掩码
+    }
+
+    #[test]
+    fn websocket_version_mismatch_is_426() {
+        let server = SalServer::new("127.0.0.1:0", 2).on_upgrade("/ws", |_| panic!("upgraded"));
+        let addr = serve_result(server, echo_path);
+
+        let raw = handshake("8").replace("Host: a\r\n", "Host: a\r\nConnection: close\r\n");
+        let res = exchange(addr, raw.as_bytes());
+        assert!(res.starts_with("HTTP/1.1 426 Upgrade Required\r\n"), "{res}");
+        assert!(res.contains("Sec-WebSocket-Version: 13\r\n"));
+    }
+
+    #[test]
+    fn oversized_continue_body_is_413() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), echo_path);
+
+        let raw = format!("POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: {}\r\n\r\n", MAX_CONTINUE + 1);
+        let res = exchange(addr, raw.as_bytes());
+        assert!(res.starts_with("HTTP/1.1 413 Content Too Large\r\n"), "{res}"); // 不应答 `100 Continue`
+        assert!(res.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn rate_limit_burst_then_429() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2).with_rate_limit(1, 3), echo_path);
+
+        let raw = b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n";
+        for _ in 0..3 {
+            let res = exchange(addr, raw);
+            assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{res}");
+        }
+        let res = exchange(addr, raw);
+        assert!(res.starts_with("HTTP/1.1 429 Too Many Requests\r\n"), "{res}");
+    }
+
+    #[test]
+    fn basic_auth_gate() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2).require_basic_auth("admin", "p@ss"), echo_path);
+        let get = |auth: &str| exchange(addr, format!("GET /a HTTP/1.1\r\nHost: a\r\n{auth}Connection: close\r\n\r\n").as_bytes());
+
+        let res = get("Authorization: Basic YWRtaW46cEBzcw==\r\n");
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n") && res.ends_with("GET /a"), "{res}");
+
+        let res = get("Authorization: Basic YWRtaW46d3Jvbmc=\r\n");
+        assert!(res.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{res}");
+
+        let res = get("");
+        assert!(res.starts_with("HTTP/1.1 401 Unauthorized\r\n"), "{res}");
+        assert!(res.contains("\r\nWWW-Authenticate: Basic realm=\"SalServer\", charset=\"UTF-8\"\r\n"), "{res}");
+    }
+
+    #[test]
+    fn date_and_server_headers() {
+        let at = |secs| http_date(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(at(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(at(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+
+        let server = SalServer::new("127.0.0.1:0", 2).server_name("Saloxy/1.0");
+        let addr = serve_result(server, |request| match request.path {
+            "/own" => Ok(Response::new(200).header("Server", "Handler/2.0")),
+            _ => Ok(Response::new(200)),
+        });
+        let get = |path: &str| exchange(addr, format!("GET {path} HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n").as_bytes());
+
+        let res = get("/");
+        assert!(res.contains("\r\nServer: Saloxy/1.0\r\n"), "{res}");
+        let date = res.lines().find_map(|x| x.strip_prefix("Date: ")).unwrap();
+        assert!(date.len() == 29 && date.ends_with(" GMT") && date.as_bytes()[3] == b',', "{date}");
+
+        let res = get("/own");
+        assert!(res.contains("\r\nServer: Handler/2.0\r\n") && !res.contains("Saloxy"), "{res}");
+    }
+
+    #[test]
+    fn stream_three_chunks() {
+        let server = SalServer::new("127.0.0.1:0", 2);
+        let addr = local_addr(&server);
+        thread::spawn(move || server.route_stream(|_, writer| {
+            writer.header("Content-Type", "text/plain");
+            for chunk in ["one,", "two,", "three"] {
+                writer.write_chunk(chunk.as_bytes())?;
+            }
+            Ok(())
+        }));
+
+        let res = exchange(addr, b"GET /s HTTP/1.1\r\nHost: a\r\n\r\n");
+        let (head, rest) = res.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n") && head.contains("\r\nTransfer-Encoding: chunked"), "{head}");
+        assert_eq!(dechunk(rest), "one,two,three");
+    }
+
+    // 还原分块编码的主体，要求以结束分块结尾
+    fn dechunk(mut rest: &str) -> String {
+        let mut body = String::new();
+        loop {
+            let (size, tail) = rest.split_once("\r\n").unwrap();
+            let size = usize::from_str_radix(size, 16).unwrap();
+            body.push_str(&tail[..size]);
+            rest = tail[size..].strip_prefix("\r\n").unwrap();
+            if size == 0 {
+                break;
+            }
+        }
+        assert_eq!(rest, "");
+        body
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_request() {
+        let path = std::env::temp_dir().join(format!("sal-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = SalServer::bind_unix(&path, 2);
+        thread::spawn(move || server.route_result(|request| {
+            Ok(Response::new(200).body(format!("{} {}", request.path, request.peer)))
+        }));
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"GET /sock HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n").unwrap();
+        let mut res = String::new();
+        let _ = stream.read_to_string(&mut res);
+
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n"), "{res}");
+        assert!(res.ends_with("\r\n\r\n/sock 0.0.0.0:0"), "{res}");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn result_errors_map_to_status() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), |request| match request.path {
+            "/io" => Err(std::io::Error::other("disk on fire"))?,
+            "/teapot" => Err(HttpError::new(418, "short and stout")),
+            "/" => Ok(Response::new(200).body("root")),
+            _ => Err(HttpError::not_found()),
+        });
+        let get = |path: &str| exchange(addr, format!("GET {path} HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n").as_bytes());
+
+        let res = get("/io");
+        assert!(res.starts_with("HTTP/1.1 500 Internal Server Error\r\n") && res.ends_with("disk on fire"), "{res}");
+        let res = get("/missing");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"), "{res}");
+        let res = get("/teapot");
+        assert!(res.starts_with("HTTP/1.1 418 ") && res.ends_with("short and stout"), "{res}");
+        assert!(get("/").starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn handler_sees_peer() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), |request| {
+            Ok(Response::new(200).body(request.peer.to_string()))
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let local = stream.local_addr().unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+
+        assert!(local.ip().is_loopback());
+        assert!(res.ends_with(&format!("\r\n\r\n{local}")), "{res}");
+    }
+
+    #[test]
+    fn forwarded_for_client_ip() {
+        let ip = |head: &str, trust_proxy: bool| {
+            let text = format!("GET / HTTP/1.1\r\n{head}\r\n");
+            Request::parse(&text, PEER).unwrap().client_ip(trust_proxy)
+        };
+
+        let valid = "X-Forwarded-For: 203.0.113.7, 10.0.0.1\r\n";
+        assert_eq!(ip(valid, true), "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(ip(valid, false), PEER.ip()); // 未信任代理时忽略
+        assert_eq!(ip("x-forwarded-for: 2001:db8::1\r\n", true), "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(ip("X-Forwarded-For: not-an-ip\r\n", true), PEER.ip());
+        assert_eq!(ip("Host: a\r\n", true), PEER.ip());
+    }
+
+    #[test]
+    fn http10_closes_http11_persists() {
+        let mut server = SalServer::new("127.0.0.1:0", 2);
+        server.set_keepalive_timeout(Duration::from_millis(300));
+        let addr = serve_result(server, |_| Ok(Response::new(200).body("ok").keep_alive(true)));
+
+        let res = exchange(addr, b"GET / HTTP/1.0\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.0 200 OK\r\n"), "{res}");
+        assert!(res.contains("Connection: close\r\n"));
+
+        // `HTTP/1.1` 保持连接，可在同一连接上发送第二个请求
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        let mut buf = [0; 1024];
+        let len = stream.read(&mut buf).unwrap();
+        let res = String::from_utf8_lossy(&buf[..len]).into_owned();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n") && res.contains("Connection: keep-alive\r\n"), "{res}");
+
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n").unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n") && res.ends_with("\r\n\r\nok"), "{res}");
+    }
+
+    #[test]
+    fn immediate_disconnect_is_silent() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), echo_path);
+
+        // 未发送任何数据即关闭写入端：不应答 `520`，直接关闭连接 (因此也不会记录写出失败)
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).unwrap();
+        assert!(res.is_empty(), "{}", String::from_utf8_lossy(&res));
+
+        drop(TcpStream::connect(addr).unwrap()); // 连接后立即断开
+        let res = exchange(addr, b"GET /next HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+        assert!(res.ends_with("\r\n\r\nGET /next"), "{res}");
+    }
+
+    #[test]
+    fn continue_before_body() {
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), |request| {
+            Ok(Response::new(200).body(request.body.to_string()))
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(b"POST / HTTP/1.1\r\nHost: a\r\nExpect: 100-continue\r\nContent-Length: 5\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut buf = [0; 25]; // 收到 `100 Continue` 后才发送主体
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"HTTP/1.1 100 Continue\r\n\r\n");
+        stream.write_all(b"hello").unwrap();
+
+        let mut res = String::new();
+        stream.read_to_string(&mut res).unwrap();
+        assert!(res.starts_with("HTTP/1.1 200 OK\r\n") && res.ends_with("\r\n\r\nhello"), "{res}");
+    }
+
+    // 以 `Router` 分发一个没有头部的请求，返回 (状态码, 主体)
+    fn dispatch(router: &Router, method: &str, path: &str) -> (u16, String) {
+        let text = format!("{method} {path} HTTP/1.1\r\nHost: a\r\n\r\n");
+        let res = router.handle(Request::parse(&text, PEER).unwrap()).unwrap_or_else(Response::from);
+        (res.status, String::from_utf8_lossy(&res.body).into_owned())
+    }
+
+    #[test]
+    fn trailing_slash_modes() {
+        let routes = |strict| Router::new()
+            .route("GET", "/files", |_| Ok(Response::new(200).body("files")))
+            .route("GET", "/users/:id", |request| Ok(Response::new(200).body(request.param("id").unwrap_or_default())))
+            .strict_trailing_slash(strict);
+
+        let router = routes(false);
+        assert_eq!(dispatch(&router, "GET", "/files"), (200, "files".into()));
+        assert_eq!(dispatch(&router, "GET", "/files/"), (200, "files".into()));
+        assert_eq!(dispatch(&router, "GET", "/files/?a=1"), (200, "files".into()));
+        assert_eq!(dispatch(&router, "GET", "/users/7/"), (200, "7".into()));
+        assert_eq!(dispatch(&router, "GET", "/users/").0, 404); // 末尾的 `/` 不作为空的参数值
+
+        let router = routes(true);
+        assert_eq!(dispatch(&router, "GET", "/files"), (200, "files".into()));
+        assert_eq!(dispatch(&router, "GET", "/files/").0, 404);
+        assert_eq!(dispatch(&router, "GET", "/users/7"), (200, "7".into()));
+        assert_eq!(dispatch(&router, "GET", "/users/7/").0, 404);
+    }
+
+    #[test]
+    fn wildcard_precedence_and_capture() {
+        let router = Router::new()
+            .route("GET", "/static/*path", |request| Ok(Response::new(200).body(format!("wild {}", request.param("path").unwrap_or_default()))))
+            .route("GET", "/static/:name", |request| Ok(Response::new(200).body(format!("param {}", request.param("name").unwrap_or_default()))))
+            .route("GET", "/static/index.html", |_| Ok(Response::new(200).body("exact")))
+            .route("GET", "/*rest", |request| Ok(Response::new(200).body(format!("root {}", request.param("rest").unwrap_or_default()))));
+
+        // 精确 > 参数 > 通配，与注册顺序无关
+        assert_eq!(dispatch(&router, "GET", "/static/index.html").1, "exact");
+        assert_eq!(dispatch(&router, "GET", "/static/app.js").1, "param app.js");
+        assert_eq!(dispatch(&router, "GET", "/static/css/a/b.css?v=1").1, "wild css/a/b.css");
+        assert_eq!(dispatch(&router, "GET", "/static/").1, "wild "); // 通配段可为空
+        assert_eq!(dispatch(&router, "GET", "/other/x/y").1, "root other/x/y");
+    }
+
+    #[test]
+    fn head_from_get_route() {
+        let server = SalServer::new("127.0.0.1:0", 2);
+        let addr = local_addr(&server);
+        let router = Router::new().route("GET", "/doc", |_| {
+            Ok(Response::new(200).header("Content-Type", "text/plain").header("X-Sal", "1").body("hello"))
+        });
+        thread::spawn(move || server.route_router(router));
+
+        // 除 `Date` 与 `X-Request-Id` 外头部相同，顺序不限
+        let head = |res: &str| {
+            let mut head: Vec<String> = res.split("\r\n\r\n").next().unwrap_or_default().lines()
+                .filter(|x| !x.starts_with("Date: ") && !x.starts_with("X-Request-Id: "))
+                .map(String::from)
+                .collect();
+            head.sort();
+            head
+        };
+
+        let get = exchange(addr, b"GET /doc HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+        let res = exchange(addr, b"HEAD /doc HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+        assert!(get.ends_with("\r\n\r\nhello"), "{get}");
+        assert!(res.ends_with("\r\n\r\n"), "{res}");
+        assert_eq!(head(&res), head(&get));
+        assert!(res.contains("\r\nContent-Length: 5\r\n"));
+    }
+
+    #[test]
+    fn sse_two_events() {
+        let server = SalServer::new("127.0.0.1:0", 2);
+        let addr = local_addr(&server);
+        thread::spawn(move || server.route_stream(|_, writer| {
+            writer.sse_event(Some("progress"), "1/2")?;
+            writer.sse_event(None, "Done\r\nBye")?;
+            Ok(())
+        }));
+
+        let res = exchange(addr, b"GET /events HTTP/1.1\r\nHost: a\r\n\r\n");
+        let (head, rest) = res.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains("\r\nContent-Type: text/event-stream; charset=utf-8"), "{head}");
+        assert!(head.contains("\r\nCache-Control: no-cache"));
+
+        let body = dechunk(rest);
+        let events: Vec<&str> = body.split_terminator("\n\n").collect();
+        assert_eq!(events, ["event: progress\ndata: 1/2", "data: Done\ndata: Bye"]);
+    }
+
+    #[test]
+    fn handler_returns_file() {
+        let path = || std::env::temp_dir().join(format!("sal_server_file_{}.html", std::process::id()));
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        std::fs::write(path(), &data).unwrap();
+
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), move |request| match request.path {
+            "/missing" => Ok(Response::file(path().with_extension("none"))),
+            _ => Ok(Response::file(path())),
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /f HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n").unwrap();
+        let mut res = Vec::new();
+        stream.read_to_end(&mut res).unwrap();
+        let end = res.windows(4).position(|x| x == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&res[..end]);
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+        assert!(head.contains("\r\nContent-Type: text/html; charset=utf-8\r\n"));
+        assert!(head.contains("\r\nContent-Length: 100000\r\n"));
+        assert!(res[end + 4..] == data);
+
+        let res = exchange(addr, b"GET /missing HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 404 Not Found\r\n"), "{res}");
+        let _ = std::fs::remove_file(path());
+    }
+
+    #[test]
+    fn not_found_and_method_not_allowed() {
+        let router = Router::new()
+            .route("GET", "/items/:id", |_| Ok(Response::new(200)))
+            .route("DELETE", "/items/:id", |_| Ok(Response::new(204)))
+            .route("POST", "/items", |_| Ok(Response::new(201)));
+
+        assert_eq!(dispatch(&router, "GET", "/nothing").0, 404);
+        assert_eq!(dispatch(&router, "DELETE", "/items/7").0, 204);
+
+        let text = "PUT /items/7 HTTP/1.1\r\nHost: a\r\n\r\n";
+        let res = router.handle(Request::parse(text, PEER).unwrap()).unwrap_or_else(Response::from);
+        assert_eq!(res.status, 405);
+        assert!(res.head.contains(&(String::from("Allow"), String::from("GET, HEAD, DELETE"))), "{:?}", res.head);
+
+        let text = "GET /items HTTP/1.1\r\nHost: a\r\n\r\n";
+        let res = router.handle(Request::parse(text, PEER).unwrap()).unwrap_or_else(Response::from);
+        assert!(res.status == 405 && res.head.contains(&(String::from("Allow"), String::from("POST"))));
+    }
+
+    #[test]
+    fn folded_header_and_trailer() {
+        assert!(Request::parse("GET / HTTP/1.1\r\nX-Long: a\r\n b\r\n\r\n", PEER).is_none());
+        assert!(Request::parse("GET / HTTP/1.1\r\nX-Long: a\r\n\tb\r\n\r\n", PEER).is_none());
+
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), |request| {
+            let trailer = request.head.keys().any(|x| x.eq_ignore_ascii_case("X-Trailer"));
+            Ok(Response::new(200).body(format!("{trailer} {:?}", request.body)))
+        });
+
+        let res = exchange(addr, b"GET / HTTP/1.1\r\nHost: a\r\nX-Long: a\r\n b\r\n\r\n");
+        assert!(res.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{res}");
+
+        // trailer 保留在主体中，不作为头部
+        let chunked = "3\r\nabc\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        let raw = format!("POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{chunked}");
+        let res = exchange(addr, raw.as_bytes());
+        assert!(res.ends_with(&format!("\r\n\r\nfalse {chunked:?}")), "{res}");
+    }
+
+    #[test]
+    fn request_id_matches_log() {
+        static LOG: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        let addr = serve_result(SalServer::new("127.0.0.1:0", 2), |request| {
+            LOG.lock().unwrap().push(format!("[{}] {} {}", request.request_id(), request.method, request.path));
+            Ok(Response::new(200))
+        });
+        let id = |res: &str| res.lines().find_map(|x| x.strip_prefix("X-Request-Id: ")).map(String::from);
+
+        let res = exchange(addr, b"GET /a HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n");
+        let first = id(&res).unwrap();
+        let res = exchange(addr, b"GET /b HTTP/1.1\r\nHost: a\r\nX-Request-Id: trace-42\r\nConnection: close\r\n\r\n");
+        assert_eq!(id(&res).as_deref(), Some("trace-42")); // 沿用请求中的值
+
+        let log = LOG.lock().unwrap();
+        assert_eq!(*log, [format!("[{first}] GET /a"), String::from("[trace-42] GET /b")]);
+    }
+}
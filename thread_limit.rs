@@ -2,14 +2,24 @@
 //! 一个对线程数量进行限制的解决方法
 //!
 
-use std::sync::{Arc, Mutex, Condvar};
-use std::{thread, panic::{self, UnwindSafe}};
+#![allow(dead_code)] // 各模块仅使用其中一部分方法
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, Condvar, mpsc::{self, Receiver}};
+use std::{thread, panic::{self, UnwindSafe, AssertUnwindSafe}};
+
+type Job = Box<dyn FnOnce() + Send>;
 
 ///
 /// 线程限制结构体
 ///
 /// 用于声明 **最大线程数量（max_threads）**
 ///
+/// 工作线程按需创建并常驻复用，数量不超过 `max_threads`；
+/// 执行任务的线程命名为 `{name}-{i}`，`i` 为递增的序号，默认为 `sal-worker-0` `sal-worker-1` ...
+///
+/// 实例被丢弃后，空闲的工作线程将在处理完剩余任务后退出
+///
 /// **Example:**
 /// ```
 /// mod thread_limit;
@@ -17,8 +27,23 @@ use std::{thread, panic::{self, UnwindSafe}};
 /// ```
 ///
 pub struct ThreadLimit {
-    max_threads: usize,
-    condvar: Arc<(Mutex<usize>, Condvar)>,
+    shared: Arc<Shared>,
+    name: String,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    work: Condvar, // 有新任务，或需要退出的工作线程
+    free: Condvar, // 有工作线程空闲，或可以创建新的工作线程
+}
+
+struct State {
+    max: usize,
+    workers: usize, // 存活的工作线程
+    idle: usize,    // 等待任务的工作线程
+    spawned: usize, // 已创建的工作线程总数，用于命名
+    jobs: VecDeque<Job>,
+    closed: bool,
 }
 
 impl ThreadLimit {
@@ -37,12 +62,83 @@ impl ThreadLimit {
     /// ```
     ///
     pub fn new(max_threads: usize) -> Self {
+        let state = State { max: max_threads, workers: 0, idle: 0, spawned: 0, jobs: VecDeque::new(), closed: false };
+
         Self {
-            max_threads,
-            condvar: Arc::new((Mutex::new(0), Condvar::new())),
+            shared: Arc::new(Shared { state: Mutex::new(state), work: Condvar::new(), free: Condvar::new() }),
+            name: String::from("sal-worker"),
         }
     }
 
+    ///
+    /// 设置线程名称的前缀，默认为 `sal-worker`
+    ///
+    /// 便于在调试器、性能分析工具与 `panic` 信息中区分线程
+    ///
+    /// **Example:**
+    /// ```
+    /// mod thread_limit;
+    /// use thread_limit::ThreadLimit;
+    ///
+    /// let thread = ThreadLimit::new(4).with_name("http");
+    ///
+    /// thread.execute(|| println!("{:?}", std::thread::current().name())); // Some("http-0")
+    /// ```
+    ///
+    pub fn with_name(mut self, prefix: &str) -> Self {
+        self.name = prefix.to_string();
+        self
+    }
+
+    ///
+    /// 调整最大线程数量
+    ///
+    /// - 扩大时立即唤醒等待中的任务，按需创建新的工作线程
+    /// - 缩小时多余的工作线程在空闲后退出，正在执行的任务与已排队的任务均不受影响
+    ///
+    /// **Example:**
+    /// ```
+    /// mod thread_limit;
+    /// use thread_limit::ThreadLimit;
+    ///
+    /// let thread = ThreadLimit::new(4);
+    ///
+    /// thread.resize(8);
+    ///
+    /// assert_eq!(thread.max_threads(), 8);
+    /// ```
+    ///
+    pub fn resize(&self, new_size: usize) {
+        self.shared.lock().max = new_size;
+
+        self.shared.free.notify_all();
+        self.shared.work.notify_all();
+    }
+
+    ///
+    /// 获取最大线程数量
+    ///
+    pub fn max_threads(&self) -> usize {
+        self.shared.lock().max
+    }
+
+    ///
+    /// 获取存活的工作线程数量，包括空闲的线程
+    ///
+    /// > 注意，缩小后多余的线程需待空闲后退出，期间可能大于 `max_threads`
+    ///
+    pub fn workers(&self) -> usize {
+        self.shared.lock().workers
+    }
+
+    ///
+    /// 获取正在执行任务的线程数量
+    ///
+    pub fn running(&self) -> usize {
+        let state = self.shared.lock();
+        state.workers - state.idle
+    }
+
     ///
     /// 在所给定的线程数量之内执行任务
     ///
@@ -58,33 +154,212 @@ impl ThreadLimit {
     ///
     /// `f` - 要执行的任务闭包，必须满足 FnOnce() + Send + 'static + UnwindSafe 特征
     ///
+    /// 所有线程均忙碌且已达到最大数量时，将阻塞至有线程空闲；
+    ///
     /// 请处理好函数 `f` 的错误，以免影响线程的进行；
     ///
     /// 若函数 `f` 执行中出现无法恢复的错误，也不会影响线程的回收，保证服务可用。
     ///
     pub fn execute<F: FnOnce() + Send + 'static + UnwindSafe>(&self, f: F) {
-        let (lock, cvar) = &*self.condvar;
-        let mut count = lock.lock().expect("Failed to acquire mutex lock");
+        let mut state = self.shared.lock();
+
+        loop {
+            if state.idle > state.jobs.len() { // 交给空闲的工作线程
+                state.jobs.push_back(Box::new(f));
+                self.shared.work.notify_one();
+                return;
+            };
+
+            if state.workers < state.max {
+                state.workers += 1;
+                state.spawned += 1;
+                let name = format!("{}-{}", self.name, state.spawned - 1);
+                drop(state);
+
+                let shared = Arc::clone(&self.shared);
+                thread::Builder::new()
+                    .name(name)
+                    .spawn(move || Shared::work(shared, Box::new(f)))
+                    .expect("Failed to spawn thread");
+                return;
+            };
+
+            state = self.shared.free.wait(state).expect("Failed to wait on condition variable");
+        };
+    }
+
+    ///
+    /// 在所给定的线程数量之内执行任务，并返回接收任务结果的通道
+    ///
+    /// **Example:**
+    /// ```
+    /// mod thread_limit;
+    /// use thread_limit::ThreadLimit;
+    ///
+    /// let thread = ThreadLimit::new(4);
+    ///
+    /// let rx = thread.execute_with_result(|| (1..=100).sum::<u32>());
+    ///
+    /// assert_eq!(rx.recv(), Ok(5050));
+    /// ```
+    ///
+    /// `f` - 要执行的任务闭包，必须满足 FnOnce() -> T + Send + 'static 特征
+    ///
+    /// 若函数 `f` 执行中出现无法恢复的错误，通道将被关闭，`recv` 返回 `Err(RecvError)`。
+    ///
+    pub fn execute_with_result<T, F>(&self, f: F) -> Receiver<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        self.execute(AssertUnwindSafe(move || {
+            let _ = tx.send(f());
+        }));
+
+        rx
+    }
+}
+
+impl Drop for ThreadLimit {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.work.notify_all();
+    }
+}
+
+impl Shared {
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Failed to acquire mutex lock")
+    }
+
+    // 工作线程：执行首个任务后等待新任务，超出最大数量或实例被丢弃时退出
+    fn work(shared: Arc<Shared>, first: Job) {
+        let mut job = first;
 
-        while *count >= self.max_threads {
-            count = cvar.wait(count).expect("Failed to wait on condition variable");
+        loop {
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+
+            let mut state = shared.lock();
+            state.idle += 1;
+            shared.free.notify_one();
+
+            job = loop {
+                if let Some(x) = state.jobs.pop_front() { // 先处理已排队的任务，避免任务被遗留
+                    state.idle -= 1;
+                    break x;
+                };
+
+                if state.workers > state.max || state.closed {
+                    state.idle -= 1;
+                    state.workers -= 1;
+                    return;
+                };
+
+                state = shared.work.wait(state).expect("Failed to wait on condition variable");
+            };
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    // 阻塞至 `open` 被调用的任务
+    #[derive(Clone, Default)]
+    struct Gate(Arc<(Mutex<bool>, Condvar)>);
+
+    impl Gate {
+        fn wait(&self) {
+            let (lock, cvar) = &*self.0;
+            let _open = cvar.wait_while(lock.lock().unwrap(), |x| !*x).unwrap();
+        }
+
+        fn open(&self) {
+            *self.0.0.lock().unwrap() = true;
+            self.0.1.notify_all();
+        }
+    }
+
+    fn eventually(f: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if f() {
+                return true;
+            };
+            thread::sleep(Duration::from_millis(5));
+        };
+        false
+    }
+
+    #[test]
+    fn worker_names() {
+        let name = |thread: &ThreadLimit| {
+            thread.execute_with_result(|| thread::current().name().map(String::from)).recv().unwrap()
         };
 
-        *count += 1;
-        drop(count);
+        assert!(name(&ThreadLimit::new(2)).is_some_and(|x| x.starts_with("sal-worker-")));
+        assert!(name(&ThreadLimit::new(2).with_name("http")).is_some_and(|x| x.starts_with("http-")));
+    }
+
+    #[test]
+    fn result_and_panic() {
+        let thread = ThreadLimit::new(2);
+
+        let rx = thread.execute_with_result(|| (1..=100).sum::<u32>());
+        assert_eq!(rx.recv(), Ok(5050));
 
-        let condvar_clone = Arc::clone(&self.condvar);
+        let rx = thread.execute_with_result(|| -> u32 { panic!("task panicked") });
+        assert!(rx.recv().is_err()); // 通道被关闭
 
-        thread::spawn(move || {
+        let rx = thread.execute_with_result(|| 42); // 线程池仍可用
+        assert_eq!(rx.recv(), Ok(42));
+    }
+
+    #[test]
+    fn resize_spawns_and_retires() {
+        let thread = ThreadLimit::new(2);
+        let gate = Gate::default();
+        for _ in 0..2 {
+            let gate = gate.clone();
+            thread.execute(move || gate.wait());
+        };
+        assert_eq!((thread.workers(), thread.running()), (2, 2));
 
-            if let Err(_) = panic::catch_unwind(|| f()) {};
+        thread.resize(4); // 不扩大时，以下任务将阻塞
+        for _ in 0..2 {
+            let gate = gate.clone();
+            thread.execute(move || gate.wait());
+        };
+        assert_eq!((thread.workers(), thread.running(), thread.max_threads()), (4, 4, 4));
 
-            let (lock, cvar) = &*condvar_clone;
-            let mut count = lock.lock().expect("Failed to acquire mutex lock");
-            *count -= 1;
-            cvar.notify_one();
+        thread.resize(1); // 正在执行的任务不受影响
+        assert_eq!(thread.workers(), 4);
+        gate.open();
+        assert!(eventually(|| thread.workers() == 1));
+        assert_eq!(thread.running(), 0);
 
-        });
+        let names: Vec<_> = (0..3)
+            .map(|_| thread.execute_with_result(|| thread::current().name().map(String::from)))
+            .map(|x| x.recv().unwrap())
+            .collect();
+        assert!(names.windows(2).all(|x| x[0] == x[1])); // 由剩余的一个线程复用
+        assert_eq!(thread.workers(), 1);
+    }
+
+    #[test]
+    fn drop_retires_idle_workers() {
+        let thread = ThreadLimit::new(3);
+        let shared = Arc::clone(&thread.shared);
+        for _ in 0..3 {
+            thread.execute(|| thread::sleep(Duration::from_millis(20)));
+        };
 
+        drop(thread);
+        assert!(eventually(|| shared.lock().workers == 0));
     }
 }
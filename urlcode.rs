@@ -0,0 +1,77 @@
+//!
+//! 一个简易的URL编解码方案 (Percent-Encoding)
+//!
+
+///
+/// 对字符串进行URL编码
+///
+/// 除 `A-Z a-z 0-9 - _ . ~` 之外的字节均编码为 `%XX`
+///
+/// **Example:**
+/// ```
+/// mod urlcode;
+///
+/// assert_eq!(urlcode::encode("b c&d"), "b%20c%26d");
+/// ```
+///
+pub fn encode(data: &str) -> String {
+    let mut res = String::with_capacity(data.len());
+
+    for byte in data.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                res.push(byte as char)
+            }
+            _ => res.push_str(&format!("%{byte:02X}")),
+        };
+    }
+
+    res
+}
+
+///
+/// 对URL编码的字符串进行解码
+///
+/// 返回一个 `Option` 枚举
+/// - Some(String): 解码后的字符串
+/// - None: 存在非法的 `%XX` 序列，或解码结果不是合法的 `UTF-8`
+///
+/// > 注意，`+` 将被解码为空格
+///
+/// **Example:**
+/// ```
+/// mod urlcode;
+///
+/// assert_eq!(urlcode::decode("b%20c%26d"), Some(String::from("b c&d")));
+/// ```
+///
+#[allow(dead_code)]
+pub fn decode(data: &str) -> Option<String> {
+    let data = data.as_bytes();
+    let mut res = Vec::with_capacity(data.len());
+
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'%' => {
+                let hex = data.get(i + 1..i + 3)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let hex = std::str::from_utf8(hex).ok()?;
+                res.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                res.push(b' ');
+                i += 1;
+            }
+            x => {
+                res.push(x);
+                i += 1;
+            }
+        };
+    }
+
+    String::from_utf8(res).ok()
+}
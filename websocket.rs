@@ -0,0 +1,223 @@
+//!
+//! 一个简易的 `WebSocket` (RFC 6455) 服务端实现
+//!
+
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use super::crypto;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+pub const VERSION: &str = "13"; // 支持的 `Sec-WebSocket-Version`
+const MAX_PAYLOAD: u64 = 16 * 1024 * 1024; // 单条消息的最大长度
+
+///
+/// `WebSocket` 消息枚举
+///
+/// - Message::Text => 文本消息
+/// - Message::Binary => 二进制消息
+/// - Message::Ping => `Ping` 控制帧，读取时已自动应答 `Pong`
+/// - Message::Pong => `Pong` 控制帧
+/// - Message::Close => 连接关闭，读取时已自动应答 `Close`
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+///
+/// `WebSocket` 连接结构体
+///
+/// 由 `SalServer::on_upgrade` 在握手完成后交给处理函数
+///
+/// **Example:**
+/// ```
+/// mod salfa_server;
+/// use salfa_server::{Message, WsStream};
+///
+/// fn echo(mut ws: WsStream) {
+///     while let Ok(msg) = ws.read() {
+///         match msg {
+///             Message::Text(_) | Message::Binary(_) => { let _ = ws.send(msg); },
+///             Message::Close => break,
+///             _ => {},
+///         };
+///     };
+/// }
+/// ```
+///
+pub struct WsStream {
+    stream: TcpStream,
+    closed: bool,
+}
+
+impl WsStream {
+    pub(super) fn new(stream: TcpStream) -> WsStream {
+        WsStream { stream, closed: false }
+    }
+
+    ///
+    /// 读取一条消息，分片消息将自动合并
+    ///
+    /// 返回一个 `Result` 枚举
+    /// - Ok(Message)
+    /// - Err(std::io::Error): 连接断开，或收到非法的帧
+    ///
+    pub fn read(&mut self) -> Result<Message> {
+        let mut message: Option<(u8, Vec<u8>)> = None;
+
+        loop {
+            let (fin, opcode, payload) = self.read_frame()?;
+
+            match opcode {
+                0x0 => {
+                    let Some((_, data)) = message.as_mut() else {
+                        return Err(Error::new(ErrorKind::InvalidData, "Unexpected Continuation Frame!"));
+                    };
+                    if (data.len() + payload.len()) as u64 > MAX_PAYLOAD {
+                        return Err(Error::new(ErrorKind::InvalidData, "Message Too Large!"));
+                    };
+                    data.extend_from_slice(&payload);
+                },
+                0x1 | 0x2 => {
+                    if message.is_some() {
+                        return Err(Error::new(ErrorKind::InvalidData, "Unfinished Fragmented Message!"));
+                    };
+                    message = Some((opcode, payload));
+                },
+                0x8 => {
+                    if !self.closed {
+                        self.closed = true;
+                        let _ = self.write_frame(0x8, payload.get(..2).unwrap_or_default());
+                    };
+                    return Ok(Message::Close);
+                },
+                0x9 => { // 控制帧可能穿插在分片消息之间
+                    self.write_frame(0xA, &payload)?;
+                    if message.is_none() {
+                        return Ok(Message::Ping(payload));
+                    };
+                    continue;
+                },
+                0xA => {
+                    if message.is_none() {
+                        return Ok(Message::Pong(payload));
+                    };
+                    continue;
+                },
+                _ => return Err(Error::new(ErrorKind::InvalidData, "Unsupported Opcode!")),
+            };
+
+            if fin {
+                return match message {
+                    Some((0x1, data)) => match String::from_utf8(data) {
+                        Ok(x) => Ok(Message::Text(x)),
+                        Err(_) => Err(Error::new(ErrorKind::InvalidData, "Invalid UTF-8 Text!")),
+                    },
+                    Some((_, data)) => Ok(Message::Binary(data)),
+                    None => Err(Error::other(format!("Unknown: {}", line!()))),
+                };
+            };
+        };
+    }
+
+    ///
+    /// 发送一条消息
+    ///
+    /// > 注意，发送 `Message::Close` 后，连接将无法继续发送消息
+    ///
+    pub fn send(&mut self, message: Message) -> Result<()> {
+        if self.closed {
+            return Err(Error::new(ErrorKind::NotConnected, "WebSocket Closed!"));
+        };
+
+        match message {
+            Message::Text(x) => self.write_frame(0x1, x.as_bytes()),
+            Message::Binary(x) => self.write_frame(0x2, &x),
+            Message::Ping(x) => self.write_frame(0x9, &x),
+            Message::Pong(x) => self.write_frame(0xA, &x),
+            Message::Close => {
+                self.closed = true;
+                self.write_frame(0x8, &1000u16.to_be_bytes())
+            },
+        }
+    }
+
+    ///
+    /// 获取客户端地址
+    ///
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    fn read_frame(&mut self) -> Result<(bool, u8, Vec<u8>)> {
+        let mut head = [0u8; 2];
+        self.stream.read_exact(&mut head)?;
+
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut buf = [0u8; 2];
+                self.stream.read_exact(&mut buf)?;
+                u16::from_be_bytes(buf) as u64
+            },
+            127 => {
+                let mut buf = [0u8; 8];
+                self.stream.read_exact(&mut buf)?;
+                u64::from_be_bytes(buf)
+            },
+            x => x as u64,
+        };
+
+        if !masked { // 客户端发送的帧必须带有掩码
+            return Err(Error::new(ErrorKind::InvalidData, "Unmasked Client Frame!"));
+        };
+        if len > MAX_PAYLOAD {
+            return Err(Error::new(ErrorKind::InvalidData, "Message Too Large!"));
+        };
+
+        let mut mask = [0u8; 4];
+        self.stream.read_exact(&mut mask)?;
+
+        let mut payload = vec![0u8; len as usize];
+        self.stream.read_exact(&mut payload)?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        };
+
+        Ok((fin, opcode, payload))
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let mut frame = vec![0x80 | opcode];
+        match payload.len() {
+            x if x < 126 => frame.push(x as u8),
+            x if x <= u16::MAX as usize => {
+                frame.push(126);
+                frame.extend_from_slice(&(x as u16).to_be_bytes());
+            },
+            x => {
+                frame.push(127);
+                frame.extend_from_slice(&(x as u64).to_be_bytes());
+            },
+        };
+        frame.extend_from_slice(payload);
+
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+}
+
+///
+/// 根据 `Sec-WebSocket-Key` 计算 `Sec-WebSocket-Accept`
+///
+pub fn accept_key(key: &str) -> String {
+    crypto::base64_encode(&crypto::sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}